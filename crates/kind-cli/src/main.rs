@@ -1,10 +1,13 @@
 #![feature(panic_info_message)]
 
+use std::collections::BTreeSet;
 use std::path::PathBuf;
-use std::{fmt, io};
+use std::{fmt, fs, io};
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use kind_driver::session::Session;
+use kind_driver::target::TargetOptions;
+use kind_optimization::Options;
 
 use kind_report::data::{FileCache, Log};
 use kind_report::RenderConfig;
@@ -46,6 +49,15 @@ pub struct Cli {
     #[arg(short, long)]
     pub trace: bool,
 
+    /// Traces only the functions whose names match the pattern (`*` matches anything),
+    /// even if other functions are marked with `#trace`
+    #[arg(long, value_name = "PATTERN")]
+    pub trace_filter: Option<String>,
+
+    /// Prints the arguments of the traced functions up to this depth
+    #[arg(long, value_name = "DEPTH")]
+    pub trace_depth: Option<u64>,
+
     /// Only ascii characters in error messages
     #[arg(short, long)]
     pub ascii: bool,
@@ -73,10 +85,59 @@ pub struct Cli {
     #[arg(short, long, value_name = "FILE")]
     pub root: Option<PathBuf>,
 
+    /// Level of the optimizations over the compiled code (0 to 2)
+    #[arg(short = 'O', long, value_name = "LEVEL", default_value_t = 0)]
+    pub opt_level: u8,
+
+    /// Compiles the natural numbers to native numbers, that wrap
+    /// around at 2^60, in a target (like `hvm`) or keeps them as
+    /// constructors (like `kdl=false`), instead of the default of the
+    /// target. Can be given once for each target
+    #[arg(long, value_name = "TARGET[=BOOL]", value_parser = parse_native_nat)]
+    pub native_nat: Vec<(String, bool)>,
+
+    /// Inlines the functions that are not recursive and whose
+    /// body has at most this size, like the level 2 does
+    #[arg(long, value_name = "SIZE")]
+    pub inline_threshold: Option<usize>,
+
     #[command(subcommand)]
     pub command: Command,
 }
 
+/// Parses the name of a target with an optional `=true` or `=false`.
+fn parse_native_nat(arg: &str) -> Result<(String, bool), String> {
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name, value.parse::<bool>().map_err(|err| err.to_string())?),
+        None => (arg, true),
+    };
+    if driver::target::get_target(name).is_none() {
+        let names: Vec<_> = driver::target::targets().iter().map(|x| x.name()).collect();
+        return Err(format!("unknown target '{}', the targets are {}", name, names.join(", ")));
+    }
+    Ok((name.to_string(), value))
+}
+
+/// Format of the books printed by `show`, `to-kind-core` and `erase`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// The code of the book
+    #[default]
+    Text,
+    /// A JSON object with the tree of the book (see `kind_driver::json`)
+    Json,
+}
+
+/// Format of the graph printed by `graph`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// The DOT language of Graphviz
+    #[default]
+    Dot,
+    /// A JSON object with the nodes, edges and cycles (see `kind_driver::graph`)
+    Json,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Check a file
@@ -88,19 +149,43 @@ pub enum Command {
         file: String,
     },
 
+    /// Check a file in the Kind Core syntax printed by `to-kind-core`
+    #[clap(aliases = &["cc"])]
+    CheckCore { file: String },
+
     /// Evaluates Main on Kind2
     #[clap(aliases = &["er"])]
     Eval { file: String },
 
     #[clap(aliases = &["k"])]
-    ToKindCore { file: String },
+    ToKindCore {
+        file: String,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
 
     #[clap(aliases = &["e"])]
-    Erase { file: String },
+    Erase {
+        file: String,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
 
     /// Runs Main on the HVM
     #[clap(aliases = &["r"])]
-    Run { file: String },
+    Run {
+        file: String,
+        /// Prints the rewrites, calls and allocations of each function
+        /// and writes the stacks of calls in the collapsed format of flamegraphs.
+        #[arg(long)]
+        profile: bool,
+        /// File of the stacks of the profile (by default, the name of the file with `.folded`)
+        #[arg(long, value_name = "FILE")]
+        profile_output: Option<PathBuf>,
+        /// Arguments of the program, that it reads with `IO.do_arg`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
 
     /// Generates a checker (.hvm) for a file
     #[clap(aliases = &["gc"])]
@@ -113,12 +198,38 @@ pub enum Command {
 
     /// Stringifies a file
     #[clap(aliases = &["s"])]
-    Show { file: String },
+    Show {
+        file: String,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+
+    /// Lists the definitions and files that the entrypoints don't use
+    DeadCode { file: String },
+
+    /// Shows the shortest chain of uses that makes a definition relevant at runtime
+    WhyRelevant { name: String, file: String },
 
     /// Gets direct dependencies of a file
     #[clap(aliases = &["gd"])]
     GetDeps { file: String },
 
+    /// Prints the dependency graph of the definitions of a file
+    Graph {
+        file: String,
+        /// Only the definitions that this one uses, directly or not
+        #[arg(long, value_name = "NAME", conflicts_with = "rdeps")]
+        deps: Option<String>,
+        /// Only the definitions that use this one, directly or not
+        #[arg(long, value_name = "NAME")]
+        rdeps: Option<String>,
+        /// Only the definitions that are part of a cycle
+        #[arg(long)]
+        cycles: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: GraphFormat,
+    },
+
     /// Compiles a file to Kindelia (.kdl)
     #[clap(aliases = &["kdl"])]
     ToKDL {
@@ -126,11 +237,70 @@ pub enum Command {
         /// If given, a namespace that goes before each compiled name. Can be at most 10 charaters long.
         #[clap(long, aliases = &["ns"])]
         namespace: Option<String>,
+        /// File of the JSON manifest with the KDL name of each entry (by default, the name of the file with `.kdl.json`)
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+    },
+
+    /// Compiles a file to a Kindelia contract (.kdl) and prints a summary of it
+    Contract {
+        file: String,
+        /// If given, a namespace that goes before each compiled name. Can be at most 10 charaters long.
+        #[clap(long, aliases = &["ns"])]
+        namespace: Option<String>,
+        /// File of the contract (by default, the name of the file with `.kdl`)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Prints the estimated size, rules, duplications and name length of each KDL statement
+    KdlStats {
+        file: String,
+        /// If given, a namespace that goes before each compiled name. Can be at most 10 charaters long.
+        #[clap(long, aliases = &["ns"])]
+        namespace: Option<String>,
+        /// Maximum size in bytes of a statement
+        #[arg(long, value_name = "BYTES")]
+        max_size: Option<u64>,
+        /// Maximum size in bytes of the whole file
+        #[arg(long, value_name = "BYTES")]
+        max_total_size: Option<u64>,
+        /// Maximum number of rules of a function
+        #[arg(long)]
+        max_rules: Option<u64>,
+        /// Maximum number of duplications of a statement
+        #[arg(long)]
+        max_dups: Option<u64>,
+        /// Maximum length of the name of a statement
+        #[arg(long)]
+        max_name_len: Option<u64>,
+        /// Reports the budgets that are exceeded as errors instead of warnings
+        #[arg(long)]
+        deny: bool,
     },
 
     /// Compiles a file to HVM (.hvm)
     #[clap(aliases = &["hvm"])]
     ToHVM { file: String },
+
+    /// Compiles a file to JavaScript (.mjs)
+    #[clap(aliases = &["js"])]
+    ToJS { file: String },
+
+    /// Compiles a file with a target and writes the generated files to a directory
+    #[clap(aliases = &["b"])]
+    Build {
+        file: String,
+        /// Name of the target (hvm, kdl or js)
+        #[clap(long)]
+        target: String,
+        /// Directory of the generated files
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        output: PathBuf,
+        /// If given, a namespace that goes before each compiled name (KDL only).
+        #[clap(long, aliases = &["ns"])]
+        namespace: Option<String>,
+    },
 }
 
 /// Helper structure to use stderr as fmt::Write
@@ -181,6 +351,19 @@ pub fn run_in_session<T>(
     )
 }
 
+/// Prints a book of a stage of the compiler in the chosen format.
+pub fn show_book<T: fmt::Display + serde::Serialize>(
+    session: &Session,
+    format: Format,
+    stage: &str,
+    book: &T,
+) -> anyhow::Result<String> {
+    match format {
+        Format::Text => Ok(book.to_string()),
+        Format::Json => Ok(format!("{}\n", driver::json::book_to_json(session, stage, book)?)),
+    }
+}
+
 pub fn run_cli(config: Cli) -> anyhow::Result<()> {
     kind_report::check_if_colors_are_supported(config.no_color);
 
@@ -207,6 +390,20 @@ pub fn run_cli(config: Cli) -> anyhow::Result<()> {
         entrypoints.push(res.clone())
     }
 
+    let mut optimizations = Options::from_level(config.opt_level);
+
+    if config.inline_threshold.is_some() {
+        optimizations.inline_threshold = config.inline_threshold;
+    }
+
+    let target_options = TargetOptions {
+        trace: config.trace,
+        trace_filter: config.trace_filter,
+        trace_depth: config.trace_depth,
+        native_nat: config.native_nat.into_iter().collect(),
+        ..Default::default()
+    };
+
     match config.command {
         Command::Check { file, coverage } => {
             run_in_session(
@@ -216,6 +413,7 @@ pub fn run_cli(config: Cli) -> anyhow::Result<()> {
                 false,
                 false,
                 &mut |session| {
+                    session.optimizations = optimizations;
                     let (_, rewrites) = driver::type_check_book(
                         session,
                         &PathBuf::from(file.clone()),
@@ -230,6 +428,22 @@ pub fn run_cli(config: Cli) -> anyhow::Result<()> {
                 },
             )?;
         }
+        Command::CheckCore { file } => {
+            run_in_session(
+                &render_config,
+                root,
+                file.clone(),
+                false,
+                false,
+                &mut |session| {
+                    let rewrites = driver::check_core_book(session, &PathBuf::from(file.clone()), config.tids)?;
+
+                    render_to_stderr(&render_config, session, &Log::Rewrites(rewrites));
+
+                    Ok(())
+                },
+            )?;
+        }
         Command::ToHVM { file } => {
             let result = run_in_session(
                 &render_config,
@@ -238,18 +452,80 @@ pub fn run_cli(config: Cli) -> anyhow::Result<()> {
                 true,
                 false,
                 &mut |session| {
-                    let book = driver::erase_book(
-                        session,
-                        &PathBuf::from(file.clone()),
-                        entrypoints.clone(),
-                    )?;
-                    Ok(driver::compile_book_to_hvm(book, config.trace))
+                    session.optimizations = optimizations;
+                    let path = PathBuf::from(file.clone());
+                    let files =
+                        driver::build_book(session, &path, "hvm", &target_options, entrypoints.clone())?;
+                    Ok(files.into_iter().map(|x| x.contents).collect::<String>())
+                },
+            )?;
+
+            println!("{}", result);
+        }
+        Command::ToJS { file } => {
+            let result = run_in_session(
+                &render_config,
+                root,
+                file.clone(),
+                true,
+                false,
+                &mut |session| {
+                    session.optimizations = optimizations;
+                    let path = PathBuf::from(file.clone());
+                    let files =
+                        driver::build_book(session, &path, "js", &target_options, entrypoints.clone())?;
+                    Ok(files.into_iter().map(|x| x.contents).collect::<String>())
                 },
             )?;
 
             println!("{}", result);
         }
-        Command::Run { file } => {
+        Command::Build {
+            file,
+            target,
+            output,
+            namespace,
+        } => {
+            let path = PathBuf::from(file.clone());
+
+            let options = TargetOptions {
+                name: path
+                    .file_stem()
+                    .map(|x| x.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "main".to_string()),
+                namespace: namespace.unwrap_or_default(),
+                source_map: true,
+                ..target_options
+            };
+
+            let files = run_in_session(
+                &render_config,
+                root,
+                file,
+                true,
+                false,
+                &mut |session| {
+                    session.optimizations = optimizations;
+                    driver::build_book(session, &path, &target, &options, entrypoints.clone())
+                },
+            )?;
+
+            fs::create_dir_all(&output)?;
+
+            for file in files {
+                fs::write(output.join(&file.path), file.contents)?;
+            }
+        }
+        Command::Run {
+            file,
+            profile,
+            profile_output,
+            args,
+        } => {
+            let mut stacks = None;
+            let mut effectful = false;
+            let mut exit_code = None;
+
             let res = run_in_session(
                 &render_config,
                 root,
@@ -257,45 +533,83 @@ pub fn run_cli(config: Cli) -> anyhow::Result<()> {
                 true,
                 false,
                 &mut |session| {
+                    session.optimizations = optimizations;
                     let path = PathBuf::from(file.clone());
                     let book = driver::erase_book(session, &path, entrypoints.clone())?;
                     driver::check_main_entry(session, &book)?;
-                    let book = driver::compile_book_to_hvm(book, config.trace);
-                    let (result, rewrites) = driver::execute_file(&book.to_string(), config.tids)?;
-
-                    render_to_stderr(&render_config, session, &Log::Rewrites(rewrites));
 
-                    Ok(result)
+                    if profile {
+                        let (result, rewrites, profile) =
+                            driver::profile_book(session, book, &target_options)?;
+                        render_to_stderr(&render_config, session, &Log::Rewrites(rewrites));
+                        eprintln!("{}", profile);
+                        stacks = Some(profile.collapsed());
+                        Ok(result)
+                    } else {
+                        let (stdin, mut stdout) = (std::io::stdin(), std::io::stdout());
+                        let mut host = driver::io::Host {
+                            args: args.clone(),
+                            input: &mut stdin.lock(),
+                            output: &mut stdout,
+                        };
+                        let outcome = driver::run_io_book(
+                            session,
+                            book,
+                            &target_options,
+                            config.tids,
+                            &mut host,
+                        )?;
+                        render_to_stderr(&render_config, session, &Log::Rewrites(outcome.rewrites));
+                        effectful = outcome.effectful;
+                        exit_code = outcome.exit_code;
+                        Ok(outcome.result)
+                    }
                 },
             )?;
-            println!("{}", res);
+
+            if let Some(stacks) = stacks {
+                let output = profile_output
+                    .unwrap_or_else(|| PathBuf::from(&file).with_extension("folded"));
+                fs::write(output, stacks)?;
+            }
+
+            if let Some(code) = exit_code {
+                std::process::exit(code as i32);
+            }
+
+            if !effectful {
+                println!("{}", res);
+            }
         }
-        Command::Show { file } => {
-            run_in_session(
+        Command::Show { file, format } => {
+            let res = run_in_session(
                 &render_config,
                 root,
                 file.clone(),
                 true,
                 false,
-                &mut |session| driver::to_book(session, &PathBuf::from(file.clone())),
-            )
-            .map(|res| {
-                print!("{}", res);
-                res
-            })?;
+                &mut |session| {
+                    let book = driver::to_book(session, &PathBuf::from(file.clone()))?;
+                    show_book(session, format, "concrete", &book)
+                },
+            )?;
+            print!("{}", res);
         }
-        Command::ToKindCore { file } => {
+        Command::ToKindCore { file, format } => {
             let res = run_in_session(
                 &render_config,
                 root,
                 file.clone(),
                 true,
                 false,
-                &mut |session| driver::desugar_book(session, &PathBuf::from(file.clone())),
+                &mut |session| {
+                    let book = driver::desugar_book(session, &PathBuf::from(file.clone()))?;
+                    show_book(session, format, "desugared", &book)
+                },
             )?;
             print!("{}", res);
         }
-        Command::Erase { file } => {
+        Command::Erase { file, format } => {
             let res = run_in_session(
                 &render_config,
                 root,
@@ -303,7 +617,10 @@ pub fn run_cli(config: Cli) -> anyhow::Result<()> {
                 true,
                 false,
                 &mut |session| {
-                    driver::erase_book(session, &PathBuf::from(file.clone()), entrypoints.clone())
+                    session.optimizations = optimizations;
+                    let path = PathBuf::from(file.clone());
+                    let book = driver::erase_book(session, &path, entrypoints.clone())?;
+                    show_book(session, format, "untyped", &book)
                 },
             )?;
             print!("{}", res);
@@ -338,24 +655,163 @@ pub fn run_cli(config: Cli) -> anyhow::Result<()> {
             )?;
             println!("{}", res);
         }
-        Command::ToKDL { file, namespace } => {
-            let res = run_in_session(
+        Command::ToKDL {
+            file,
+            namespace,
+            manifest,
+        } => {
+            let (res, names) = run_in_session(
                 &render_config,
                 root,
                 file.clone(),
                 true,
                 false,
                 &mut |session| {
-                    driver::compile_book_to_kdl(
-                        &PathBuf::from(file.clone()),
-                        session,
-                        &namespace.clone().unwrap_or("".to_string()),
-                        entrypoints.clone(),
-                    )
+                    session.optimizations = optimizations;
+                    let path = PathBuf::from(file.clone());
+                    let options = TargetOptions {
+                        namespace: namespace.clone().unwrap_or_default(),
+                        ..target_options.clone()
+                    };
+                    let mut files =
+                        driver::build_book(session, &path, "kdl", &options, entrypoints.clone())?;
+                    let names = driver::take_output(session, &mut files, "kdl", "kdl.json")?;
+                    Ok((files.into_iter().map(|x| x.contents).collect::<String>(), names))
                 },
             )?;
+            let output = manifest.unwrap_or_else(|| PathBuf::from(&file).with_extension("kdl.json"));
+            fs::write(output, names.contents)?;
             println!("{}", res);
         }
+        Command::Contract {
+            file,
+            namespace,
+            output,
+        } => {
+            let contract = run_in_session(
+                &render_config,
+                root,
+                file.clone(),
+                true,
+                false,
+                &mut |session| {
+                    session.optimizations = optimizations;
+                    let path = PathBuf::from(file.clone());
+                    let options = TargetOptions {
+                        namespace: namespace.clone().unwrap_or_default(),
+                        ..target_options.clone()
+                    };
+                    driver::build_contract(session, &path, &options, entrypoints.clone())
+                },
+            )?;
+            let output = output.unwrap_or_else(|| PathBuf::from(&file).with_extension("kdl"));
+            fs::write(&output, contract.bundle())?;
+            fs::write(output.with_extension("kdl.json"), contract.file.manifest.to_json())?;
+            print!("{}", contract);
+        }
+        Command::KdlStats {
+            file,
+            namespace,
+            max_size,
+            max_total_size,
+            max_rules,
+            max_dups,
+            max_name_len,
+            deny,
+        } => {
+            let budgets = kind_target_kdl::Budgets {
+                max_size,
+                max_total_size,
+                max_rules,
+                max_dups,
+                max_name_len,
+                deny,
+            };
+            run_in_session(
+                &render_config,
+                root,
+                file.clone(),
+                true,
+                false,
+                &mut |session| {
+                    session.optimizations = optimizations;
+                    let path = PathBuf::from(file.clone());
+                    let options = TargetOptions {
+                        namespace: namespace.clone().unwrap_or_default(),
+                        ..target_options.clone()
+                    };
+                    let stats =
+                        driver::kdl_stats(session, &path, &options, &budgets, entrypoints.clone())?;
+                    // Printed here so the table is shown even if a budget is exceeded.
+                    print!("{}", stats);
+                    Ok(())
+                },
+            )?;
+        }
+        Command::Graph {
+            file,
+            deps,
+            rdeps,
+            cycles,
+            format,
+        } => {
+            let res = run_in_session(
+                &render_config,
+                root,
+                file.clone(),
+                true,
+                false,
+                &mut |session| {
+                    let graph = driver::dependency_graph(session, &PathBuf::from(file.clone()))?;
+
+                    let mut nodes: BTreeSet<usize> = match (&deps, &rdeps) {
+                        (Some(name), _) => graph.reachable(driver::find_definition(session, &graph, name)?, false),
+                        (_, Some(name)) => graph.reachable(driver::find_definition(session, &graph, name)?, true),
+                        _ => (0..graph.names.len()).collect(),
+                    };
+
+                    if cycles {
+                        let in_cycles: BTreeSet<usize> = graph.cycles().into_iter().flatten().collect();
+                        nodes.retain(|x| in_cycles.contains(x));
+                    }
+
+                    match format {
+                        GraphFormat::Dot => Ok(graph.to_dot(&nodes)),
+                        GraphFormat::Json => Ok(format!("{}\n", graph.to_json(session, &nodes)?)),
+                    }
+                },
+            )?;
+            print!("{}", res);
+        }
+        Command::DeadCode { file } => {
+            let res = run_in_session(
+                &render_config,
+                root,
+                file.clone(),
+                true,
+                false,
+                &mut |session| driver::dead_code(session, &PathBuf::from(file.clone()), entrypoints.clone()),
+            )?;
+            print!("{}", res);
+        }
+        Command::WhyRelevant { name, file } => {
+            let res = run_in_session(
+                &render_config,
+                root,
+                file.clone(),
+                true,
+                false,
+                &mut |session| {
+                    driver::relevance_chain(session, &PathBuf::from(file.clone()), entrypoints.clone(), &name)
+                },
+            )?;
+
+            if let Some(chain) = res {
+                let mut names = vec![chain.first().map(|x| x.from.clone()).unwrap_or(name)];
+                names.extend(chain.into_iter().map(|x| x.to));
+                println!("{}", names.join(" -> "));
+            }
+        }
         Command::GetDeps { file } => {
             let res = run_in_session(
                 &render_config,
@@ -5,6 +5,10 @@ pub(crate) enum DeriveDiagnostic {
     CannotUseNamedVariable(Range),
     CannotUseAll(Range),
     InvalidReturnType(Range),
+    CannotDeriveForIndices(String, Range),
+    CannotDeriveForDependentField(String, Range),
+    CannotDeriveForParameters(String, Range),
+    CannotDeriveForFields(String, Range),
 }
 
 impl Diagnostic for DeriveDiagnostic {
@@ -13,6 +17,10 @@ impl Diagnostic for DeriveDiagnostic {
             DeriveDiagnostic::CannotUseNamedVariable(range) => Some(range.ctx),
             DeriveDiagnostic::CannotUseAll(range) => Some(range.ctx),
             DeriveDiagnostic::InvalidReturnType(range) => Some(range.ctx),
+            DeriveDiagnostic::CannotDeriveForIndices(_, range) => Some(range.ctx),
+            DeriveDiagnostic::CannotDeriveForDependentField(_, range) => Some(range.ctx),
+            DeriveDiagnostic::CannotDeriveForParameters(_, range) => Some(range.ctx),
+            DeriveDiagnostic::CannotDeriveForFields(_, range) => Some(range.ctx),
         }
     }
 
@@ -59,6 +67,62 @@ impl Diagnostic for DeriveDiagnostic {
                     no_code: false,
                     main: true,
                 }],
+            },
+            DeriveDiagnostic::CannotDeriveForIndices(name, range) => DiagnosticFrame {
+                code: 104,
+                severity: Severity::Error,
+                title: format!("Cannot derive '{}' for a type with indices", name),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            DeriveDiagnostic::CannotDeriveForDependentField(name, range) => DiagnosticFrame {
+                code: 105,
+                severity: Severity::Error,
+                title: format!("Cannot derive '{}' for a constructor with dependent fields", name),
+                subtitles: vec![],
+                hints: vec!["The type of a field cannot refer to the previous fields of the same constructor".to_string()],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "This field is referenced here".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            DeriveDiagnostic::CannotDeriveForParameters(name, range) => DiagnosticFrame {
+                code: 106,
+                severity: Severity::Error,
+                title: format!("Cannot derive '{}' for a type with parameters", name),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            DeriveDiagnostic::CannotDeriveForFields(name, range) => DiagnosticFrame {
+                code: 107,
+                severity: Severity::Error,
+                title: format!("Cannot derive '{}' for a constructor with fields", name),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
             }
         }
     }
@@ -68,7 +132,11 @@ impl Diagnostic for DeriveDiagnostic {
         match self {
             CannotUseNamedVariable(_)
             | CannotUseAll(_)
-            | InvalidReturnType(_) => Severity::Error,
+            | InvalidReturnType(_)
+            | CannotDeriveForIndices(_, _)
+            | CannotDeriveForDependentField(_, _)
+            | CannotDeriveForParameters(_, _)
+            | CannotDeriveForFields(_, _) => Severity::Error,
         }
     }
 }
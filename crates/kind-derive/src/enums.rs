@@ -0,0 +1,198 @@
+//! Module to derive the conversions between a sum type with
+//! only nullary constructors and 60 bit numbers.
+
+use kind_report::data::Diagnostic;
+use kind_span::Range;
+
+use kind_tree::concrete::expr::Expr;
+use kind_tree::concrete::pat::{Pat, PatKind};
+use kind_tree::concrete::*;
+use kind_tree::concrete::{self};
+use kind_tree::symbol::{Ident, QualifiedIdent};
+use kind_tree::telescope::Telescope;
+
+use crate::diagnostic::DeriveDiagnostic;
+
+type Errs = Vec<Box<dyn Diagnostic>>;
+
+/// Derives the conversions of an enumeration `T`. The value of each
+/// constructor is its position inside the declaration. It generates:
+///
+/// * `T.to_u60`: the number of a value.
+/// * `T.from_u60`: the value of a number, `Data.Maybe.none` if it's out of range.
+/// * `T.values`: a `Data.List` with all of the values in order.
+pub fn derive_enum(range: Range, sum: &SumTypeDecl) -> (Vec<concrete::Entry>, Errs) {
+    let mut errs: Errs = Vec::new();
+
+    if let Some(arg) = sum.indices.iter().next() {
+        errs.push(Box::new(DeriveDiagnostic::CannotDeriveForIndices(
+            "enum".to_string(),
+            arg.range,
+        )));
+    }
+
+    if let Some(arg) = sum.parameters.iter().next() {
+        errs.push(Box::new(DeriveDiagnostic::CannotDeriveForParameters(
+            "enum".to_string(),
+            arg.range,
+        )));
+    }
+
+    for cons in &sum.constructors {
+        if let Some(arg) = cons.args.iter().next() {
+            errs.push(Box::new(DeriveDiagnostic::CannotDeriveForFields(
+                "enum".to_string(),
+                arg.range,
+            )));
+        }
+    }
+
+    if !errs.is_empty() {
+        return (vec![], errs);
+    }
+
+    let mk_value = |cons: &Constructor| -> Box<Expr> {
+        Expr::cons(sum.name.add_segment(cons.name.to_str()), vec![], range)
+    };
+
+    let mk_num = |numb: u64| -> Box<Expr> {
+        Box::new(Expr {
+            data: ExprKind::Lit {
+                lit: Literal::NumU60(numb),
+            },
+            range,
+        })
+    };
+
+    let u60_type = Box::new(Expr {
+        data: ExprKind::Lit {
+            lit: Literal::NumTypeU60,
+        },
+        range,
+    });
+
+    let sum_type = Expr::cons(sum.name.clone(), vec![], range);
+
+    let mut entries = Vec::new();
+
+    // Enumerations without values cannot be converted to a number
+    // because a function without rules is a constructor.
+    if !sum.constructors.is_empty() {
+        let name = sum.name.add_segment("to_u60");
+
+        let rules = sum
+            .constructors
+            .iter()
+            .enumerate()
+            .map(|(i, cons)| {
+                Box::new(Rule {
+                    name: name.clone(),
+                    pats: vec![Box::new(Pat {
+                        data: PatKind::App(sum.name.add_segment(cons.name.to_str()), vec![]),
+                        range,
+                    })],
+                    body: mk_num(i as u64),
+                    range,
+                })
+            })
+            .collect();
+
+        entries.push(Entry {
+            name,
+            docs: vec![],
+            args: Telescope::new(vec![Argument {
+                hidden: false,
+                erased: false,
+                instance: false,
+                name: Ident::new_static("value", range),
+                typ: Some(sum_type.clone()),
+                range,
+            }]),
+            typ: u60_type.clone(),
+            rules,
+            range,
+            attrs: vec![],
+            generated_by: Some(sum.name.to_string()),
+        });
+    }
+
+    let maybe = QualifiedIdent::new_static("Data.Maybe", None, range);
+    let name = sum.name.add_segment("from_u60");
+
+    let mut rules: Vec<Box<Rule>> = sum
+        .constructors
+        .iter()
+        .enumerate()
+        .map(|(i, cons)| {
+            Box::new(Rule {
+                name: name.clone(),
+                pats: vec![Box::new(Pat {
+                    data: PatKind::U60(i as u64),
+                    range,
+                })],
+                body: Expr::cons(
+                    maybe.add_segment("some"),
+                    vec![Binding::Positional(mk_value(cons))],
+                    range,
+                ),
+                range,
+            })
+        })
+        .collect();
+
+    rules.push(Box::new(Rule {
+        name: name.clone(),
+        pats: vec![Pat::var(Ident::new_static("numb", range))],
+        body: Expr::cons(maybe.add_segment("none"), vec![], range),
+        range,
+    }));
+
+    entries.push(Entry {
+        name,
+        docs: vec![],
+        args: Telescope::new(vec![Argument {
+            hidden: false,
+            erased: false,
+            instance: false,
+            name: Ident::new_static("numb", range),
+            typ: Some(u60_type),
+            range,
+        }]),
+        typ: Expr::cons(maybe, vec![Binding::Positional(sum_type.clone())], range),
+        rules,
+        range,
+        attrs: vec![],
+        generated_by: Some(sum.name.to_string()),
+    });
+
+    let name = sum.name.add_segment("values");
+
+    let list = Box::new(Expr {
+        data: ExprKind::List {
+            args: sum.constructors.iter().map(|x| *mk_value(x)).collect(),
+        },
+        range,
+    });
+
+    entries.push(Entry {
+        name: name.clone(),
+        docs: vec![],
+        args: Default::default(),
+        typ: Expr::cons(
+            QualifiedIdent::new_static("Data.List", None, range),
+            vec![Binding::Positional(sum_type)],
+            range,
+        ),
+        rules: vec![Box::new(Rule {
+            name,
+            pats: vec![],
+            body: list,
+            range,
+        })],
+        range,
+        attrs: vec![],
+        generated_by: Some(sum.name.to_string()),
+    });
+
+    (entries, errs)
+}
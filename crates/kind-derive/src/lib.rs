@@ -3,9 +3,11 @@
 //! Utility to derive functions from their definitions.
 
 pub mod diagnostic;
+pub mod enums;
 pub mod matching;
 pub mod open;
 pub mod subst;
 pub mod getters;
 pub mod setters;
-pub mod mutters;
\ No newline at end of file
+pub mod mutters;
+pub mod no_confusion;
\ No newline at end of file
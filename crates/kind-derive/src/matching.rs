@@ -55,6 +55,7 @@ pub fn derive_match(range: Range, sum: &SumTypeDecl) -> (concrete::Entry, Errs)
     types.push(Argument {
         hidden: false,
         erased: false,
+        instance: false,
         name: Ident::generate("scrutinee"),
         typ: Some(current_return_type.clone()),
         range,
@@ -80,6 +81,7 @@ pub fn derive_match(range: Range, sum: &SumTypeDecl) -> (concrete::Entry, Errs)
     types.push(Argument {
         hidden: false,
         erased: true,
+        instance: false,
         name: motive_ident.clone(),
         typ: Some(motive_type),
         range,
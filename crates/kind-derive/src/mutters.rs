@@ -35,6 +35,7 @@ pub fn derive_mutters(range: Range, rec: &RecordDecl) -> Vec<concrete::Entry> {
     types.push(Argument {
         hidden: false,
         erased: false,
+        instance: false,
         name: Ident::generate("scrutinee"),
         typ: Some(res_motive_ty.clone()),
         range,
@@ -89,6 +90,7 @@ pub fn derive_mutters(range: Range, rec: &RecordDecl) -> Vec<concrete::Entry> {
         types.push(Argument {
             hidden: false,
             erased: false,
+            instance: false,
             name: Ident::generate("mut"),
             typ: Some(Expr::all(
                 Ident::generate("_"),
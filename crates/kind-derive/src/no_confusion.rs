@@ -0,0 +1,539 @@
+//! Module to derive injectivity and disjointness lemmas
+//! for the constructors of a sum type declaration.
+
+use kind_report::data::Diagnostic;
+use kind_span::Range;
+
+use kind_tree::concrete::expr::Expr;
+use kind_tree::concrete::pat::{Pat, PatKind};
+use kind_tree::concrete::visitor::Visitor;
+use kind_tree::concrete::*;
+use kind_tree::concrete::{self};
+use kind_tree::symbol::{Ident, QualifiedIdent};
+
+use crate::diagnostic::DeriveDiagnostic;
+
+type Errs = Vec<Box<dyn Diagnostic>>;
+
+/// Collects the first occurrence of a set of names inside an expression.
+struct Occurs<'a> {
+    names: &'a [String],
+    found: Option<Range>,
+}
+
+impl<'a> Visitor for Occurs<'a> {
+    fn visit_ident(&mut self, ident: &mut Ident) {
+        if self.found.is_none() && self.names.iter().any(|x| x == ident.to_str()) {
+            self.found = Some(ident.range)
+        }
+    }
+}
+
+/// Names of the variables that are used on the left and on the
+/// right side of an equation between two constructors.
+fn side_names(cons: &Constructor, suffix: &str, range: Range) -> Vec<Ident> {
+    cons.args
+        .iter()
+        .map(|arg| Ident::new_static(&format!("{}_{}", arg.name, suffix), range))
+        .collect()
+}
+
+fn equal(typ: Box<Expr>, left: Box<Expr>, right: Box<Expr>, range: Range) -> Box<Expr> {
+    Expr::cons(
+        QualifiedIdent::new_static("Equal", None, range),
+        vec![
+            Binding::Positional(typ),
+            Binding::Positional(left),
+            Binding::Positional(right),
+        ],
+        range,
+    )
+}
+
+fn fun(
+    name: &QualifiedIdent,
+    args: impl IntoIterator<Item = Box<Expr>>,
+    range: Range,
+) -> Box<Expr> {
+    Expr::cons(
+        name.clone(),
+        args.into_iter().map(Binding::Positional).collect(),
+        range,
+    )
+}
+
+fn arrow(typ: Box<Expr>, body: Box<Expr>, range: Range) -> Box<Expr> {
+    Expr::all(Ident::generate("_"), typ, body, false, range)
+}
+
+fn implicit(name: Ident, typ: Box<Expr>, range: Range) -> Argument {
+    Argument {
+        hidden: true,
+        erased: true,
+        instance: false,
+        name,
+        typ: Some(typ),
+        range,
+    }
+}
+
+/// Derives the `no_confusion` family of a sum type. It generates:
+///
+/// * `T.no_confusion.type`: the type that an equality between two values
+///   of `T` implies. It's the conclusion if the constructors are different
+///   or a function from the equalities of all the fields otherwise.
+/// * `T.no_confusion.diag`: a proof of the diagonal of the type above.
+/// * `T.no_confusion`: the proof that every equality implies its `no_confusion.type`.
+/// * `T.C.f.inj`: an injectivity lemma for each field `f` of each constructor `C`.
+/// * `T.C.not_D`: a disjointness lemma for each pair of constructors `C` and `D`.
+///
+/// The lemmas are stated in terms of `Equal` and `Equal.match` so the
+/// library `Equal` must derive `match`.
+pub fn derive_no_confusion(range: Range, sum: &SumTypeDecl) -> (Vec<concrete::Entry>, Errs) {
+    let mut errs: Errs = Vec::new();
+
+    if let Some(arg) = sum.indices.iter().next() {
+        errs.push(Box::new(DeriveDiagnostic::CannotDeriveForIndices(
+            "no_confusion".to_string(),
+            arg.range,
+        )));
+        return (vec![], errs);
+    }
+
+    for cons in &sum.constructors {
+        let mut previous: Vec<String> = Vec::new();
+        for arg in cons.args.iter() {
+            if let Some(typ) = &arg.typ {
+                let mut occurs = Occurs {
+                    names: &previous,
+                    found: None,
+                };
+                occurs.visit_expr(&mut typ.clone());
+                if let Some(place) = occurs.found {
+                    errs.push(Box::new(DeriveDiagnostic::CannotDeriveForDependentField(
+                        "no_confusion".to_string(),
+                        place,
+                    )));
+                }
+            }
+            previous.push(arg.name.to_string());
+        }
+    }
+
+    if !errs.is_empty() {
+        return (vec![], errs);
+    }
+
+    let params: Vec<Box<Expr>> = sum
+        .parameters
+        .iter()
+        .map(|x| Expr::var(x.name.clone()))
+        .collect();
+
+    let param_args = sum.parameters.map(|x| x.to_implicit());
+
+    let sum_type = fun(&sum.name, params.clone(), range);
+
+    let cons_name = |cons: &Constructor| sum.name.add_segment(cons.name.to_str());
+
+    let field_type = |arg: &Argument| arg.typ.clone().unwrap_or_else(|| Expr::typ(range));
+
+    let instantiate = |cons: &Constructor, names: &[Ident]| {
+        let args = [
+            params.as_slice(),
+            &names.iter().cloned().map(Expr::var).collect::<Vec<_>>(),
+        ]
+        .concat();
+        fun(&cons_name(cons), args, range)
+    };
+
+    let pattern = |cons: &Constructor, names: &[Ident], suffix: &str| {
+        let params = sum
+            .parameters
+            .iter()
+            .map(|x| Pat::var(Ident::new_static(&format!("{}_p{}", x.name, suffix), range)));
+        let fields = names.iter().cloned().map(Pat::var);
+        Box::new(Pat {
+            data: PatKind::App(cons_name(cons), params.chain(fields).collect()),
+            range,
+        })
+    };
+
+    let param_pats = || {
+        sum.parameters
+            .iter()
+            .map(|x| Pat::var(x.name.clone()))
+            .collect::<Vec<_>>()
+    };
+
+    let motive_ident = Ident::new_static("p_", range);
+    let motive = Expr::var(motive_ident.clone());
+
+    let x_ident = Ident::new_static("x_", range);
+    let y_ident = Ident::new_static("y_", range);
+    let e_ident = Ident::new_static("e_", range);
+
+    let type_name = sum.name.add_segment("no_confusion").add_segment("type");
+    let diag_name = sum.name.add_segment("no_confusion").add_segment("diag");
+    let main_name = sum.name.add_segment("no_confusion");
+
+    let mut entries = Vec::new();
+
+    // T.no_confusion.type
+
+    let mut rules = Vec::new();
+
+    for left in &sum.constructors {
+        let left_names = side_names(left, "l", range);
+        for right in &sum.constructors {
+            let right_names = side_names(right, "r", range);
+
+            let body = if left.name == right.name {
+                let premise = left
+                    .args
+                    .iter()
+                    .zip(left_names.iter().zip(&right_names))
+                    .rfold(motive.clone(), |out, (arg, (l, r))| {
+                        let eq = equal(
+                            field_type(arg),
+                            Expr::var(l.clone()),
+                            Expr::var(r.clone()),
+                            range,
+                        );
+                        arrow(eq, out, range)
+                    });
+                arrow(premise, motive.clone(), range)
+            } else {
+                motive.clone()
+            };
+
+            let mut pats = param_pats();
+            pats.push(Pat::var(motive_ident.clone()));
+            pats.push(pattern(left, &left_names, "l"));
+            pats.push(pattern(right, &right_names, "r"));
+
+            rules.push(Box::new(Rule {
+                name: type_name.clone(),
+                pats,
+                body,
+                range: left.name.range,
+            }))
+        }
+    }
+
+    let mut args = param_args.clone();
+    args.push(Argument::new_explicit(
+        motive_ident.clone(),
+        Expr::typ(range),
+        range,
+    ));
+    args.push(Argument::new_explicit(
+        x_ident.clone(),
+        sum_type.clone(),
+        range,
+    ));
+    args.push(Argument::new_explicit(
+        y_ident.clone(),
+        sum_type.clone(),
+        range,
+    ));
+
+    entries.push(Entry {
+        name: type_name.clone(),
+        docs: vec![],
+        args,
+        typ: Expr::typ(range),
+        rules,
+        range,
+        attrs: vec![],
+        generated_by: Some(sum.name.to_string()),
+    });
+
+    // T.no_confusion.diag
+
+    let f_ident = Ident::new_static("f_", range);
+
+    let mut rules = Vec::new();
+
+    for cons in &sum.constructors {
+        let names = side_names(cons, "l", range);
+
+        let refls = cons
+            .args
+            .iter()
+            .map(|_| {
+                AppBinding::explicit(fun(
+                    &QualifiedIdent::new_static("Equal.refl", None, range),
+                    vec![],
+                    range,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let applied = if refls.is_empty() {
+            Expr::var(f_ident.clone())
+        } else {
+            Expr::app(Expr::var(f_ident.clone()), refls, range)
+        };
+
+        let mut pats = param_pats();
+        pats.push(Pat::var(motive_ident.clone()));
+        pats.push(pattern(cons, &names, "l"));
+
+        rules.push(Box::new(Rule {
+            name: diag_name.clone(),
+            pats,
+            body: Expr::lambda(f_ident.clone(), None, applied, false, range),
+            range: cons.name.range,
+        }))
+    }
+
+    let type_of = |x: Box<Expr>, y: Box<Expr>| {
+        let args = [params.as_slice(), &[motive.clone(), x, y]].concat();
+        fun(&type_name, args, range)
+    };
+
+    let mut args = param_args.clone();
+    args.push(Argument::new_explicit(
+        motive_ident.clone(),
+        Expr::typ(range),
+        range,
+    ));
+    args.push(Argument::new_explicit(
+        x_ident.clone(),
+        sum_type.clone(),
+        range,
+    ));
+
+    entries.push(Entry {
+        name: diag_name.clone(),
+        docs: vec![],
+        args,
+        typ: type_of(Expr::var(x_ident.clone()), Expr::var(x_ident.clone())),
+        rules,
+        range,
+        attrs: vec![],
+        generated_by: Some(sum.name.to_string()),
+    });
+
+    // T.no_confusion
+
+    let mut args = param_args.clone();
+    args.push(implicit(x_ident.clone(), sum_type.clone(), range));
+    args.push(implicit(y_ident.clone(), sum_type.clone(), range));
+    args.push(Argument::new_explicit(
+        motive_ident.clone(),
+        Expr::typ(range),
+        range,
+    ));
+    args.push(Argument::new_explicit(
+        e_ident.clone(),
+        equal(
+            sum_type.clone(),
+            Expr::var(x_ident.clone()),
+            Expr::var(y_ident.clone()),
+            range,
+        ),
+        range,
+    ));
+
+    let mut pats = param_pats();
+    pats.push(Pat::var(x_ident.clone()));
+    pats.push(Pat::var(y_ident.clone()));
+    pats.push(Pat::var(motive_ident.clone()));
+    pats.push(Pat::var(e_ident.clone()));
+
+    let y_ = y_ident.add_underscore();
+    let e_ = e_ident.add_underscore();
+
+    let elim_motive = Expr::lambda(
+        y_.clone(),
+        None,
+        Expr::lambda(
+            e_,
+            None,
+            type_of(Expr::var(x_ident.clone()), Expr::var(y_)),
+            false,
+            range,
+        ),
+        false,
+        range,
+    );
+
+    let diag = fun(
+        &diag_name,
+        [
+            params.as_slice(),
+            &[motive.clone(), Expr::var(x_ident.clone())],
+        ]
+        .concat(),
+        range,
+    );
+
+    let body = fun(
+        &QualifiedIdent::new_static("Equal.match", None, range),
+        vec![Expr::var(e_ident.clone()), elim_motive, diag],
+        range,
+    );
+
+    entries.push(Entry {
+        name: main_name.clone(),
+        docs: vec![],
+        args,
+        typ: type_of(Expr::var(x_ident), Expr::var(y_ident)),
+        rules: vec![Box::new(Rule {
+            name: main_name.clone(),
+            pats,
+            body,
+            range,
+        })],
+        range,
+        attrs: vec![],
+        generated_by: Some(sum.name.to_string()),
+    });
+
+    // Arguments shared by the lemmas about a pair of constructors.
+
+    let lemma = |name: QualifiedIdent,
+                 left: &Constructor,
+                 right: &Constructor,
+                 extra: Vec<Argument>,
+                 typ: Box<Expr>,
+                 body: Box<Expr>| {
+        let left_names = side_names(left, "l", range);
+        let right_names = side_names(right, "r", range);
+
+        let mut args = param_args.clone();
+        let mut pats = param_pats();
+
+        for (arg, name) in left.args.iter().zip(&left_names) {
+            args.push(implicit(name.clone(), field_type(arg), range));
+            pats.push(Pat::var(name.clone()));
+        }
+
+        for (arg, name) in right.args.iter().zip(&right_names) {
+            args.push(implicit(name.clone(), field_type(arg), range));
+            pats.push(Pat::var(name.clone()));
+        }
+
+        let left_val = instantiate(left, &left_names);
+        let right_val = instantiate(right, &right_names);
+
+        args.push(Argument::new_explicit(
+            e_ident.clone(),
+            equal(sum_type.clone(), left_val, right_val, range),
+            range,
+        ));
+        pats.push(Pat::var(e_ident.clone()));
+
+        for arg in extra {
+            pats.push(Pat::var(arg.name.clone()));
+            args.push(arg);
+        }
+
+        let mut name = name;
+        name.range = left.name.range;
+
+        Entry {
+            name: name.clone(),
+            docs: vec![],
+            args,
+            typ,
+            rules: vec![Box::new(Rule {
+                name,
+                pats,
+                body,
+                range: left.name.range,
+            })],
+            range: left.name.range,
+            attrs: vec![],
+            generated_by: Some(sum.name.to_string()),
+        }
+    };
+
+    let confuse = |left: &Constructor, right: &Constructor, conclusion: Box<Expr>| {
+        let left_val = instantiate(left, &side_names(left, "l", range));
+        let right_val = instantiate(right, &side_names(right, "r", range));
+        let args = [
+            params.as_slice(),
+            &[left_val, right_val, conclusion, Expr::var(e_ident.clone())],
+        ]
+        .concat();
+        fun(&main_name, args, range)
+    };
+
+    // T.C.f.inj
+
+    for cons in &sum.constructors {
+        let left_names = side_names(cons, "l", range);
+        let right_names = side_names(cons, "r", range);
+        let eq_names: Vec<Ident> = cons
+            .args
+            .iter()
+            .map(|arg| Ident::new_static(&format!("{}_eq", arg.name), range))
+            .collect();
+
+        for (i, arg) in cons.args.iter().enumerate() {
+            let conclusion = equal(
+                field_type(arg),
+                Expr::var(left_names[i].clone()),
+                Expr::var(right_names[i].clone()),
+                range,
+            );
+
+            let projection = eq_names
+                .iter()
+                .rfold(Expr::var(eq_names[i].clone()), |out, name| {
+                    Expr::lambda(name.clone(), None, out, false, range)
+                });
+
+            let body = Expr::app(
+                confuse(cons, cons, conclusion.clone()),
+                vec![AppBinding::explicit(projection)],
+                range,
+            );
+
+            let name = cons_name(cons)
+                .add_segment(arg.name.to_str())
+                .add_segment("inj");
+
+            entries.push(lemma(name, cons, cons, vec![], conclusion, body));
+        }
+    }
+
+    // T.C.not_D
+
+    for (i, left) in sum.constructors.iter().enumerate() {
+        for right in &sum.constructors[i + 1..] {
+            let name = cons_name(left).add_segment(&format!("not_{}", right.name));
+
+            let extra = vec![Argument::new_explicit(
+                motive_ident.clone(),
+                Expr::typ(range),
+                range,
+            )];
+
+            let body = confuse(left, right, motive.clone());
+
+            entries.push(lemma(name, left, right, extra, motive.clone(), body));
+        }
+    }
+
+    (entries, errs)
+}
+
+/// Derives the `no_confusion` family of a record by treating it
+/// as a sum type with a single constructor.
+pub fn derive_no_confusion_rec(range: Range, rec: &RecordDecl) -> (Vec<concrete::Entry>, Errs) {
+    derive_no_confusion(
+        range,
+        &SumTypeDecl {
+            name: rec.name.clone(),
+            docs: rec.docs.clone(),
+            parameters: rec.parameters.clone(),
+            indices: Default::default(),
+            constructors: vec![rec.get_constructor()],
+            attrs: rec.attrs.clone(),
+        },
+    )
+}
@@ -62,6 +62,7 @@ pub fn derive_setters(range: Range, rec: &RecordDecl) -> Vec<concrete::Entry> {
     types.push(Argument {
         hidden: false,
         erased: false,
+        instance: false,
         name: Ident::generate("scrutinee"),
         typ: Some(res_motive_ty.clone()),
         range,
@@ -116,6 +117,7 @@ pub fn derive_setters(range: Range, rec: &RecordDecl) -> Vec<concrete::Entry> {
         types.push(Argument {
             hidden: false,
             erased: false,
+            instance: false,
             name: Ident::generate("set"),
             typ: Some(cons_typ.clone()),
             range,
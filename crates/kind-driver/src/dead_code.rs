@@ -0,0 +1,217 @@
+//! Report of the definitions that the entrypoints of a book don't
+//! use, grouped by the file that defines them. A definition is
+//! `type-only` if the entrypoints only use it in types, so the erasure
+//! removes it, and `unused` if they don't use it at all. The entries
+//! that are derived from a type are left out of the report. The files
+//! of the root directory that are not loaded are wholly unused.
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use fxhash::{FxHashMap, FxHashSet};
+use kind_pass::erasure::Reach;
+use kind_tree::desugared::Book;
+
+use crate::json::relative_path;
+use crate::session::Session;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EntryKind {
+    Type,
+    Constructor,
+    Definition,
+}
+
+impl Display for EntryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntryKind::Type => f.pad("type"),
+            EntryKind::Constructor => f.pad("constructor"),
+            EntryKind::Definition => f.pad("definition"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeadEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub reach: Reach,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeadFile {
+    pub path: String,
+    /// How the file is used if none of its definitions is relevant.
+    pub whole: Option<Reach>,
+    /// If the entrypoints load the file. The definitions of the files
+    /// that are not loaded are not listed.
+    pub loaded: bool,
+    /// The definitions that are not relevant, in the order of the file.
+    pub entries: Vec<DeadEntry>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DeadCode {
+    /// The files with definitions that are not relevant, sorted by path.
+    pub files: Vec<DeadFile>,
+}
+
+fn reach_label(reach: Reach) -> &'static str {
+    match reach {
+        Reach::Relevant => "relevant",
+        Reach::TypeOnly => "type-only",
+        Reach::Unused => "unused",
+    }
+}
+
+/// Collects the `.kind2` files under the directory.
+fn kind_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            kind_files(&path, files);
+        } else if path.extension().map(|x| x == "kind2").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+}
+
+impl DeadCode {
+    pub fn new(
+        session: &Session,
+        book: &Book,
+        reach: &FxHashMap<String, Reach>,
+        derived: &FxHashSet<String>,
+    ) -> anyhow::Result<DeadCode> {
+        let current = PathBuf::from(".").canonicalize()?;
+
+        let constructors: FxHashSet<&str> = book
+            .families
+            .values()
+            .flat_map(|family| family.constructors.iter().map(|x| x.to_str()))
+            .collect();
+
+        // The entries of each file with their positions and reaches.
+        let mut by_file: FxHashMap<usize, Vec<(usize, DeadEntry)>> = FxHashMap::default();
+
+        for (name, entry) in book
+            .entrs
+            .iter()
+            .filter(|(name, _)| !derived.contains(*name))
+        {
+            let kind = if book.families.contains_key(name) {
+                EntryKind::Type
+            } else if constructors.contains(name.as_str()) {
+                EntryKind::Constructor
+            } else {
+                EntryKind::Definition
+            };
+
+            let dead = DeadEntry {
+                name: name.clone(),
+                kind,
+                reach: reach.get(name).cloned().unwrap_or(Reach::Unused),
+            };
+
+            let range = entry.name.range;
+            by_file
+                .entry(range.ctx.0)
+                .or_default()
+                .push((range.start.index as usize, dead));
+        }
+
+        let mut files = Vec::new();
+
+        for (ctx, mut entries) in by_file {
+            entries.sort_by_key(|x| x.0);
+            let entries: Vec<DeadEntry> = entries.into_iter().map(|x| x.1).collect();
+
+            let whole = if entries.iter().all(|x| x.reach == Reach::Unused) {
+                Some(Reach::Unused)
+            } else if entries.iter().all(|x| x.reach != Reach::Relevant) {
+                Some(Reach::TypeOnly)
+            } else {
+                None
+            };
+
+            let entries: Vec<DeadEntry> = entries
+                .into_iter()
+                .filter(|x| x.reach != Reach::Relevant)
+                .collect();
+
+            if entries.is_empty() {
+                continue;
+            }
+
+            let path = session
+                .loaded_paths
+                .get(ctx)
+                .map(|path| relative_path(&current, path))
+                .unwrap_or_default();
+
+            files.push(DeadFile {
+                path,
+                whole,
+                loaded: true,
+                entries,
+            });
+        }
+
+        let mut root_files = Vec::new();
+        kind_files(&session.root, &mut root_files);
+
+        for path in root_files {
+            let Ok(path) = path.canonicalize() else {
+                continue;
+            };
+            if session.loaded_paths_map.contains_key(&path) {
+                continue;
+            }
+            files.push(DeadFile {
+                path: relative_path(&current, &path),
+                whole: Some(Reach::Unused),
+                loaded: false,
+                entries: Vec::new(),
+            });
+        }
+
+        files.sort_by(|x, y| x.path.cmp(&y.path));
+
+        Ok(DeadCode { files })
+    }
+}
+
+impl Display for DeadCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in &self.files {
+            match file.whole {
+                _ if !file.loaded => writeln!(
+                    f,
+                    "{} (the whole file is unused, it's not loaded)",
+                    file.path
+                )?,
+                Some(reach) => writeln!(
+                    f,
+                    "{} (the whole file is {})",
+                    file.path,
+                    reach_label(reach)
+                )?,
+                None => writeln!(f, "{}", file.path)?,
+            }
+            for entry in &file.entries {
+                writeln!(
+                    f,
+                    "  {:<10} {:<12} {}",
+                    reach_label(entry.reach),
+                    entry.kind,
+                    entry.name
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@
 use std::{path::PathBuf, fmt::Display, error::Error};
 
 use kind_report::{data::{Color, Diagnostic, DiagnosticFrame, Marker, Severity, Subtitle, Word}, RenderConfig};
+use kind_span::Range;
 use kind_tree::symbol::{Ident, QualifiedIdent};
 
 #[derive(Debug)]
@@ -25,6 +26,12 @@ pub(crate) enum DriverDiagnostic {
     MultiplePaths(QualifiedIdent, Vec<PathBuf>),
     DefinedMultipleTimes(QualifiedIdent, QualifiedIdent),
     ThereIsntAMain,
+    UnknownTarget(String, Vec<String>),
+    StuckApplication(String, String, String, usize, Range, Vec<Range>),
+    EffectFailed(String, String),
+    UnknownDefinition(String, Vec<String>),
+    RuntimeFailed(String),
+    MissingOutput(String, String),
 }
 
 impl Diagnostic for DriverDiagnostic {
@@ -32,6 +39,12 @@ impl Diagnostic for DriverDiagnostic {
         match self {
             DriverDiagnostic::CannotFindFile(_) => None,
             DriverDiagnostic::ThereIsntAMain => None,
+            DriverDiagnostic::UnknownTarget(_, _) => None,
+            DriverDiagnostic::EffectFailed(_, _) => None,
+            DriverDiagnostic::UnknownDefinition(_, _) => None,
+            DriverDiagnostic::RuntimeFailed(_) => None,
+            DriverDiagnostic::MissingOutput(_, _) => None,
+            DriverDiagnostic::StuckApplication(_, _, _, _, range, _) => Some(range.ctx),
             DriverDiagnostic::UnboundVariable(v, _) => Some(v[0].range.ctx),
             DriverDiagnostic::MultiplePaths(id, _) => Some(id.range.ctx),
             DriverDiagnostic::DefinedMultipleTimes(fst, _) => Some(fst.range.ctx),
@@ -121,6 +134,89 @@ impl Diagnostic for DriverDiagnostic {
                 hints: vec![],
                 positions: vec![],
             },
+            DriverDiagnostic::UnknownTarget(name, targets) => DiagnosticFrame {
+                code: 104,
+                severity: Severity::Error,
+                title: format!("Cannot find the target '{}'", name),
+                subtitles: vec![],
+                hints: vec![format!("The available targets are {}", targets.join(", "))],
+                positions: vec![],
+            },
+            DriverDiagnostic::StuckApplication(name, fun, term, times, range, rules) => DiagnosticFrame {
+                code: 105,
+                severity: Severity::Warning,
+                title: format!("The application of '{}' got stuck at runtime.", name),
+                subtitles: std::iter::once(Subtitle::Phrase(
+                    Color::Fst,
+                    vec![Word::White(format!("No rule matches {}", term))],
+                ))
+                .chain((*times > 1).then(|| {
+                    Subtitle::Phrase(
+                        Color::Fst,
+                        vec![Word::White(format!("It's in the result {} times", times))],
+                    )
+                }))
+                .collect(),
+                hints: if fun != name {
+                    vec![format!("The function '{}' is generated from '{}'", fun, name)]
+                } else {
+                    vec![]
+                },
+                positions: std::iter::once(Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Defined here!".to_string(),
+                    no_code: false,
+                    main: true,
+                })
+                .chain(rules.iter().map(|rule| Marker {
+                    position: *rule,
+                    color: Color::Snd,
+                    text: "This rule does not match".to_string(),
+                    no_code: false,
+                    main: false,
+                }))
+                .collect(),
+            },
+            DriverDiagnostic::EffectFailed(effect, reason) => DiagnosticFrame {
+                code: 106,
+                severity: Severity::Error,
+                title: format!("The effect '{}' failed at runtime.", effect),
+                subtitles: vec![Subtitle::Phrase(Color::Fst, vec![Word::White(reason.clone())])],
+                hints: vec![],
+                positions: vec![],
+            },
+            DriverDiagnostic::UnknownDefinition(name, suggestions) => DiagnosticFrame {
+                code: 107,
+                severity: Severity::Error,
+                title: format!("Cannot find the definition '{}' in the book.", name),
+                subtitles: vec![],
+                hints: if suggestions.is_empty() {
+                    vec![]
+                } else {
+                    vec![format!(
+                        "Maybe you're looking for {}",
+                        suggestions.iter().map(|x| format!("'{}'", x)).collect::<Vec<String>>().join(", ")
+                    )]
+                },
+                positions: vec![],
+            },
+            DriverDiagnostic::RuntimeFailed(reason) => DiagnosticFrame {
+                code: 108,
+                severity: Severity::Error,
+                title: "The HVM could not run the compiled program.".to_string(),
+                subtitles: vec![Subtitle::Phrase(Color::Fst, vec![Word::White(reason.clone())])],
+                hints: vec![],
+                positions: vec![],
+            },
+            DriverDiagnostic::MissingOutput(target, extension) => DiagnosticFrame {
+                code: 109,
+                severity: Severity::Error,
+                title: format!("The target '{}' did not generate a '.{}' file.", target, extension),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![],
+            },
         }
     }
 
@@ -131,7 +227,13 @@ impl Diagnostic for DriverDiagnostic {
             | UnboundVariable(_, _)
             | MultiplePaths(_, _)
             | DefinedMultipleTimes(_, _)
-            | ThereIsntAMain => Severity::Error
+            | ThereIsntAMain
+            | UnknownTarget(_, _)
+            | EffectFailed(_, _)
+            | UnknownDefinition(_, _)
+            | RuntimeFailed(_)
+            | MissingOutput(_, _) => Severity::Error,
+            StuckApplication(_, _, _, _, _, _) => Severity::Warning,
         }
     }
 }
@@ -0,0 +1,238 @@
+//! Graph of the dependencies between the definitions of a desugared
+//! book. There's an edge from a definition to each function, type or
+//! constructor that appears in its type, in the types of its arguments
+//! or in its rules, so it includes the definitions that are only used
+//! by types. The definitions made by the compiler, like the `match`
+//! of the types that derive it, are part of the graph too.
+//!
+//! The JSON output of `kind2 graph` is an object like:
+//!
+//! ```json
+//! { "schema": "kind2-graph", "version": 1,
+//!   "nodes": [{ "name": "Main", "file": "Main.kind2" }, ...],
+//!   "edges": [{ "from": "Main", "to": "Data.Nat.succ" }, ...],
+//!   "cycles": [["Even", "Odd"], ...] }
+//! ```
+//!
+//! The cycles are the groups of two or more definitions that use each
+//! other, so a function that only calls itself is not a cycle.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use fxhash::FxHashMap;
+use kind_pass::graph::strongly_connected_components;
+use kind_span::Range;
+use kind_tree::desugared::{Book, Entry, Expr, ExprKind};
+use serde_json::json;
+
+use crate::json::relative_path;
+use crate::session::Session;
+
+pub const SCHEMA_VERSION: u64 = 1;
+
+#[derive(Debug, Default)]
+pub struct DepGraph {
+    /// Names of the definitions in the order of the book.
+    pub names: Vec<String>,
+    pub ids: FxHashMap<String, usize>,
+    /// Range of the name of each definition.
+    pub ranges: Vec<Range>,
+    /// Definitions that each definition uses directly.
+    pub deps: Vec<BTreeSet<usize>>,
+    /// Definitions that use each definition directly.
+    pub rdeps: Vec<BTreeSet<usize>>,
+}
+
+fn collect_expr(ids: &FxHashMap<String, usize>, expr: &Expr, deps: &mut BTreeSet<usize>) {
+    use ExprKind::*;
+    match &expr.data {
+        Fun { name, args } | Ctr { name, args } => {
+            if let Some(id) = ids.get(name.to_str()) {
+                deps.insert(*id);
+            }
+            args.iter().for_each(|arg| collect_expr(ids, arg, deps));
+        }
+        All { typ, body, .. } => {
+            collect_expr(ids, typ, deps);
+            collect_expr(ids, body, deps);
+        }
+        Lambda { body, .. } => collect_expr(ids, body, deps),
+        App { fun, args } => {
+            collect_expr(ids, fun, deps);
+            args.iter()
+                .for_each(|arg| collect_expr(ids, &arg.data, deps));
+        }
+        Let { val, next, .. } => {
+            collect_expr(ids, val, deps);
+            collect_expr(ids, next, deps);
+        }
+        Ann { expr, typ } => {
+            collect_expr(ids, expr, deps);
+            collect_expr(ids, typ, deps);
+        }
+        Sub { expr, .. } => collect_expr(ids, expr, deps),
+        Binary { left, right, .. } => {
+            collect_expr(ids, left, deps);
+            collect_expr(ids, right, deps);
+        }
+        Var { .. }
+        | Typ
+        | NumTypeU60
+        | NumTypeF60
+        | NumU60 { .. }
+        | NumF60 { .. }
+        | Str { .. }
+        | Hole { .. }
+        | Hlp(_)
+        | Err => (),
+    }
+}
+
+fn collect_entry(ids: &FxHashMap<String, usize>, entry: &Entry) -> BTreeSet<usize> {
+    let mut deps = BTreeSet::new();
+    for arg in &entry.args {
+        collect_expr(ids, &arg.typ, &mut deps);
+    }
+    collect_expr(ids, &entry.typ, &mut deps);
+    for rule in &entry.rules {
+        rule.pats
+            .iter()
+            .for_each(|pat| collect_expr(ids, pat, &mut deps));
+        collect_expr(ids, &rule.body, &mut deps);
+    }
+    deps
+}
+
+/// Quotes a name as an identifier of the DOT language, where only the
+/// quotes and the backslashes have to be escaped.
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl DepGraph {
+    pub fn from_book(book: &Book) -> DepGraph {
+        let mut graph = DepGraph::default();
+
+        for (name, entry) in book.entrs.iter() {
+            graph.ids.insert(name.clone(), graph.names.len());
+            graph.names.push(name.clone());
+            graph.ranges.push(entry.name.range);
+        }
+
+        graph.rdeps = vec![BTreeSet::new(); graph.names.len()];
+        for entry in book.entrs.values() {
+            let id = graph.deps.len();
+            let deps = collect_entry(&graph.ids, entry);
+            for dep in &deps {
+                graph.rdeps[*dep].insert(id);
+            }
+            graph.deps.push(deps);
+        }
+
+        graph
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).cloned()
+    }
+
+    /// The definitions that can be reached from `from`, including
+    /// itself, by the dependencies or, if `reverse`, by the uses.
+    pub fn reachable(&self, from: usize, reverse: bool) -> BTreeSet<usize> {
+        let edges = if reverse { &self.rdeps } else { &self.deps };
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![from];
+        while let Some(node) = stack.pop() {
+            if seen.insert(node) {
+                stack.extend(edges[node].iter().filter(|x| !seen.contains(x)));
+            }
+        }
+        seen
+    }
+
+    /// The groups of two or more definitions that depend on each other.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let components =
+            strongly_connected_components(self.names.len(), |node| self.deps[node].iter().cloned());
+
+        let mut cycles: Vec<_> = components
+            .into_iter()
+            .filter(|x| x.len() > 1)
+            .map(|mut x| {
+                x.sort();
+                x
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    /// Names of the definitions of `nodes` and the edges between them.
+    fn subgraph(&self, nodes: &BTreeSet<usize>) -> (Vec<&str>, Vec<(&str, &str)>) {
+        let names = nodes.iter().map(|x| self.names[*x].as_str()).collect();
+        let mut edges = Vec::new();
+        for node in nodes {
+            for dep in self.deps[*node].iter().filter(|x| nodes.contains(x)) {
+                edges.push((self.names[*node].as_str(), self.names[*dep].as_str()));
+            }
+        }
+        (names, edges)
+    }
+
+    /// The subgraph of `nodes` in the DOT language of Graphviz.
+    pub fn to_dot(&self, nodes: &BTreeSet<usize>) -> String {
+        let (names, edges) = self.subgraph(nodes);
+        let mut out = String::from("digraph kind2 {\n");
+        for name in names {
+            writeln!(out, "  {};", dot_id(name)).unwrap();
+        }
+        for (from, to) in edges {
+            writeln!(out, "  {} -> {};", dot_id(from), dot_id(to)).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// The subgraph of `nodes` and its cycles in JSON, with the file of
+    /// each definition relative to the current directory.
+    pub fn to_json(&self, session: &Session, nodes: &BTreeSet<usize>) -> anyhow::Result<String> {
+        let current = PathBuf::from(".").canonicalize()?;
+        let (names, edges) = self.subgraph(nodes);
+
+        let nodes_json: Vec<_> = names
+            .iter()
+            .map(|name| {
+                let ctx = self.ranges[self.ids[*name]].ctx.0;
+                let file = session
+                    .loaded_paths
+                    .get(ctx)
+                    .map(|path| relative_path(&current, path));
+                json!({ "name": name, "file": file })
+            })
+            .collect();
+
+        let edges_json: Vec<_> = edges
+            .iter()
+            .map(|(from, to)| json!({ "from": from, "to": to }))
+            .collect();
+
+        let cycles_json: Vec<Vec<&str>> = self
+            .cycles()
+            .into_iter()
+            .filter(|cycle| cycle.iter().all(|x| nodes.contains(x)))
+            .map(|cycle| cycle.iter().map(|x| self.names[*x].as_str()).collect())
+            .collect();
+
+        let out = json!({
+            "schema": "kind2-graph",
+            "version": SCHEMA_VERSION,
+            "nodes": nodes_json,
+            "edges": edges_json,
+            "cycles": cycles_json,
+        });
+
+        Ok(serde_json::to_string_pretty(&out)?)
+    }
+}
@@ -0,0 +1,309 @@
+//! Interpreter of the effects of the programs that run in the HVM.
+//! `Main` is reduced to the weak head normal form and, while it's one
+//! of the constructors of the `IO` type, the effect is performed on the
+//! host and the evaluation continues with the continuation applied to
+//! its result. The constructors follow the ones of the HVM:
+//!
+//! ```kind2
+//! type IO (a: Type) {
+//!     done (value: a)
+//!     do_output (text: Data.String) (then: Data.U60 -> IO a)
+//!     do_input (then: Data.String -> IO a)
+//!     do_load (path: Data.String) (then: Data.String -> IO a)
+//!     do_store (path: Data.String) (text: Data.String) (then: Data.U60 -> IO a)
+//!     do_arg (index: Data.U60) (then: Data.Maybe Data.String -> IO a)
+//!     do_exit (code: Data.U60)
+//! }
+//! ```
+//!
+//! The results are built with the constructors of `Data.String` and
+//! `Data.Maybe`, that are added to the file by [run_file] because the
+//! program may not use all of them.
+
+use std::io::{BufRead, Write};
+
+use hvm::language;
+use hvm::runtime::{self, Heap, Program, Ptr};
+use hvm::syntax::Term;
+
+/// The world in which the effects are performed.
+pub struct Host<'a> {
+    /// Arguments that are given to the program by `IO.do_arg`.
+    pub args: Vec<String>,
+    pub input: &'a mut dyn BufRead,
+    pub output: &'a mut dyn Write,
+}
+
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    /// The normal form of `Main` or, if it's an effect, the value of
+    /// the `IO.done` that it finished with.
+    pub result: String,
+    pub rewrites: u64,
+    /// If `Main` performed effects.
+    pub effectful: bool,
+    /// The code of the `IO.do_exit` that stopped the program.
+    pub exit_code: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub enum EffectError {
+    /// The HVM file could not be loaded.
+    Runtime(String),
+    /// An effect could not be performed, with the reason.
+    Failed(String, String),
+}
+
+#[derive(Clone, Copy)]
+enum Effect {
+    Done,
+    Output,
+    Input,
+    Load,
+    Store,
+    Arg,
+    Exit,
+}
+
+const EFFECTS: [(&str, Effect); 7] = [
+    ("IO.done", Effect::Done),
+    ("IO.do_output", Effect::Output),
+    ("IO.do_input", Effect::Input),
+    ("IO.do_load", Effect::Load),
+    ("IO.do_store", Effect::Store),
+    ("IO.do_arg", Effect::Arg),
+    ("IO.do_exit", Effect::Exit),
+];
+
+struct Machine<'a> {
+    heap: &'a Heap,
+    prog: &'a Program,
+    tids: &'a [usize],
+    ids: &'a dyn Fn(&str) -> Option<u64>,
+}
+
+impl<'a> Machine<'a> {
+    /// Normalizes a field of a constructor and reads it as a string.
+    fn read_string(&self, term: Ptr, field: u64) -> Option<String> {
+        let loc = runtime::get_loc(term, field);
+        runtime::normalize(self.heap, self.prog, self.tids, loc, false);
+        as_string(&language::readback::as_term(self.heap, self.prog, loc))
+    }
+
+    fn read_number(&self, term: Ptr, field: u64) -> Option<u64> {
+        let loc = runtime::get_loc(term, field);
+        let value = runtime::reduce(self.heap, self.prog, self.tids, loc, false, false);
+        (runtime::get_tag(value) == runtime::U60).then(|| runtime::get_num(value))
+    }
+
+    /// The id of a constructor that the results are made of.
+    fn ctr(&self, name: &str) -> Result<u64, String> {
+        (self.ids)(name).ok_or_else(|| format!("The constructor '{}' is not in the program", name))
+    }
+
+    fn make_string(&self, text: &str) -> Result<Ptr, String> {
+        let (cons, tid) = (self.ctr("Data.String.cons")?, self.tids[0]);
+        let mut term = runtime::Ctr(self.ctr("Data.String.nil")?, 0);
+        for chr in text.chars().rev() {
+            let node = runtime::alloc(self.heap, tid, 2);
+            runtime::link(self.heap, node, runtime::U6O(chr as u64));
+            runtime::link(self.heap, node + 1, term);
+            term = runtime::Ctr(cons, node);
+        }
+        Ok(term)
+    }
+
+    fn make_maybe(&self, value: Option<Ptr>) -> Result<Ptr, String> {
+        match value {
+            None => Ok(runtime::Ctr(self.ctr("Data.Maybe.none")?, 0)),
+            Some(value) => {
+                let some = self.ctr("Data.Maybe.some")?;
+                let node = runtime::alloc(self.heap, self.tids[0], 1);
+                runtime::link(self.heap, node, value);
+                Ok(runtime::Ctr(some, node))
+            }
+        }
+    }
+
+    /// Replaces the effect at the host with the continuation in the
+    /// field `then` applied to the result, and frees the effect.
+    fn resume(&self, host: u64, term: Ptr, arity: u64, then: u64, result: Ptr) {
+        let tid = self.tids[0];
+        let app = runtime::alloc(self.heap, tid, 2);
+        runtime::link(self.heap, app, runtime::load_arg(self.heap, term, then));
+        runtime::link(self.heap, app + 1, result);
+        for field in (0..arity).filter(|x| *x != then) {
+            let arg = runtime::load_arg(self.heap, term, field);
+            runtime::collect(self.heap, &self.prog.aris, tid, arg);
+        }
+        runtime::free(self.heap, tid, runtime::get_loc(term, 0), arity);
+        runtime::link(self.heap, host, runtime::App(app));
+    }
+}
+
+/// Reads a term made of the constructors of `Data.String`.
+fn as_string(term: &Term) -> Option<String> {
+    let mut text = String::new();
+    let mut term = term;
+    loop {
+        match term {
+            Term::Ctr { name, args } if name == "Data.String.nil" && args.is_empty() => {
+                return Some(text)
+            }
+            Term::Ctr { name, args } if name == "Data.String.cons" && args.len() == 2 => {
+                match &*args[0] {
+                    Term::U6O { numb } => {
+                        text.push(char::from_u32(*numb as u32)?);
+                        term = &args[1];
+                    }
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn failed(effect: &str, reason: impl ToString) -> EffectError {
+    EffectError::Failed(effect.to_string(), reason.to_string())
+}
+
+/// A rule that uses the constructors of the results, so they have an
+/// id in the program even if it doesn't use them.
+const RESULTS: &str = "HVM_IO_RESULTS = (HVM_IO_RESULTS.all (Data.Maybe.none) (Data.Maybe.some 0) (Data.String.cons 0 (Data.String.nil)))";
+
+/// Runs the `Main` of a HVM file, performing its effects on the host.
+pub fn run_file(file: &str, tids: Option<usize>, world: &mut Host) -> Result<Outcome, EffectError> {
+    let file = language::syntax::read_file(&format!("{}\nHVM_MAIN_CALL = Main\n{}", file, RESULTS))
+        .map_err(EffectError::Runtime)?;
+    let book = language::rulebook::gen_rulebook(&file);
+
+    let mut prog = runtime::Program::new();
+    prog.add_book(&book);
+
+    let ids = |name: &str| book.name_to_id.get(name).cloned();
+    let effects: Vec<(u64, Effect)> = EFFECTS
+        .iter()
+        .filter_map(|(name, effect)| Some((*book.name_to_id.get(*name)?, *effect)))
+        .collect();
+
+    let heap = runtime::new_heap(runtime::default_heap_size(), tids.unwrap_or(1));
+    let tids = runtime::new_tids(tids.unwrap_or(1));
+
+    let machine = Machine {
+        heap: &heap,
+        prog: &prog,
+        tids: &tids,
+        ids: &ids,
+    };
+
+    let host = 0;
+    runtime::link(&heap, host, runtime::Fun(ids("HVM_MAIN_CALL").unwrap(), 0));
+
+    let mut effectful = false;
+    let mut exit_code = None;
+
+    loop {
+        let term = runtime::reduce(&heap, &prog, &tids, host, false, false);
+
+        let effect = (runtime::get_tag(term) == runtime::CTR)
+            .then(|| effects.iter().find(|x| x.0 == runtime::get_ext(term)))
+            .flatten();
+
+        let Some((_, effect)) = effect else {
+            break;
+        };
+
+        effectful = true;
+
+        match effect {
+            Effect::Done => {
+                runtime::link(&heap, host, runtime::load_arg(&heap, term, 0));
+                runtime::free(&heap, tids[0], runtime::get_loc(term, 0), 1);
+                break;
+            }
+            Effect::Output => {
+                let text = machine
+                    .read_string(term, 0)
+                    .ok_or_else(|| failed("IO.do_output", "The text is not a string"))?;
+                write!(world.output, "{}", text)
+                    .and_then(|_| world.output.flush())
+                    .map_err(|err| failed("IO.do_output", err))?;
+                machine.resume(host, term, 2, 1, runtime::U6O(0));
+            }
+            Effect::Input => {
+                let mut line = String::new();
+                world
+                    .input
+                    .read_line(&mut line)
+                    .map_err(|err| failed("IO.do_input", err))?;
+                let line = line.strip_suffix('\n').unwrap_or(&line);
+                let line = line.strip_suffix('\r').unwrap_or(line);
+                let line = machine
+                    .make_string(line)
+                    .map_err(|err| failed("IO.do_input", err))?;
+                machine.resume(host, term, 1, 0, line);
+            }
+            Effect::Load => {
+                let path = machine
+                    .read_string(term, 0)
+                    .ok_or_else(|| failed("IO.do_load", "The path is not a string"))?;
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|err| failed("IO.do_load", format!("{}: {}", path, err)))?;
+                let text = machine
+                    .make_string(&text)
+                    .map_err(|err| failed("IO.do_load", err))?;
+                machine.resume(host, term, 2, 1, text);
+            }
+            Effect::Store => {
+                let path = machine
+                    .read_string(term, 0)
+                    .ok_or_else(|| failed("IO.do_store", "The path is not a string"))?;
+                let text = machine
+                    .read_string(term, 1)
+                    .ok_or_else(|| failed("IO.do_store", "The text is not a string"))?;
+                std::fs::write(&path, text)
+                    .map_err(|err| failed("IO.do_store", format!("{}: {}", path, err)))?;
+                machine.resume(host, term, 3, 2, runtime::U6O(0));
+            }
+            Effect::Arg => {
+                let index = machine
+                    .read_number(term, 0)
+                    .ok_or_else(|| failed("IO.do_arg", "The index is not a number"))?;
+                let arg = world
+                    .args
+                    .get(index as usize)
+                    .map(|arg| machine.make_string(arg))
+                    .transpose()
+                    .and_then(|arg| machine.make_maybe(arg))
+                    .map_err(|err| failed("IO.do_arg", err))?;
+                machine.resume(host, term, 2, 1, arg);
+            }
+            Effect::Exit => {
+                exit_code = Some(
+                    machine
+                        .read_number(term, 0)
+                        .ok_or_else(|| failed("IO.do_exit", "The code is not a number"))?,
+                );
+                break;
+            }
+        }
+    }
+
+    let result = if exit_code.is_some() {
+        String::new()
+    } else {
+        runtime::normalize(&heap, &prog, &tids, host, false);
+        language::readback::as_code(&heap, &prog, host)
+    };
+
+    runtime::collect(&heap, &prog.aris, tids[0], runtime::load_ptr(&heap, host));
+    runtime::free(&heap, 0, 0, 1);
+
+    Ok(Outcome {
+        result,
+        rewrites: runtime::get_cost(&heap),
+        effectful,
+        exit_code,
+    })
+}
@@ -0,0 +1,91 @@
+//! Export of the books of the compiler as JSON, for the tools that
+//! need the view of the compiler of a project. The output is an object
+//! with the version of the schema, the stage of the book and the book:
+//!
+//! ```json
+//! { "schema": "kind2-book", "version": 1, "stage": "untyped", "book": { ... } }
+//! ```
+//!
+//! - `stage` is `concrete` for `kind2 show`, `desugared` for
+//!   `kind2 to-kind-core` and `untyped` for `kind2 erase`.
+//! - The book has the fields of `concrete::Book`, `desugared::Book` or
+//!   `untyped::Book`, with the same names. The maps keep their order,
+//!   except the hash maps, that are sorted by their keys.
+//! - Enums are objects with a single key, the name of the variant, like
+//!   `{"Var": {"name": ...}}`. Variants without fields are strings.
+//! - Names are strings and identifiers are objects with their `data`,
+//!   their `range` and if they were `generated` by the compiler. The
+//!   names of the top level definitions have a `root` and an `aux`
+//!   instead of `data`, where `aux` is `null` after the `use`s are expanded.
+//! - Ranges are `{"file": "Main.kind2", "start": {"line": 1, "column": 1},
+//!   "end": {...}}`, with the path relative to the current directory like
+//!   in the diagnostics and with lines and columns (in bytes) that start
+//!   at 1. The ranges of the code made by the compiler without a place in
+//!   the sources are `null`.
+//!
+//! The version changes every time that a field is renamed or removed.
+
+use std::path::{Path, PathBuf};
+
+use kind_report::report::LineGuide;
+use kind_span::{Location, Point, Pos, Range};
+use pathdiff::diff_paths;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::session::Session;
+
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// The path of a loaded file relative to `current`, like in the diagnostics.
+pub(crate) fn relative_path(current: &Path, path: &Path) -> String {
+    let path = diff_paths(path, current).unwrap_or(path.to_path_buf());
+    path.display().to_string()
+}
+
+/// Finds the file, lines and columns of the ranges with the files
+/// that the session loaded.
+fn locator(session: &Session, current: &Path) -> kind_span::Locator {
+    let files: Vec<(String, LineGuide)> = session
+        .loaded_paths
+        .iter()
+        .zip(session.loaded_sources.iter())
+        .map(|(path, code)| (relative_path(current, path), LineGuide::get(code)))
+        .collect();
+
+    Box::new(move |range: &Range| {
+        let (file, guide) = files.get(range.ctx.0)?;
+        let point = |pos: Pos| {
+            let point = guide.find(pos);
+            Point {
+                line: point.line + 1,
+                column: point.column + 1,
+            }
+        };
+        Some(Location {
+            file: file.clone(),
+            start: point(range.start),
+            end: point(range.end),
+        })
+    })
+}
+
+/// Serializes a book of a stage of the compiler with the ranges
+/// resolved by the files that the session loaded.
+pub fn book_to_json<T: Serialize>(
+    session: &Session,
+    stage: &str,
+    book: &T,
+) -> anyhow::Result<String> {
+    let current = PathBuf::from(".").canonicalize()?;
+    let book = kind_span::with_locator(locator(session, &current), || serde_json::to_value(book))?;
+
+    let out = json!({
+        "schema": "kind2-book",
+        "version": SCHEMA_VERSION,
+        "stage": stage,
+        "book": book,
+    });
+
+    Ok(serde_json::to_string_pretty(&out)?)
+}
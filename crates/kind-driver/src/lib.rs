@@ -8,17 +8,26 @@ use kind_report::{
 };
 use kind_span::SyntaxCtxIndex;
 
-use hvm::language::syntax as backend;
 use kind_tree::{concrete, desugared, untyped};
 use resolution::ResolutionError;
 use session::Session;
-use std::{path::PathBuf, time::Instant};
+use kind_target_hvm::{SourceMap, StuckApp};
+use profile::Profile;
+use target::{HvmTarget, KdlTarget, OutputFile, Sources, Target, TargetOptions};
+use std::{path::{Path, PathBuf}, time::Instant};
 
 use kind_checker as checker;
 
+pub mod dead_code;
 pub mod diagnostic;
+pub mod graph;
+pub mod io;
+pub mod json;
+pub mod library;
+pub mod profile;
 pub mod resolution;
 pub mod session;
+pub mod target;
 
 pub use resolution::get_unbound_top_levels_in_file;
 
@@ -56,13 +65,43 @@ pub fn type_check_book(
     let mut book = erasure::erase_book(
         &desugared_book,
         session.diagnostic_sender.clone(),
-        entrypoints,
+        entrypoints.clone(),
     )?;
-    inline_book(&mut book);
+    optimize_erased_book(session, &mut book, &entrypoints);
 
     Ok((book, result.unwrap()))
 }
 
+/// Type checks a file in the Kind Core syntax, without checking the
+/// coverage of the patterns, because the file doesn't say which
+/// constructors belong to each type.
+pub fn check_core_book(
+    session: &mut Session,
+    path: &Path,
+    tids: Option<usize>,
+) -> anyhow::Result<u64> {
+    let book = resolution::new_core_book_from_file(session, path)?;
+    check_core(session, book, tids)
+}
+
+/// Same as `check_core_book` but with the source already in memory.
+pub fn check_core_source(
+    session: &mut Session,
+    path: PathBuf,
+    input: String,
+    tids: Option<usize>,
+) -> anyhow::Result<u64> {
+    let book = resolution::new_core_book_from_source(session, path, input)?;
+    check_core(session, book, tids)
+}
+
+fn check_core(session: &Session, book: desugared::Book, tids: Option<usize>) -> anyhow::Result<u64> {
+    let all = book.entrs.keys().cloned().collect();
+
+    checker::type_check(&book, session.diagnostic_sender.clone(), all, false, tids)
+        .ok_or_else(|| ResolutionError.into())
+}
+
 pub fn to_book(session: &mut Session, path: &PathBuf) -> anyhow::Result<concrete::Book> {
     let mut concrete_book = resolution::new_book_from_entry_file(session, path)?;
 
@@ -78,17 +117,54 @@ pub fn erase_book(
 ) -> anyhow::Result<untyped::Book> {
     let concrete_book = to_book(session, path)?;
     let desugared_book = desugar::desugar_book(session.diagnostic_sender.clone(), &concrete_book)?;
+    erase_desugared_book(session, &desugared_book, entrypoints)
+}
 
+/// Same as `erase_book` but checks the book with the target before
+/// its types are erased.
+pub fn erase_book_for_target(
+    session: &mut Session,
+    path: &PathBuf,
+    target: &dyn Target,
+    entrypoints: Vec<String>,
+) -> anyhow::Result<untyped::Book> {
+    let concrete_book = to_book(session, path)?;
+    let desugared_book = desugar::desugar_book(session.diagnostic_sender.clone(), &concrete_book)?;
+
+    if !target.check(&desugared_book, &session.diagnostic_sender) {
+        return Err(GenericDriverError.into());
+    }
+
+    erase_desugared_book(session, &desugared_book, entrypoints)
+}
+
+fn erase_desugared_book(
+    session: &mut Session,
+    desugared_book: &desugared::Book,
+    entrypoints: Vec<String>,
+) -> anyhow::Result<untyped::Book> {
     let mut book = erasure::erase_book(
-        &desugared_book,
+        desugared_book,
         session.diagnostic_sender.clone(),
-        entrypoints,
+        entrypoints.clone(),
     )?;
 
-    inline_book(&mut book);
+    optimize_erased_book(session, &mut book, &entrypoints);
     Ok(book)
 }
 
+/// Runs the inlining and the optimizer of the session over an erased
+/// book, keeping the statistics of the optimizer in the session.
+fn optimize_erased_book(session: &mut Session, book: &mut untyped::Book, entrypoints: &[String]) {
+    inline_book(
+        book,
+        session.diagnostic_sender.clone(),
+        session.optimizations.inline_threshold,
+    );
+    session.optimization_stats =
+        kind_optimization::optimize_book(book, &session.optimizations, entrypoints);
+}
+
 pub fn desugar_book(session: &mut Session, path: &PathBuf) -> anyhow::Result<desugared::Book> {
     let concrete_book = to_book(session, path)?;
     desugar::desugar_book(session.diagnostic_sender.clone(), &concrete_book)
@@ -102,30 +178,147 @@ pub fn check_erasure_book(
     desugar::desugar_book(session.diagnostic_sender.clone(), &concrete_book)
 }
 
-pub fn compile_book_to_hvm(book: untyped::Book, trace: bool) -> backend::File {
-    kind_target_hvm::compile_book(book, trace)
+pub fn dependency_graph(session: &mut Session, path: &PathBuf) -> anyhow::Result<graph::DepGraph> {
+    let book = desugar_book(session, path)?;
+    Ok(graph::DepGraph::from_book(&book))
 }
 
-pub fn compile_book_to_kdl(
-    path: &PathBuf,
+pub fn dead_code(
     session: &mut Session,
-    namespace: &str,
+    path: &PathBuf,
     entrypoints: Vec<String>,
-) -> anyhow::Result<kind_target_kdl::File> {
+) -> anyhow::Result<dead_code::DeadCode> {
     let concrete_book = to_book(session, path)?;
-    let desugared_book = desugar::desugar_book(session.diagnostic_sender.clone(), &concrete_book)?;
+    let book = desugar::desugar_book(session.diagnostic_sender.clone(), &concrete_book)?;
+    let reach = erasure::reachability(&book, session.diagnostic_sender.clone(), entrypoints);
+
+    // The derived entries cannot be removed by the user.
+    let derived = concrete_book
+        .entries
+        .iter()
+        .filter(|(_, top_level)| matches!(top_level, concrete::TopLevel::Entry(entry) if entry.generated_by.is_some()))
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    dead_code::DeadCode::new(session, &book, &reach, &derived)
+}
 
-    let mut book = erasure::erase_book(
-        &desugared_book,
-        session.diagnostic_sender.clone(),
-        entrypoints,
-    )?;
+/// Sends the shortest chain of relevant uses from the entrypoints to
+/// the definition `name` as a diagnostic, or a note if it's erased.
+pub fn relevance_chain(
+    session: &mut Session,
+    path: &PathBuf,
+    entrypoints: Vec<String>,
+    name: &str,
+) -> anyhow::Result<Option<Vec<erasure::Step>>> {
+    let book = desugar_book(session, path)?;
+    find_definition(session, &graph::DepGraph::from_book(&book), name)?;
+    Ok(erasure::relevance_chain(&book, session.diagnostic_sender.clone(), entrypoints, name))
+}
+
+/// Finds a definition of the graph by its name, sending an error with
+/// the similar names if there's none.
+pub fn find_definition(session: &mut Session, graph: &graph::DepGraph, name: &str) -> anyhow::Result<usize> {
+    if let Some(id) = graph.get(name) {
+        return Ok(id);
+    }
+
+    let mut similar_names = graph
+        .names
+        .iter()
+        .map(|x| (strsim::jaro(x, name).abs(), x))
+        .filter(|x| x.0 > 0.8)
+        .collect::<Vec<_>>();
+
+    similar_names.sort_by(|x, y| y.0.total_cmp(&x.0));
+
+    let err = Box::new(DriverDiagnostic::UnknownDefinition(
+        name.to_string(),
+        similar_names.iter().take(5).map(|x| x.1.clone()).collect(),
+    ));
+
+    session.diagnostic_sender.send(err).unwrap();
+    Err(GenericDriverError.into())
+}
 
-    inline_book(&mut book);
+/// Compiles a file to a Kindelia contract, with the same passes of
+/// the KDL target.
+pub fn build_contract(
+    session: &mut Session,
+    path: &PathBuf,
+    options: &TargetOptions,
+    entrypoints: Vec<String>,
+) -> anyhow::Result<kind_target_kdl::Contract> {
+    let book = erase_book_for_target(session, path, &KdlTarget, entrypoints)?;
+    let book = target::lower_book(&KdlTarget, book, options);
+    let sender = session.diagnostic_sender.clone();
+    let contract = kind_target_kdl::compile_contract(book, sender, &options.namespace)?;
+    Ok(contract)
+}
 
-    let res = kind_target_kdl::compile_book(book, session.diagnostic_sender.clone(), namespace)?;
+/// Measures the statements of the KDL file of a file.
+pub fn kdl_stats(
+    session: &mut Session,
+    path: &PathBuf,
+    options: &TargetOptions,
+    budgets: &kind_target_kdl::Budgets,
+    entrypoints: Vec<String>,
+) -> anyhow::Result<kind_target_kdl::Stats> {
+    let book = erase_book_for_target(session, path, &KdlTarget, entrypoints)?;
+    let book = target::lower_book(&KdlTarget, book, options);
+    let sender = session.diagnostic_sender.clone();
+    let stats = kind_target_kdl::compile_stats(book, sender, &options.namespace, budgets)?;
+    Ok(stats)
+}
+
+/// Erases the file and compiles it with the target named `target`,
+/// returning the files that it generated.
+pub fn build_book(
+    session: &mut Session,
+    path: &PathBuf,
+    target: &str,
+    options: &TargetOptions,
+    entrypoints: Vec<String>,
+) -> anyhow::Result<Vec<OutputFile>> {
+    let Some(target) = target::get_target(target) else {
+        let names = target::targets().iter().map(|x| x.name().to_string()).collect();
+        let err = Box::new(DriverDiagnostic::UnknownTarget(target.to_string(), names));
+        session.diagnostic_sender.send(err).unwrap();
+        return Err(GenericDriverError.into());
+    };
 
-    Ok(res)
+    let book = erase_book_for_target(session, path, target.as_ref(), entrypoints)?;
+
+    let mut options = options.clone();
+    if options.source_map {
+        options.sources = Sources::from_session(session);
+    }
+
+    target::compile_with_target(
+        target.as_ref(),
+        book,
+        &options,
+        session.diagnostic_sender.clone(),
+    )
+}
+
+/// Removes the file with the extension from the files generated by
+/// the target, like the manifest of the KDL.
+pub fn take_output(
+    session: &mut Session,
+    files: &mut Vec<OutputFile>,
+    target: &str,
+    extension: &str,
+) -> anyhow::Result<OutputFile> {
+    let suffix = format!(".{}", extension);
+    match files.iter().position(|x| x.path.to_string_lossy().ends_with(&suffix)) {
+        Some(index) => Ok(files.remove(index)),
+        None => {
+            let err = DriverDiagnostic::MissingOutput(target.to_string(), extension.to_string());
+            session.diagnostic_sender.send(Box::new(err)).unwrap();
+            Err(GenericDriverError.into())
+        }
+    }
 }
 
 pub fn check_main_entry(session: &mut Session, book: &untyped::Book) -> anyhow::Result<()> {
@@ -151,6 +344,103 @@ pub fn check_main_desugared_entry(
     }
 }
 
+fn report_stuck_apps(session: &Session, map: &SourceMap, result: &str) {
+    // The same application can be copied many times in the result, so
+    // they are reported once, in the order that they first appear.
+    let mut apps: Vec<(StuckApp, usize)> = Vec::new();
+    for app in map.stuck_apps(result) {
+        match apps.iter_mut().find(|(x, _)| x.name == app.name && x.term == app.term) {
+            Some((_, times)) => *times += 1,
+            None => apps.push((app, 1)),
+        }
+    }
+
+    for (app, times) in apps {
+        let rules = map.rules_of(&app.name).collect();
+        let fun = map.get(&app.name).unwrap();
+        let err = Box::new(DriverDiagnostic::StuckApplication(
+            fun.origin.clone(),
+            app.name,
+            app.term,
+            times,
+            fun.range,
+            rules,
+        ));
+        session.diagnostic_sender.send(err).unwrap();
+    }
+}
+
+/// Runs the `Main` of the book in the HVM. The applications that got
+/// stuck in the result are reported at the place of the source that
+/// defines the function, using the source map of the HVM file.
+pub fn run_book(
+    session: &mut Session,
+    book: untyped::Book,
+    options: &TargetOptions,
+    tids: Option<usize>,
+) -> anyhow::Result<(String, u64)> {
+    let book = target::lower_book(&HvmTarget, book, options);
+    let (file, map) = HvmTarget.compile_with_map(book, options);
+
+    let (result, rewrites) = execute_file(&file.to_string(), tids)?;
+    report_stuck_apps(session, &map, &result);
+
+    Ok((result, rewrites))
+}
+
+/// Same as [run_book] but `Main` can be an `IO` action, whose effects
+/// are performed on the host while it runs.
+pub fn run_io_book(
+    session: &mut Session,
+    book: untyped::Book,
+    options: &TargetOptions,
+    tids: Option<usize>,
+    host: &mut io::Host,
+) -> anyhow::Result<io::Outcome> {
+    let book = target::lower_book(&HvmTarget, book, options);
+    let (file, map) = HvmTarget.compile_with_map(book, options);
+
+    match io::run_file(&file.to_string(), tids, host) {
+        Ok(outcome) => {
+            report_stuck_apps(session, &map, &outcome.result);
+            Ok(outcome)
+        }
+        Err(io::EffectError::Failed(effect, reason)) => {
+            let err = Box::new(DriverDiagnostic::EffectFailed(effect, reason));
+            session.diagnostic_sender.send(err).unwrap();
+            Err(GenericDriverError.into())
+        }
+        Err(io::EffectError::Runtime(reason)) => {
+            let err = Box::new(DriverDiagnostic::RuntimeFailed(reason));
+            session.diagnostic_sender.send(err).unwrap();
+            Err(GenericDriverError.into())
+        }
+    }
+}
+
+/// Same as [run_book] but with the profiler, that always uses a
+/// single thread.
+pub fn profile_book(
+    session: &mut Session,
+    book: untyped::Book,
+    options: &TargetOptions,
+) -> anyhow::Result<(String, u64, Profile)> {
+    let book = target::lower_book(&HvmTarget, book, options);
+    let (file, map) = HvmTarget.compile_with_map(book, options);
+
+    match profile::profile_file(&file.to_string(), &map) {
+        Ok((result, rewrites, profile)) => {
+            report_stuck_apps(session, &map, &result);
+            Ok((result, rewrites, profile))
+        }
+        Err(reason) => {
+            let err = Box::new(DriverDiagnostic::RuntimeFailed(reason));
+            session.diagnostic_sender.send(err).unwrap();
+            Err(GenericDriverError.into())
+        }
+    }
+}
+
 pub fn execute_file(file: &str, tids: Option<usize>) -> anyhow::Result<(String, u64)> {
     match eval(file, "Main", false, tids) {
         Ok((res, rewrites)) => Ok((res.to_string(), rewrites)),
@@ -210,6 +500,9 @@ pub fn run_in_session<T>(
     }
 
     if !contains_error {
+        if session.optimization_stats.total() > 0 {
+            log(&session, &Log::Optimized(session.optimization_stats.to_string()));
+        }
         log(
             &session,
             &if compiled {
@@ -0,0 +1,49 @@
+//! Kind sources that are shipped inside of the compiler, like the
+//! `IO` type that `kind2 run` performs. The resolution loads them
+//! when a name is not found in the files of the root, so a program
+//! can use them without a copy of its own, and a file of the root
+//! with the same name takes their place.
+
+use std::path::PathBuf;
+
+/// The directory that is shown as the place of the library files in
+/// the diagnostics.
+const ROOT: &str = "<kind2>";
+
+const FILES: [(&str, &str); 9] = [
+    ("IO/_.kind2", include_str!("../lib/IO/_.kind2")),
+    ("IO/arg.kind2", include_str!("../lib/IO/arg.kind2")),
+    ("IO/bind.kind2", include_str!("../lib/IO/bind.kind2")),
+    ("IO/exit.kind2", include_str!("../lib/IO/exit.kind2")),
+    ("IO/output.kind2", include_str!("../lib/IO/output.kind2")),
+    ("IO/prompt.kind2", include_str!("../lib/IO/prompt.kind2")),
+    ("IO/pure.kind2", include_str!("../lib/IO/pure.kind2")),
+    (
+        "IO/read_file.kind2",
+        include_str!("../lib/IO/read_file.kind2"),
+    ),
+    (
+        "IO/write_file.kind2",
+        include_str!("../lib/IO/write_file.kind2"),
+    ),
+];
+
+/// Finds the file of the library that can define `name`, in the same
+/// order that the resolution searches the root: `IO.bind` is searched
+/// in `IO/bind.kind2`, `IO/bind/_.kind2`, `IO.kind2` and `IO/_.kind2`.
+pub fn find(name: &str) -> Option<(PathBuf, &'static str)> {
+    let base = name.replace('.', "/");
+    let parent = base.rsplit_once('/').map(|(parent, _)| parent);
+
+    let mut options = [Some(base.as_str()), parent]
+        .into_iter()
+        .flatten()
+        .flat_map(|base| [format!("{}.kind2", base), format!("{}/_.kind2", base)]);
+
+    options.find_map(|path| {
+        FILES
+            .iter()
+            .find(|(file, _)| *file == path)
+            .map(|(file, source)| (PathBuf::from(ROOT).join(file), *source))
+    })
+}
@@ -0,0 +1,361 @@
+//! Profiler of the programs that run in the HVM. Each function of
+//! the program is replaced by a wrapper that counts its calls and
+//! the words allocated by its rules before applying them. HVM only
+//! counts the rewrites of the whole program, so the rewrites that
+//! happen between an application of a function and the next one
+//! (the beta reductions, duplications and operations that its body
+//! needs) are charged to the function.
+//!
+//! The stacks of the profile are the stacks of creation of the
+//! calls: a call that appears in the body of a rule of `F` has the
+//! stack of the application of `F` with itself on top of it. With
+//! lazy evaluation, it's the same as the stack of a strict language.
+//!
+//! The wrapper needs to know which rule an application is going to
+//! use, to count the words that its body allocates and to give its
+//! stack to the calls that it creates, so it repeats the matching of
+//! `fun::apply` of the HVM 1.0 (see [matching_rule]). It's an
+//! approximation that only holds while both of them agree. The
+//! applications that superpose the function match no rule: they are
+//! not counted as calls and their rewrites are charged to the stack of
+//! the application before them.
+//!
+//! The functions are folded into the entries that the user wrote with
+//! the source map, so the helpers of the flattening, of the lowering of
+//! `Data.Nat` and of the traces are charged to their entry. A helper
+//! that is applied by its own entry is not a call and stays in its
+//! stack.
+
+use std::fmt::Display;
+use std::sync::Mutex;
+
+use fxhash::FxHashMap;
+use hvm::language;
+use hvm::runtime::{self, fun, ApplyObj, Function, Ptr, ReduceCtx, RuleBodyCell, VisitObj};
+use kind_target_hvm::SourceMap;
+
+/// Counters of a function or of a stack.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub rewrites: u64,
+    pub calls: u64,
+    pub allocations: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    /// Counters of each function, sorted by the number of rewrites.
+    pub functions: Vec<(String, Counters)>,
+    /// Number of rewrites of each stack of calls.
+    pub stacks: Vec<(Vec<String>, u64)>,
+}
+
+impl Profile {
+    /// The stacks in the collapsed format of the flamegraph tools,
+    /// with a line like `Main;List.map;List.map 12` for each stack.
+    pub fn collapsed(&self) -> String {
+        let mut out = String::new();
+        for (stack, rewrites) in &self.stacks {
+            out.push_str(&format!("{} {}\n", stack.join(";"), rewrites));
+        }
+        out
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>10} {:>10} {:>12}  function",
+            "rewrites", "calls", "allocations"
+        )?;
+        for (name, counters) in &self.functions {
+            writeln!(
+                f,
+                "{:>10} {:>10} {:>12}  {}",
+                counters.rewrites, counters.calls, counters.allocations, name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A function of the program that was replaced by the wrapper.
+struct Wrapped {
+    /// Name of the entry that the user wrote.
+    name: String,
+    /// If it's a helper that the compiler made for the entry.
+    helper: bool,
+    visit: VisitObj,
+    apply: ApplyObj,
+    /// Words allocated by the body of each rule.
+    allocations: Vec<u64>,
+    /// Nodes of the body of each rule that are calls to functions.
+    calls: Vec<Vec<(u64, u64)>>,
+}
+
+/// State of the profiler while the program runs. The stack `0` is
+/// the root, that has no functions.
+struct State {
+    funs: FxHashMap<u64, Wrapped>,
+    /// Each stack is the function on top of it and the stack below.
+    stacks: Vec<(usize, u64)>,
+    stack_ids: FxHashMap<(usize, u64), usize>,
+    counters: Vec<Counters>,
+    /// Stack that created each of the calls that were not applied yet,
+    /// by the location of the call in the heap.
+    creators: FxHashMap<u64, usize>,
+    last_stack: usize,
+    last_cost: u64,
+}
+
+impl State {
+    fn stack(&mut self, parent: usize, fid: u64) -> usize {
+        if let Some(id) = self.stack_ids.get(&(parent, fid)) {
+            return *id;
+        }
+        let id = self.stacks.len();
+        self.stacks.push((parent, fid));
+        self.stack_ids.insert((parent, fid), id);
+        self.counters.push(Counters::default());
+        id
+    }
+
+    /// Charges the rewrites since the last application to its stack.
+    fn charge(&mut self, cost: u64, next: usize) {
+        self.counters[self.last_stack].rewrites += cost - self.last_cost;
+        self.last_stack = next;
+        self.last_cost = cost;
+    }
+
+    fn path(&self, mut stack: usize) -> Vec<String> {
+        let mut path = Vec::new();
+        while stack != 0 {
+            let (parent, fid) = self.stacks[stack];
+            path.push(self.funs[&fid].name.clone());
+            stack = parent;
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// State of the program that is being profiled. The wrappers are
+/// plain functions, so they can only reach it through a global.
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+/// Only one program can be profiled at a time, because the wrappers
+/// are functions and not closures.
+static RUNNING: Mutex<()> = Mutex::new(());
+
+/// Returns the rule that `fun::apply` will apply, or `None` if it's
+/// going to superpose the function or if no rule matches. It's a copy
+/// of the conditions of `fun::apply`, so it must be kept in sync with
+/// the version of the HVM.
+fn matching_rule(ctx: &ReduceCtx, visit: &VisitObj, apply: &ApplyObj) -> Option<usize> {
+    use runtime::*;
+
+    let arg = |i: u64| load_arg(ctx.heap, ctx.term, i);
+
+    for (n, is_strict) in visit.strict_map.iter().enumerate() {
+        if *is_strict && get_tag(arg(n as u64)) == SUP {
+            return None;
+        }
+    }
+
+    apply.rules.iter().enumerate().position(|(r, rule)| {
+        rule.cond.iter().enumerate().all(|(i, cond)| {
+            let i = i as u64;
+            match get_tag(*cond) {
+                U60 | F60 => get_tag(arg(i)) == get_tag(*cond) && get_num(arg(i)) == get_num(*cond),
+                CTR => {
+                    (get_tag(arg(i)) == CTR || get_tag(arg(i)) == FUN)
+                        && get_ext(arg(i)) == get_ext(*cond)
+                }
+                VAR if visit.strict_map[i as usize] => {
+                    let tag = get_tag(arg(i));
+                    if rule.hoas && r != apply.rules.len() - 1 {
+                        tag == U60
+                            || tag == F60
+                            || (tag == CTR && arity_of(&ctx.prog.aris, arg(i)) == 0)
+                            || (tag == CTR
+                                && get_ext(arg(i)) >= KIND_TERM_CT0
+                                && get_ext(arg(i)) <= KIND_TERM_F60)
+                    } else {
+                        tag == CTR || tag == U60 || tag == F60
+                    }
+                }
+                _ => true,
+            }
+        })
+    })
+}
+
+fn profiled_visit(ctx: ReduceCtx) -> bool {
+    let guard = STATE.lock().unwrap();
+    let state = guard.as_ref().unwrap();
+    let fun = &state.funs[&runtime::get_ext(ctx.term)];
+    fun::visit(ctx, &fun.visit.strict_idx)
+}
+
+fn profiled_apply(ctx: ReduceCtx) -> bool {
+    let fid = runtime::get_ext(ctx.term);
+    let loc = runtime::get_loc(ctx.term, 0);
+    let (heap, tid) = (ctx.heap, ctx.tid);
+
+    let mut guard = STATE.lock().unwrap();
+    let state = guard.as_mut().unwrap();
+
+    let cost = runtime::get_cost(heap);
+    let fun = &state.funs[&fid];
+    let rule = matching_rule(&ctx, &fun.visit, &fun.apply);
+    let done = fun::apply(ctx, fid, &fun.visit, &fun.apply);
+
+    if let (true, Some(rule)) = (done, rule) {
+        let allocations = fun.allocations[rule];
+        let calls = fun.calls[rule].clone();
+
+        let parent = state.creators.remove(&loc).unwrap_or(0);
+        let inner =
+            fun.helper && parent != 0 && state.funs[&state.stacks[parent].1].name == fun.name;
+        let stack = if inner {
+            parent
+        } else {
+            state.stack(parent, fid)
+        };
+        state.charge(cost, stack);
+
+        let counters = &mut state.counters[stack];
+        counters.calls += u64::from(!inner);
+        counters.allocations += allocations;
+
+        for (targ, slot) in calls {
+            let node = heap.aloc[tid][targ as usize].load(std::sync::atomic::Ordering::Relaxed);
+            state.creators.insert(node + slot, stack);
+        }
+    }
+
+    done
+}
+
+fn wrap(name: String, helper: bool, visit: VisitObj, apply: ApplyObj) -> Wrapped {
+    let allocations = apply
+        .rules
+        .iter()
+        .map(|rule| rule.body.1.iter().map(|node| node.len() as u64).sum())
+        .collect();
+
+    let calls = apply
+        .rules
+        .iter()
+        .map(|rule| {
+            let (root, nodes, _) = &rule.body;
+            std::iter::once(root)
+                .chain(nodes.iter().flatten())
+                .filter_map(|cell| match cell {
+                    RuleBodyCell::Ptr { value, targ, slot }
+                        if runtime::get_tag(*value) == runtime::FUN =>
+                    {
+                        Some((*targ, *slot))
+                    }
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+
+    Wrapped {
+        name,
+        helper,
+        visit,
+        apply,
+        allocations,
+        calls,
+    }
+}
+
+/// Runs the `Main` of a HVM file with the profiler in a single
+/// thread. Returns the result, the number of rewrites and the profile,
+/// with the functions named by the entries of the source map.
+pub fn profile_file(file: &str, map: &SourceMap) -> Result<(String, u64, Profile), String> {
+    let _running = RUNNING.lock().unwrap_or_else(|err| err.into_inner());
+
+    let file = language::syntax::read_file(&format!("{}\nHVM_MAIN_CALL = Main", file))?;
+    let book = language::rulebook::gen_rulebook(&file);
+
+    let mut prog = runtime::Program::new();
+    prog.add_book(&book);
+
+    let main_call = *book.name_to_id.get("HVM_MAIN_CALL").unwrap();
+
+    let mut funs = FxHashMap::default();
+
+    for (fid, slot) in prog.funs.data.iter_mut().enumerate() {
+        let fid = fid as u64;
+        if fid < runtime::PRECOMP_COUNT || fid == main_call {
+            continue;
+        }
+        if let Some(Function::Interpreted { smap, visit, apply }) = slot.take() {
+            let name = prog.nams.get(&fid).cloned().unwrap_or_default();
+            let origin = map
+                .get(&name)
+                .map_or_else(|| name.clone(), |x| x.origin.clone());
+            let helper = origin != name;
+            funs.insert(fid, wrap(origin, helper, visit, apply));
+            *slot = Some(Function::Compiled {
+                smap,
+                visit: profiled_visit,
+                apply: profiled_apply,
+            });
+        }
+    }
+
+    *STATE.lock().unwrap() = Some(State {
+        funs,
+        stacks: vec![(0, 0)],
+        stack_ids: FxHashMap::default(),
+        counters: vec![Counters::default()],
+        creators: FxHashMap::default(),
+        last_stack: 0,
+        last_cost: 0,
+    });
+
+    let heap = runtime::new_heap(runtime::default_heap_size(), 1);
+    let tids = runtime::new_tids(1);
+
+    runtime::link(&heap, 0, runtime::Fun(main_call, 0));
+
+    let host = 0;
+    runtime::normalize(&heap, &prog, &tids, host, false);
+    let code = language::readback::as_term(&heap, &prog, host).to_string();
+
+    let ptr: Ptr = runtime::load_ptr(&heap, host);
+    runtime::collect(&heap, &prog.aris, tids[0], ptr);
+    runtime::free(&heap, 0, 0, 1);
+
+    let cost = runtime::get_cost(&heap);
+
+    let mut state = STATE.lock().unwrap().take().unwrap();
+    state.charge(cost, 0);
+
+    let mut functions: FxHashMap<String, Counters> = FxHashMap::default();
+    let mut stacks = Vec::new();
+
+    for (id, counters) in state.counters.iter().enumerate().skip(1) {
+        let name = &state.funs[&state.stacks[id].1].name;
+        let total = functions.entry(name.clone()).or_default();
+        total.rewrites += counters.rewrites;
+        total.calls += counters.calls;
+        total.allocations += counters.allocations;
+
+        if counters.rewrites > 0 {
+            stacks.push((state.path(id), counters.rewrites));
+        }
+    }
+
+    let mut functions: Vec<_> = functions.into_iter().collect();
+    functions.sort_by(|a, b| b.1.rewrites.cmp(&a.1.rewrites).then_with(|| a.0.cmp(&b.0)));
+    stacks.sort();
+
+    Ok((code, cost, Profile { functions, stacks }))
+}
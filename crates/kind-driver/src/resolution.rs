@@ -3,7 +3,8 @@
 
 use core::fmt;
 use fxhash::FxHashSet;
-use kind_pass::expand::expand_module;
+use kind_macros::expand_macros;
+use kind_pass::expand::expand_module_with;
 use kind_pass::expand::uses::expand_uses;
 use std::error::Error;
 use std::fs;
@@ -14,9 +15,10 @@ use strsim::jaro;
 use kind_pass::unbound::{self, UnboundCollector};
 use kind_tree::concrete::visitor::Visitor;
 use kind_tree::concrete::{Book, Module, TopLevel};
+use kind_tree::desugared;
 use kind_tree::symbol::{Ident, QualifiedIdent};
 
-use crate::{diagnostic::DriverDiagnostic, session::Session};
+use crate::{diagnostic::DriverDiagnostic, library, session::Session};
 
 /// The extension of kind2 files.
 const EXT: &str = "kind2";
@@ -62,6 +64,34 @@ pub fn update_book_with_entry_file(
     }
 }
 
+/// Loads a file in the Kind Core syntax, the one that `to-kind-core`
+/// prints, into a new `desugared::Book`. It doesn't load any other file,
+/// so the file has to contain all the definitions that it uses.
+pub fn new_core_book_from_file(session: &mut Session, path: &Path) -> anyhow::Result<desugared::Book> {
+    let Some(input) = read_file(session, path) else { return Err(ResolutionError.into()) };
+    new_core_book_from_source(session, fs::canonicalize(path).unwrap(), input)
+}
+
+/// Same as `new_core_book_from_file` but with the source already in
+/// memory. The path is only used to report the diagnostics.
+pub fn new_core_book_from_source(
+    session: &mut Session,
+    path: PathBuf,
+    input: String,
+) -> anyhow::Result<desugared::Book> {
+    let ctx_id = session.book_counter;
+    session.add_path(Rc::new(path), input.clone());
+
+    let tx = session.diagnostic_sender.clone();
+    let (book, failed) = kind_parser::parse_core_book(tx, ctx_id, &input);
+
+    if failed {
+        Err(ResolutionError.into())
+    } else {
+        Ok(book)
+    }
+}
+
 /// Returns a list of all unbound top levels required in a file.
 ///
 /// Returns `None` if it fails to read the file.
@@ -73,7 +103,8 @@ pub fn get_unbound_top_levels_in_file(session: &mut Session, path: &Path) -> Opt
     let (mut module, _) = kind_parser::parse_book(tx.clone(), 0, &input);
 
     expand_uses(&mut module, tx.clone());
-    expand_module(tx.clone(), &mut module);
+    expand_macros(tx.clone(), &mut module);
+    expand_module_with(tx.clone(), &mut module, &session.derives);
 
     let mut state = UnboundCollector::new(tx.clone(), false);
     state.visit_module(&mut module);
@@ -314,15 +345,38 @@ fn load_file_to_book(
 
     let Some(input) = read_file(session, path) else { return true };
 
+    load_source_to_book(session, canon_path.clone(), input, book, immediate)
+}
+
+/// Loads a file of the library that is shipped with the compiler and
+/// that defines `ident`, if there's one. It's used for the names that
+/// aren't found in the root.
+fn load_library_to_book(session: &mut Session, ident: &QualifiedIdent, book: &mut Book) -> bool {
+    match library::find(&ident.to_string()) {
+        Some((path, _)) if session.loaded_paths_map.contains_key(&path) => false,
+        Some((path, input)) => load_source_to_book(session, path, input.to_string(), book, false),
+        None => false,
+    }
+}
+
+/// Same as [load_file_to_book] but with the source already read.
+fn load_source_to_book(
+    session: &mut Session,
+    path: PathBuf,
+    input: String,
+    book: &mut Book,
+    immediate: bool,
+) -> bool {
     let ctx_id = session.book_counter;
-    session.add_path(Rc::new(fs::canonicalize(path).unwrap()), input.clone());
+    session.add_path(Rc::new(path), input.clone());
 
     let tx = session.diagnostic_sender.clone();
 
     let (mut module, mut failed) = kind_parser::parse_book(tx.clone(), ctx_id, &input);
 
     failed |= expand_uses(&mut module, tx.clone());
-    failed |= expand_module(tx.clone(), &mut module);
+    failed |= expand_macros(tx.clone(), &mut module);
+    failed |= expand_module_with(tx.clone(), &mut module, &session.derives);
 
     if failed {
         return failed;
@@ -344,7 +398,7 @@ fn load_file_to_book(
         if !book.names.contains_key(&fst.to_string()) {
             failed |= match ident_to_path(session, &session.root, fst) {
                 Ok(Some(path)) => load_file_to_book(session, &path, book, false),
-                Ok(None) => false,
+                Ok(None) => load_library_to_book(session, fst, book),
                 Err(()) => true,
             };
         }
@@ -9,6 +9,8 @@ use std::sync::mpsc::Sender;
 use fxhash::{FxHashMap, FxHashSet};
 use kind_report::data::Diagnostic;
 
+pub use kind_pass::expand::registry::{CustomDerive, DeriveRegistry, DeriveResult};
+
 #[derive(Debug, Clone)]
 pub struct Session {
     pub loaded_paths: Vec<Rc<PathBuf>>,
@@ -26,6 +28,15 @@ pub struct Session {
     pub book_counter: usize,
 
     pub show_immediate_deps: bool,
+
+    /// Derivations that are not built in the compiler
+    pub derives: DeriveRegistry,
+
+    /// Passes of the optimizer that run after the erasure
+    pub optimizations: kind_optimization::Options,
+
+    /// Rewrites done by the optimizer in the last erased book
+    pub optimization_stats: kind_optimization::Stats,
 }
 
 impl Session {
@@ -38,7 +49,10 @@ impl Session {
             root,
             book_counter: 0,
             diagnostic_sender: sender,
-            show_immediate_deps
+            show_immediate_deps,
+            derives: DeriveRegistry::default(),
+            optimizations: Default::default(),
+            optimization_stats: Default::default(),
         }
     }
     pub fn add_path(&mut self, path: Rc<PathBuf>, code: String) -> usize {
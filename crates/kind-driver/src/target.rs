@@ -0,0 +1,252 @@
+//! Backends of the compiler. Every target receives the erased
+//! book after the passes that are shared by all of them (inlining,
+//! the lowering of enums and of natural numbers and the unboxing)
+//! and returns the files that it generates. Errors are sent to the diagnostic
+//! channel, like in the rest of the compiler.
+
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+use fxhash::FxHashMap;
+use kind_pass::enums::lower_enums;
+use kind_pass::flatten::flatten;
+use kind_pass::nat::lower_nats;
+use kind_pass::unbox::unbox_newtypes;
+use kind_report::data::{Diagnostic, FileCache};
+use kind_span::SyntaxCtxIndex;
+use kind_target_hvm::{SourceMap, TraceOptions};
+use kind_tree::{desugared, untyped};
+
+use crate::session::Session;
+
+/// Options that are given to every target. Each one of them
+/// uses only the options that make sense for it.
+#[derive(Clone, Debug, Default)]
+pub struct TargetOptions {
+    /// Name of the compiled program, used to name the output files.
+    pub name: String,
+    /// Prints all of the functions and their evaluation (HVM).
+    pub trace: bool,
+    /// Traces only the functions whose names match the pattern, instead
+    /// of the ones marked with `#trace` (HVM).
+    pub trace_filter: Option<String>,
+    /// Prints the arguments of the traces up to this depth (HVM).
+    pub trace_depth: Option<u64>,
+    /// A namespace that goes before each compiled name (KDL).
+    pub namespace: String,
+    /// If `Data.Nat` is compiled to native numbers, by the name of the
+    /// target, instead of the default of the target. The numbers of the
+    /// targets have 60 bits, so they wrap around at 2^60 instead of
+    /// growing without a limit.
+    pub native_nat: FxHashMap<String, bool>,
+    /// Writes a source map next to the generated code (HVM).
+    pub source_map: bool,
+    /// Files of the program, used to write the positions of the
+    /// source maps.
+    pub sources: Sources,
+}
+
+/// Paths and contents of the loaded files, indexed by the syntax
+/// context of the ranges.
+#[derive(Clone, Debug, Default)]
+pub struct Sources(pub Vec<(PathBuf, String)>);
+
+impl Sources {
+    pub fn from_session(session: &Session) -> Sources {
+        let paths = session.loaded_paths.iter().map(|x| x.as_ref().clone());
+        Sources(paths.zip(session.loaded_sources.iter().cloned()).collect())
+    }
+}
+
+impl TargetOptions {
+    /// If the target compiles `Data.Nat` to native numbers.
+    pub fn native_nat(&self, target: &dyn Target) -> bool {
+        self.native_nat
+            .get(target.name())
+            .copied()
+            .unwrap_or_else(|| target.native_nat())
+    }
+}
+
+impl FileCache for Sources {
+    fn fetch(&self, ctx: SyntaxCtxIndex) -> Option<(PathBuf, &String)> {
+        self.0.get(ctx.0).map(|(path, code)| (path.clone(), code))
+    }
+}
+
+/// A file generated by a target. The path is relative to the
+/// output directory.
+#[derive(Clone, Debug)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+pub trait Target {
+    /// Name used to select the target in the command line.
+    fn name(&self) -> &'static str;
+
+    /// If `Data.Nat` is compiled to native numbers when the options
+    /// don't choose it.
+    fn native_nat(&self) -> bool {
+        false
+    }
+
+    /// Checks the properties of the book that need its types, so
+    /// it's called before the erasure.
+    fn check(&self, _book: &desugared::Book, _sender: &Sender<Box<dyn Diagnostic>>) -> bool {
+        true
+    }
+
+    fn compile(
+        &self,
+        book: untyped::Book,
+        options: &TargetOptions,
+        sender: Sender<Box<dyn Diagnostic>>,
+    ) -> anyhow::Result<Vec<OutputFile>>;
+}
+
+pub struct HvmTarget;
+
+pub struct KdlTarget;
+
+pub struct JsTarget;
+
+fn single_file(options: &TargetOptions, extension: &str, contents: String) -> Vec<OutputFile> {
+    vec![OutputFile {
+        path: PathBuf::from(&options.name).with_extension(extension),
+        contents,
+    }]
+}
+
+impl HvmTarget {
+    /// Compiles the book to a HVM file and to the source map of it.
+    pub fn compile_with_map(
+        &self,
+        book: untyped::Book,
+        options: &TargetOptions,
+    ) -> (hvm::syntax::File, SourceMap) {
+        // HVM can match nested patterns by itself, but the book is
+        // flattened so it behaves in the same way as the other targets.
+        let trace = TraceOptions {
+            all: options.trace,
+            filter: options.trace_filter.clone(),
+            depth: options.trace_depth,
+        };
+        kind_target_hvm::compile_book_with_map(flatten(book), &trace)
+    }
+}
+
+impl Target for HvmTarget {
+    fn name(&self) -> &'static str {
+        "hvm"
+    }
+
+    fn compile(
+        &self,
+        book: untyped::Book,
+        options: &TargetOptions,
+        _: Sender<Box<dyn Diagnostic>>,
+    ) -> anyhow::Result<Vec<OutputFile>> {
+        let (file, map) = self.compile_with_map(book, options);
+        let mut files = single_file(options, "hvm", file.to_string());
+
+        if options.source_map {
+            files.push(OutputFile {
+                path: PathBuf::from(&options.name).with_extension("hvm.map"),
+                contents: map.render(&options.sources),
+            });
+        }
+
+        Ok(files)
+    }
+}
+
+impl Target for KdlTarget {
+    fn name(&self) -> &'static str {
+        "kdl"
+    }
+
+    /// Each constructor takes space in the state of Kindelia and each
+    /// rewrite costs mana, so the unary numbers are too expensive.
+    fn native_nat(&self) -> bool {
+        true
+    }
+
+    fn check(&self, book: &desugared::Book, sender: &Sender<Box<dyn Diagnostic>>) -> bool {
+        kind_target_kdl::check_states(book, sender)
+    }
+
+    fn compile(
+        &self,
+        book: untyped::Book,
+        options: &TargetOptions,
+        sender: Sender<Box<dyn Diagnostic>>,
+    ) -> anyhow::Result<Vec<OutputFile>> {
+        let file = kind_target_kdl::compile_book(book, sender, &options.namespace)?;
+        let mut files = single_file(options, "kdl", file.to_string());
+
+        files.push(OutputFile {
+            path: PathBuf::from(&options.name).with_extension("kdl.json"),
+            contents: file.manifest.to_json(),
+        });
+
+        Ok(files)
+    }
+}
+
+impl Target for JsTarget {
+    fn name(&self) -> &'static str {
+        "js"
+    }
+
+    fn compile(
+        &self,
+        book: untyped::Book,
+        options: &TargetOptions,
+        sender: Sender<Box<dyn Diagnostic>>,
+    ) -> anyhow::Result<Vec<OutputFile>> {
+        // The patterns are matched one level at a time, so each
+        // argument is forced only when a rule needs it.
+        let file = kind_target_js::compile_book(flatten(book), sender)?;
+        Ok(single_file(options, "mjs", file.to_string()))
+    }
+}
+
+/// All of the targets that are available in the compiler.
+pub fn targets() -> Vec<Box<dyn Target>> {
+    vec![Box::new(HvmTarget), Box::new(KdlTarget), Box::new(JsTarget)]
+}
+
+pub fn get_target(name: &str) -> Option<Box<dyn Target>> {
+    targets().into_iter().find(|target| target.name() == name)
+}
+
+/// Runs the passes that are shared by all of the targets, with the
+/// options of the target.
+pub fn lower_book(
+    target: &dyn Target,
+    mut book: untyped::Book,
+    options: &TargetOptions,
+) -> untyped::Book {
+    lower_enums(&mut book);
+    unbox_newtypes(&mut book);
+
+    if options.native_nat(target) {
+        book = lower_nats(book);
+    }
+
+    book
+}
+
+/// Runs the passes that are shared by all of the targets and
+/// then compiles the book with the given target.
+pub fn compile_with_target(
+    target: &dyn Target,
+    book: untyped::Book,
+    options: &TargetOptions,
+    sender: Sender<Box<dyn Diagnostic>>,
+) -> anyhow::Result<Vec<OutputFile>> {
+    let book = lower_book(target, book, options);
+    target.compile(book, options, sender)
+}
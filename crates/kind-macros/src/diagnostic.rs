@@ -0,0 +1,162 @@
+use kind_report::data::{Color, Diagnostic, DiagnosticFrame, Marker, Severity};
+use kind_report::RenderConfig;
+use kind_span::{Range, SyntaxCtxIndex};
+
+/// Errors of the macro expansion. The ones that happen on a
+/// call contain the range of the call and the range of the
+/// macro definition.
+pub(crate) enum MacroDiagnostic {
+    RepeatedMacro(Range, Range),
+    RepeatedParameter(Range, Range),
+    ShadowedParameter(Range, Range),
+    WrongArity(Range, Range, usize, usize),
+    NamedArgument(Range, Range),
+    ArgumentShouldBeAName(Range, Range),
+    RecursiveMacro(Range, Range),
+}
+
+fn call_and_definition(call: Range, call_text: &str, def: Range) -> Vec<Marker> {
+    vec![
+        Marker {
+            position: call,
+            color: Color::Fst,
+            text: call_text.to_string(),
+            no_code: false,
+            main: true,
+        },
+        Marker {
+            position: def,
+            color: Color::Snd,
+            text: "Defined here".to_string(),
+            no_code: false,
+            main: false,
+        },
+    ]
+}
+
+impl Diagnostic for MacroDiagnostic {
+    fn get_syntax_ctx(&self) -> Option<SyntaxCtxIndex> {
+        match self {
+            MacroDiagnostic::RepeatedMacro(_, range) => Some(range.ctx),
+            MacroDiagnostic::RepeatedParameter(_, range) => Some(range.ctx),
+            MacroDiagnostic::ShadowedParameter(range, _) => Some(range.ctx),
+            MacroDiagnostic::WrongArity(range, _, _, _) => Some(range.ctx),
+            MacroDiagnostic::NamedArgument(range, _) => Some(range.ctx),
+            MacroDiagnostic::ArgumentShouldBeAName(range, _) => Some(range.ctx),
+            MacroDiagnostic::RecursiveMacro(range, _) => Some(range.ctx),
+        }
+    }
+
+    fn to_diagnostic_frame(&self, _: &RenderConfig) -> DiagnosticFrame {
+        match self {
+            MacroDiagnostic::RepeatedMacro(fst, snd) => DiagnosticFrame {
+                code: 300,
+                severity: Severity::Error,
+                title: "Repeated macro name".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![
+                    Marker {
+                        position: *snd,
+                        color: Color::Fst,
+                        text: "Second declaration".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *fst,
+                        color: Color::Snd,
+                        text: "First declaration".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+            MacroDiagnostic::RepeatedParameter(fst, snd) => DiagnosticFrame {
+                code: 301,
+                severity: Severity::Error,
+                title: "Repeated macro parameter".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![
+                    Marker {
+                        position: *snd,
+                        color: Color::Fst,
+                        text: "Second occurence".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *fst,
+                        color: Color::Snd,
+                        text: "First occurence".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+            MacroDiagnostic::ShadowedParameter(binder, param) => DiagnosticFrame {
+                code: 302,
+                severity: Severity::Error,
+                title: "The body of a macro cannot bind the name of a parameter".to_string(),
+                subtitles: vec![],
+                hints: vec!["Rename the variable or the parameter".to_string()],
+                positions: vec![
+                    Marker {
+                        position: *binder,
+                        color: Color::Fst,
+                        text: "Bound here".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *param,
+                        color: Color::Snd,
+                        text: "Parameter".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+            MacroDiagnostic::WrongArity(call, def, expected, got) => DiagnosticFrame {
+                code: 303,
+                severity: Severity::Error,
+                title: format!(
+                    "The macro expects {} arguments but got {}",
+                    expected, got
+                ),
+                subtitles: vec![],
+                hints: vec![],
+                positions: call_and_definition(*call, "In this call", *def),
+            },
+            MacroDiagnostic::NamedArgument(call, def) => DiagnosticFrame {
+                code: 304,
+                severity: Severity::Error,
+                title: "Macros cannot receive named arguments".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: call_and_definition(*call, "This argument", *def),
+            },
+            MacroDiagnostic::ArgumentShouldBeAName(call, def) => DiagnosticFrame {
+                code: 305,
+                severity: Severity::Error,
+                title: "This argument of the macro should be a variable".to_string(),
+                subtitles: vec![],
+                hints: vec!["The parameter is used in a place that only accepts names".to_string()],
+                positions: call_and_definition(*call, "This argument", *def),
+            },
+            MacroDiagnostic::RecursiveMacro(call, def) => DiagnosticFrame {
+                code: 306,
+                severity: Severity::Error,
+                title: "The expansion of this macro calls itself".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: call_and_definition(*call, "In this call", *def),
+            },
+        }
+    }
+
+    fn get_severity(&self) -> Severity {
+        Severity::Error
+    }
+}
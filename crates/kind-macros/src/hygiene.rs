@@ -0,0 +1,172 @@
+//! Hygiene of the macro expansion. All of the names that are
+//! bound inside the body of a macro are renamed to fresh generated
+//! names before the substitution of the parameters, so they cannot
+//! capture any variable of the arguments.
+
+use fxhash::FxHashMap;
+use kind_span::Range;
+use kind_tree::concrete::expr::{Binding, Case, CaseBinding, Destruct, Expr, ExprKind, Match};
+use kind_tree::concrete::visitor::{walk_expr, walk_match, Visitor};
+use kind_tree::symbol::Ident;
+
+/// Collects the names that are bound inside an expression.
+#[derive(Default)]
+pub(crate) struct Binders {
+    pub names: Vec<Ident>,
+}
+
+impl Visitor for Binders {
+    fn visit_destruct(&mut self, destruct: &mut Destruct) {
+        match destruct {
+            Destruct::Destruct(_, _, bindings, _) => {
+                for binding in bindings {
+                    self.visit_case_binding(binding)
+                }
+            }
+            Destruct::Ident(ident) => self.names.push(ident.clone()),
+        }
+    }
+
+    fn visit_case_binding(&mut self, binding: &mut CaseBinding) {
+        match binding {
+            CaseBinding::Field(ident) => self.names.push(ident.clone()),
+            CaseBinding::Renamed(_, ident) => self.names.push(ident.clone()),
+        }
+    }
+
+    fn visit_match(&mut self, matcher: &mut Match) {
+        if matcher.value.is_some() {
+            self.names.push(matcher.scrutinee.clone());
+        }
+        walk_match(self, matcher)
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match &expr.data {
+            ExprKind::Lambda { param, .. }
+            | ExprKind::All {
+                param: Some(param), ..
+            }
+            | ExprKind::Sigma {
+                param: Some(param), ..
+            } => self.names.push(param.clone()),
+            _ => (),
+        }
+        walk_expr(self, expr)
+    }
+}
+
+/// Renames a name or a field access of it (e.g. `x.fst`).
+fn rename(names: &FxHashMap<String, String>, ident: &Ident) -> Option<Ident> {
+    let (base, rest) = match ident.to_str().split_once('.') {
+        Some((base, rest)) => (base, Some(rest)),
+        None => (ident.to_str(), None),
+    };
+    let fresh = names.get(base)?;
+    let name = match rest {
+        Some(rest) => format!("{}.{}", fresh, rest),
+        None => fresh.clone(),
+    };
+    Some(Ident::new_static(&name, ident.range).to_generated())
+}
+
+/// Renames all of the bound names of a macro body.
+pub(crate) struct Hygiene {
+    pub names: FxHashMap<String, String>,
+}
+
+impl Hygiene {
+    pub fn new(binders: &[Ident], expansion: usize) -> Hygiene {
+        let names = binders
+            .iter()
+            .map(|x| (x.to_string(), format!("{}__{}", x, expansion)))
+            .collect();
+        Hygiene { names }
+    }
+}
+
+impl Visitor for Hygiene {
+    fn visit_ident(&mut self, ident: &mut Ident) {
+        if let Some(fresh) = rename(&self.names, ident) {
+            *ident = fresh;
+        }
+    }
+
+    fn visit_case_binding(&mut self, binding: &mut CaseBinding) {
+        match binding {
+            // The field keeps its name and is bound to a fresh variable
+            CaseBinding::Field(field) => {
+                if let Some(fresh) = rename(&self.names, field) {
+                    *binding = CaseBinding::Renamed(field.clone(), fresh);
+                }
+            }
+            CaseBinding::Renamed(_, ident) => self.visit_ident(ident),
+        }
+    }
+
+    fn visit_case(&mut self, case: &mut Case) {
+        for binding in &mut case.bindings {
+            self.visit_case_binding(binding);
+        }
+        self.visit_expr(&mut case.value)
+    }
+
+    fn visit_binding(&mut self, binding: &mut Binding) {
+        match binding {
+            Binding::Positional(expr) => self.visit_expr(expr),
+            Binding::Named(_, _, expr) => self.visit_expr(expr),
+        }
+    }
+}
+
+/// Substitutes the parameters of a macro by the arguments of a call.
+pub(crate) struct Substitute<'a> {
+    pub args: FxHashMap<String, &'a Expr>,
+    /// Range of the argument and of the place that needed a name.
+    pub errors: Vec<(Range, Range)>,
+}
+
+impl<'a> Substitute<'a> {
+    /// Substitutes a parameter in a place that only accepts names.
+    fn substitute_name(&mut self, ident: &mut Ident) {
+        let (base, rest) = match ident.to_str().split_once('.') {
+            Some((base, rest)) => (base, Some(rest)),
+            None => (ident.to_str(), None),
+        };
+        let Some(arg) = self.args.get(base) else { return };
+        match &arg.data {
+            ExprKind::Var { name } => {
+                let name = match rest {
+                    Some(rest) => format!("{}.{}", name, rest),
+                    None => name.to_string(),
+                };
+                *ident = Ident::new_static(&name, ident.range)
+            }
+            _ => self.errors.push((arg.range, ident.range)),
+        }
+    }
+}
+
+impl<'a> Visitor for Substitute<'a> {
+    fn visit_match(&mut self, matcher: &mut Match) {
+        self.substitute_name(&mut matcher.scrutinee);
+        for (name, _) in &mut matcher.with_vars {
+            self.substitute_name(name);
+        }
+        walk_match(self, matcher)
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match &mut expr.data {
+            ExprKind::Var { name } if self.args.contains_key(name.to_str()) => {
+                *expr = self.args[name.to_str()].clone();
+            }
+            ExprKind::Var { name } => self.substitute_name(name),
+            ExprKind::Open { var_name, .. } => {
+                self.substitute_name(var_name);
+                walk_expr(self, expr)
+            }
+            _ => walk_expr(self, expr),
+        }
+    }
+}
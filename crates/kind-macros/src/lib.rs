@@ -0,0 +1,191 @@
+//! Expands the syntax macros of a module. A macro is declared
+//! at top level like
+//!
+//! ```kind2
+//! macro Twice x { Pair.new x x }
+//! ```
+//!
+//! and each call of it (e.g. `Twice (+ 1 2)`) inside the same module
+//! is replaced by its body. The expansion is hygienic, so the names
+//! that are bound inside the body never capture the variables of
+//! the arguments.
+
+use std::sync::mpsc::Sender;
+
+use fxhash::FxHashMap;
+use kind_report::data::Diagnostic;
+use kind_tree::concrete::expr::{Binding, Expr, ExprKind};
+use kind_tree::concrete::visitor::{walk_expr, Visitor};
+use kind_tree::concrete::{MacroDecl, Module};
+
+use crate::diagnostic::MacroDiagnostic;
+use crate::hygiene::{Binders, Hygiene, Substitute};
+
+pub mod diagnostic;
+mod hygiene;
+
+type Channel = Sender<Box<dyn Diagnostic>>;
+
+pub struct Expand<'a> {
+    macros: FxHashMap<String, &'a MacroDecl>,
+    errors: Channel,
+    /// Macros that are being expanded right now, so we
+    /// can stop recursive expansions.
+    stack: Vec<String>,
+    expansions: usize,
+    failed: bool,
+}
+
+impl<'a> Expand<'a> {
+    fn send_err(&mut self, err: MacroDiagnostic) {
+        self.errors.send(Box::new(err)).unwrap();
+        self.failed = true;
+    }
+
+    /// Returns the expansion of a call or `None` if it fails.
+    fn expand_call(&mut self, mac: &MacroDecl, expr: &Expr, args: &[Binding]) -> Option<Expr> {
+        let mut positional = Vec::new();
+
+        for arg in args {
+            match arg {
+                Binding::Positional(arg) => positional.push(arg.as_ref()),
+                Binding::Named(range, _, _) => {
+                    self.send_err(MacroDiagnostic::NamedArgument(*range, mac.name.range));
+                    return None;
+                }
+            }
+        }
+
+        if positional.len() != mac.params.len() {
+            self.send_err(MacroDiagnostic::WrongArity(
+                expr.range,
+                mac.name.range,
+                mac.params.len(),
+                positional.len(),
+            ));
+            return None;
+        }
+
+        if self.stack.contains(&mac.name.to_string()) {
+            self.send_err(MacroDiagnostic::RecursiveMacro(expr.range, mac.name.range));
+            return None;
+        }
+
+        self.expansions += 1;
+
+        let mut body = mac.body.clone();
+
+        let mut binders = Binders::default();
+        binders.visit_expr(&mut body);
+
+        Hygiene::new(&binders.names, self.expansions).visit_expr(&mut body);
+
+        let mut subst = Substitute {
+            args: mac
+                .params
+                .iter()
+                .map(|x| x.to_string())
+                .zip(positional)
+                .collect(),
+            errors: Vec::new(),
+        };
+
+        subst.visit_expr(&mut body);
+
+        if !subst.errors.is_empty() {
+            for (arg, place) in subst.errors {
+                self.send_err(MacroDiagnostic::ArgumentShouldBeAName(arg, place));
+            }
+            return None;
+        }
+
+        body.range = expr.range;
+
+        Some(*body)
+    }
+}
+
+impl<'a> Visitor for Expand<'a> {
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr(self, expr);
+
+        let ExprKind::Constr { name, args } = &expr.data else { return };
+        let Some(mac) = self.macros.get(name.to_str()).cloned() else { return };
+
+        if let Some(expansion) = self.expand_call(mac, expr, args) {
+            *expr = expansion;
+            self.stack.push(mac.name.to_string());
+            self.visit_expr(expr);
+            self.stack.pop();
+        }
+    }
+}
+
+/// Checks that a macro does not repeat or bind the names of its parameters.
+fn check_macro(errors: &Channel, mac: &MacroDecl) -> bool {
+    let mut failed = false;
+    let mut params: FxHashMap<String, &kind_tree::symbol::Ident> = FxHashMap::default();
+
+    for param in &mac.params {
+        if let Some(fst) = params.get(param.to_str()) {
+            let err = MacroDiagnostic::RepeatedParameter(fst.range, param.range);
+            errors.send(Box::new(err)).unwrap();
+            failed = true;
+        } else {
+            params.insert(param.to_string(), param);
+        }
+    }
+
+    let mut binders = Binders::default();
+    binders.visit_expr(&mut mac.body.clone());
+
+    for binder in binders.names {
+        if let Some(param) = params.get(binder.to_str()) {
+            let err = MacroDiagnostic::ShadowedParameter(binder.range, param.range);
+            errors.send(Box::new(err)).unwrap();
+            failed = true;
+        }
+    }
+
+    failed
+}
+
+/// Expands all of the macro calls inside the entries of a module.
+/// Returns `true` if any error occurred.
+pub fn expand_macros(errors: Channel, module: &mut Module) -> bool {
+    if module.macros.is_empty() {
+        return false;
+    }
+
+    let mut failed = false;
+    let mut macros: FxHashMap<String, &MacroDecl> = FxHashMap::default();
+
+    for mac in &module.macros {
+        if let Some(fst) = macros.get(mac.name.to_str()) {
+            let err = MacroDiagnostic::RepeatedMacro(fst.name.range, mac.name.range);
+            errors.send(Box::new(err)).unwrap();
+            failed = true;
+        } else {
+            failed |= check_macro(&errors, mac);
+            macros.insert(mac.name.to_string(), mac);
+        }
+    }
+
+    if failed {
+        return true;
+    }
+
+    let mut expand = Expand {
+        macros,
+        errors,
+        stack: Vec::new(),
+        expansions: 0,
+        failed: false,
+    };
+
+    for entry in module.entries.iter_mut() {
+        expand.visit_top_level(entry)
+    }
+
+    expand.failed
+}
@@ -0,0 +1,95 @@
+//! Optimizations over the untyped tree that run after the erasure
+//! and before the code generation, so all of the targets can use
+//! them. The passes are
+//!
+//! * [simplify][simplify] - Folds the operations over constants, reduces the
+//!   applications of lambdas and removes the `let`s that are not used.
+//! * [unreachable][unreachable] - Removes the entries that cannot be
+//!   reached from the entrypoints.
+
+use std::fmt::Display;
+
+use kind_tree::untyped;
+
+pub mod simplify;
+pub mod unreachable;
+
+/// Passes that are enabled in the optimizer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Evaluates binary operations over `U60` literals and propagates
+    /// the literals that are bound by `let`s.
+    pub fold_constants: bool,
+    /// Turns applications of lambdas into `let`s.
+    pub beta_reduce: bool,
+    pub remove_dead_lets: bool,
+    pub remove_unreachable: bool,
+    /// Inlines the functions that are not recursive and whose body
+    /// has at most this number of nodes. It runs with the inlining of
+    /// the functions marked with `#inline`.
+    pub inline_threshold: Option<usize>,
+}
+
+/// Threshold of the automatic inlining in the level 2.
+pub const DEFAULT_INLINE_THRESHOLD: usize = 16;
+
+impl Options {
+    /// Options of each optimization level of the command line. The
+    /// level 0 does nothing, the level 1 enables the passes that only
+    /// remove code and the level 2 (or more) enables all of them,
+    /// including the inlining of small functions.
+    pub fn from_level(level: u8) -> Options {
+        Options {
+            fold_constants: level >= 1,
+            beta_reduce: level >= 2,
+            remove_dead_lets: level >= 1,
+            remove_unreachable: level >= 1,
+            inline_threshold: (level >= 2).then_some(DEFAULT_INLINE_THRESHOLD),
+        }
+    }
+}
+
+/// Number of rewrites done by each pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub folded: u64,
+    pub beta_reduced: u64,
+    pub dead_lets: u64,
+    pub unreachable: u64,
+}
+
+impl Stats {
+    pub fn total(&self) -> u64 {
+        self.folded + self.beta_reduced + self.dead_lets + self.unreachable
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} folded, {} beta reduced, {} dead lets, {} unreachable entries",
+            self.folded, self.beta_reduced, self.dead_lets, self.unreachable
+        )
+    }
+}
+
+/// Optimizes the book in place. The `entrypoints` are the entries
+/// that cannot be removed, like the ones given to the erasure.
+pub fn optimize_book(
+    book: &mut untyped::Book,
+    options: &Options,
+    entrypoints: &[String],
+) -> Stats {
+    let mut stats = Stats::default();
+
+    if options.fold_constants || options.beta_reduce || options.remove_dead_lets {
+        simplify::simplify_book(book, options, &mut stats);
+    }
+
+    if options.remove_unreachable {
+        unreachable::remove_unreachable(book, entrypoints, &mut stats);
+    }
+
+    stats
+}
@@ -0,0 +1,159 @@
+//! Simplifies the bodies of the rules from the leaves to the root,
+//! so the result of a rewrite can enable the rewrites of the
+//! expressions around it (e.g. `(x => (+ x 1)) 2` becomes `3`).
+
+use fxhash::FxHashMap;
+use kind_pass::unbound::subst::subst_on_expr;
+use kind_tree::untyped::{Book, Expr, ExprKind};
+use kind_tree::Operator;
+
+use crate::{Options, Stats};
+
+const U60_MASK: u64 = 0xFFF_FFFF_FFFF_FFFF;
+
+/// Evaluates an operation with the same semantics of the HVM. The
+/// operations that are not defined (e.g. division by zero) are not
+/// folded.
+fn fold_u60(op: Operator, a: u64, b: u64) -> Option<u64> {
+    use Operator::*;
+    let res = match op {
+        Add => a.wrapping_add(b) & U60_MASK,
+        Sub => a.wrapping_sub(b) & U60_MASK,
+        Mul => a.wrapping_mul(b) & U60_MASK,
+        Div => a.checked_div(b)?,
+        Mod => a.checked_rem(b)?,
+        And => a & b,
+        Or => a | b,
+        Xor => a ^ b,
+        Shl => a.checked_shl(u32::try_from(b).ok()?)? & U60_MASK,
+        Shr => a.checked_shr(u32::try_from(b).ok()?)?,
+        Ltn => (a < b) as u64,
+        Lte => (a <= b) as u64,
+        Eql => (a == b) as u64,
+        Gte => (a >= b) as u64,
+        Gtn => (a > b) as u64,
+        Neq => (a != b) as u64,
+    };
+    Some(res)
+}
+
+/// Checks if a variable occurs free inside an expression.
+fn occurs(name: &str, expr: &Expr) -> bool {
+    match &expr.data {
+        ExprKind::Var { name: var } => var.to_str() == name,
+        ExprKind::Lambda { param, body, .. } => param.to_str() != name && occurs(name, body),
+        ExprKind::App { fun, args } => occurs(name, fun) || args.iter().any(|x| occurs(name, x)),
+        ExprKind::Fun { args, .. } | ExprKind::Ctr { args, .. } => {
+            args.iter().any(|x| occurs(name, x))
+        }
+        ExprKind::Let { name: var, val, next } => {
+            occurs(name, val) || (var.to_str() != name && occurs(name, next))
+        }
+        ExprKind::Binary { left, right, .. } => occurs(name, left) || occurs(name, right),
+        ExprKind::U60 { .. } | ExprKind::F60 { .. } | ExprKind::Str { .. } | ExprKind::Err => {
+            false
+        }
+    }
+}
+
+struct Simplify<'a> {
+    options: &'a Options,
+    stats: &'a mut Stats,
+}
+
+impl<'a> Simplify<'a> {
+    fn simplify(&mut self, expr: &mut Box<Expr>) {
+        match &mut expr.data {
+            ExprKind::Lambda { body, .. } => self.simplify(body),
+            ExprKind::App { fun, args } => {
+                self.simplify(fun);
+                args.iter_mut().for_each(|x| self.simplify(x));
+                if self.options.beta_reduce {
+                    self.beta_reduce(expr);
+                }
+            }
+            ExprKind::Fun { args, .. } | ExprKind::Ctr { args, .. } => {
+                args.iter_mut().for_each(|x| self.simplify(x))
+            }
+            ExprKind::Let { val, next, .. } => {
+                self.simplify(val);
+                self.simplify(next);
+                self.simplify_let(expr);
+            }
+            ExprKind::Binary { op, left, right } => {
+                self.simplify(left);
+                self.simplify(right);
+                if !self.options.fold_constants {
+                    return;
+                }
+                if let (ExprKind::U60 { numb: a }, ExprKind::U60 { numb: b }) =
+                    (&left.data, &right.data)
+                {
+                    if let Some(numb) = fold_u60(*op, *a, *b) {
+                        self.stats.folded += 1;
+                        *expr = Expr::u60(expr.range, numb);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Turns `(x => body) arg` into `let x = arg; body`, which keeps
+    /// the argument shared and never captures its variables.
+    fn beta_reduce(&mut self, expr: &mut Box<Expr>) {
+        let ExprKind::App { fun, args } = &mut expr.data else { return };
+
+        let ExprKind::Lambda { param, body, .. } = &mut fun.data else { return };
+
+        // The other arguments are applied inside of the `let`, so they
+        // cannot use the name of the parameter.
+        if args.iter().skip(1).any(|x| occurs(param.to_str(), x)) {
+            return;
+        }
+
+        self.stats.beta_reduced += 1;
+
+        let arg = args.remove(0);
+        let mut reduced = Expr::let_(expr.range, param.clone(), arg, body.clone());
+
+        if !args.is_empty() {
+            if let ExprKind::Let { next, .. } = &mut reduced.data {
+                let range = next.range;
+                let fun = std::mem::replace(next, Expr::u60(range, 0));
+                *next = Expr::app(range, fun, std::mem::take(args));
+                self.beta_reduce(next);
+            }
+        }
+
+        self.simplify_let(&mut reduced);
+        *expr = reduced;
+    }
+
+    /// Simplifies a `let` whose value and body are already simplified.
+    fn simplify_let(&mut self, expr: &mut Box<Expr>) {
+        let ExprKind::Let { name, val, next } = &mut expr.data else { return };
+
+        if self.options.fold_constants && matches!(val.data, ExprKind::U60 { .. }) {
+            let subst = FxHashMap::from_iter([(name.to_string(), val.clone())]);
+            subst_on_expr(next, subst);
+            self.simplify(next);
+        }
+
+        if self.options.remove_dead_lets && !occurs(name.to_str(), next) {
+            self.stats.dead_lets += 1;
+            let range = next.range;
+            *expr = std::mem::replace(next, Expr::u60(range, 0));
+        }
+    }
+}
+
+pub fn simplify_book(book: &mut Book, options: &Options, stats: &mut Stats) {
+    let mut state = Simplify { options, stats };
+
+    for (_, entry) in book.entrs.iter_mut() {
+        for rule in &mut entry.rules {
+            state.simplify(&mut rule.body);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Removes the entries that are not used after the other passes.
+//! The roots are the same ones of the erasure: the entrypoints
+//! and the entries that are marked with `#keep`, `#kdl_run` or
+//! used as `#kdl_state`.
+
+use fxhash::FxHashSet;
+use kind_pass::graph::collect_refs;
+use kind_tree::untyped::Book;
+
+use crate::Stats;
+
+pub fn remove_unreachable(book: &mut Book, entrypoints: &[String], stats: &mut Stats) {
+    let mut queue: Vec<String> = entrypoints.to_vec();
+
+    for entry in book.entrs.values() {
+        if entry.attrs.keep || entry.attrs.kdl_run {
+            queue.push(entry.name.to_string());
+        }
+        if let Some(state) = &entry.attrs.kdl_state {
+            queue.push(state.to_string());
+        }
+    }
+
+    let mut reachable = FxHashSet::default();
+
+    while let Some(name) = queue.pop() {
+        if reachable.contains(&name) {
+            continue;
+        }
+
+        let Some(entry) = book.entrs.get(&name) else { continue };
+
+        for rule in &entry.rules {
+            rule.pats.iter().for_each(|x| collect_refs(x, &mut queue));
+            collect_refs(&rule.body, &mut queue);
+        }
+
+        reachable.insert(name);
+    }
+
+    let unreachable: Vec<String> = book
+        .entrs
+        .keys()
+        .filter(|name| !reachable.contains(*name))
+        .cloned()
+        .collect();
+
+    for name in unreachable {
+        stats.unreachable += 1;
+        book.entrs.remove(&name);
+        book.names.remove(&name);
+    }
+}
@@ -0,0 +1,485 @@
+//! Parser of the Kind Core syntax, the one that `kind2 to-kind-core`
+//! prints, back to a desugared book. The text doesn't say which names
+//! are functions or constructors, so the names that start a line are
+//! the entries and the entries without rules are the constructors, like
+//! in the desugarer. Variables, holes and names that aren't entries are
+//! resolved by their scope. The families are not part of the text, so
+//! the book is only good for type checking without coverage.
+
+use std::sync::mpsc::Sender;
+
+use fxhash::FxHashMap;
+use kind_report::data::Diagnostic;
+use kind_span::{Range, SyntaxCtxIndex};
+use kind_tree::desugared::{AppBinding, Argument, Book, Entry, Expr, ExprKind, Rule};
+use kind_tree::symbol::{Ident, QualifiedIdent};
+use kind_tree::{Attributes, Operator};
+
+use crate::diagnostic::SyntaxDiagnostic;
+use crate::lexer::state::Lexer;
+use crate::lexer::tokens::Token;
+use crate::state::Parser;
+
+pub struct CoreParser<'a> {
+    parser: Parser<'a>,
+    /// Names of the entries and if they have rules.
+    entries: FxHashMap<String, bool>,
+    /// Variables that are bound in the current position.
+    scope: Vec<String>,
+    /// If it's parsing the patterns of a rule, where the unknown names
+    /// are variables that are bound by the rule.
+    in_pattern: bool,
+    holes: u64,
+}
+
+fn name_of(token: &Token) -> Option<String> {
+    match token {
+        Token::LowerId(name) | Token::UpperId(name, None) => Some(name.clone()),
+        Token::UpperId(name, Some(aux)) => Some(format!("{}/{}", name, aux)),
+        Token::Return => Some("return".to_string()),
+        Token::Ask => Some("ask".to_string()),
+        Token::With => Some("with".to_string()),
+        _ => None,
+    }
+}
+
+fn operator_of(token: &Token) -> Option<Operator> {
+    match token {
+        Token::Plus => Some(Operator::Add),
+        Token::Minus => Some(Operator::Sub),
+        Token::Star => Some(Operator::Mul),
+        Token::Slash => Some(Operator::Div),
+        Token::Percent => Some(Operator::Mod),
+        Token::Ampersand => Some(Operator::And),
+        Token::Bar => Some(Operator::Or),
+        Token::Hat => Some(Operator::Xor),
+        Token::GreaterGreater => Some(Operator::Shr),
+        Token::LessLess => Some(Operator::Shl),
+        Token::Less => Some(Operator::Ltn),
+        Token::LessEq => Some(Operator::Lte),
+        Token::EqEq => Some(Operator::Eql),
+        Token::GreaterEq => Some(Operator::Gte),
+        Token::Greater => Some(Operator::Gtn),
+        Token::BangEq => Some(Operator::Neq),
+        _ => None,
+    }
+}
+
+/// Counts the lines that start with each name. Each entry has a line
+/// for its signature and one for each of its rules.
+fn collect_entries(ctx: SyntaxCtxIndex, input: &str) -> FxHashMap<String, bool> {
+    let mut lexer = Lexer::new(input, input.chars().peekable(), ctx);
+    let (sender, _receiver) = std::sync::mpsc::channel();
+    let mut entries = FxHashMap::default();
+    let mut first = true;
+
+    loop {
+        let (is_break, token, _) = lexer.get_next_no_error(sender.clone());
+        if token.is_eof() {
+            break;
+        }
+        if is_break || first {
+            if let Some(name) = name_of(&token) {
+                entries
+                    .entry(name)
+                    .and_modify(|has_rules| *has_rules = true)
+                    .or_insert(false);
+            }
+        }
+        first = false;
+    }
+
+    entries
+}
+
+impl<'a> CoreParser<'a> {
+    pub fn new(parser: Parser<'a>, entries: FxHashMap<String, bool>) -> CoreParser<'a> {
+        CoreParser {
+            parser,
+            entries,
+            scope: Vec::new(),
+            in_pattern: false,
+            holes: 0,
+        }
+    }
+
+    fn bump(&mut self) -> Range {
+        self.parser.advance().1
+    }
+
+    fn expect(&mut self, token: Token) -> Result<Range, SyntaxDiagnostic> {
+        Ok(self.parser.eat_variant(token)?.1)
+    }
+
+    fn parse_name(&mut self) -> Result<Ident, SyntaxDiagnostic> {
+        match name_of(self.parser.get()) {
+            Some(name) => Ok(Ident::new(name, self.bump())),
+            None => self.parser.fail(vec![]),
+        }
+    }
+
+    fn is_binder(&self, at: usize) -> bool {
+        self.parser.peek(at).same_variant(&Token::LPar)
+            && name_of(self.parser.peek(at + 1)).is_some()
+            && self.parser.peek(at + 2).same_variant(&Token::Colon)
+    }
+
+    fn resolve(&mut self, name: Ident) -> Box<Expr> {
+        let range = name.range;
+        let text = name.to_string();
+
+        if self.scope.contains(&text) {
+            return Expr::var(name);
+        }
+
+        match text.as_str() {
+            "Type" => return Expr::typ(range),
+            "Data.U60" => return Expr::type_u60(range),
+            "Data.F60" => return Expr::type_f60(range),
+            "ERR" => return Expr::err(range),
+            _ => (),
+        }
+
+        let qualified = QualifiedIdent::new_static(&text, None, range);
+        match self.entries.get(&text) {
+            Some(true) => Expr::fun(range, qualified, vec![]),
+            Some(false) => Expr::ctr(range, qualified, vec![]),
+            None => {
+                if self.in_pattern {
+                    self.scope.push(text);
+                }
+                Expr::var(name)
+            }
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Box<Expr>, SyntaxDiagnostic> {
+        let range = self.parser.range();
+        match self.parser.get().clone() {
+            Token::LPar => self.parse_paren(),
+            Token::Num60(numb) => {
+                self.bump();
+                Ok(Expr::num_u60(range, numb))
+            }
+            Token::Nat(numb) => {
+                self.bump();
+                let succ = QualifiedIdent::new_static("Data.Nat.succ", None, range);
+                let zero = QualifiedIdent::new_static("Data.Nat.zero", None, range);
+                let mut expr = Expr::ctr(range, zero, vec![]);
+                for _ in 0..numb {
+                    expr = Expr::ctr(range, succ.clone(), vec![expr]);
+                }
+                Ok(expr)
+            }
+            Token::Str(val) => {
+                self.bump();
+                Ok(Expr::str(range, val))
+            }
+            Token::Hole => {
+                self.bump();
+                self.holes += 1;
+                Ok(Expr::hole(range, self.holes - 1))
+            }
+            Token::Help(name) => {
+                self.bump();
+                Ok(Expr::hlp(range, Ident::new(name, range)))
+            }
+            _ => {
+                let name = self.parse_name()?;
+                Ok(self.resolve(name))
+            }
+        }
+    }
+
+    /// Parses a chain of dependent functions, like `(a : A) -> ~B -> C`.
+    fn parse_all(&mut self) -> Result<Box<Expr>, SyntaxDiagnostic> {
+        let start = self.parser.range();
+        let erased = self.parser.check_and_eat(Token::Tilde);
+
+        let (param, typ) = if self.is_binder(0) {
+            self.bump();
+            let param = self.parse_name()?;
+            self.expect(Token::Colon)?;
+            let typ = self.parse_atom()?;
+            self.expect(Token::RPar)?;
+            (param, typ)
+        } else {
+            let typ = self.parse_atom()?;
+            if !erased && !self.parser.check_actual(Token::RightArrow) {
+                return Ok(typ);
+            }
+            (Ident::new_static("_", typ.range), typ)
+        };
+
+        self.expect(Token::RightArrow)?;
+        self.scope.push(param.to_string());
+        let body = self.parse_all()?;
+        self.scope.pop();
+
+        Ok(Expr::all(start.mix(body.range), param, typ, body, erased))
+    }
+
+    fn parse_paren(&mut self) -> Result<Box<Expr>, SyntaxDiagnostic> {
+        let start = self.expect(Token::LPar)?;
+
+        if self.parser.check_and_eat(Token::HashHash) {
+            let name = self.parse_name()?;
+            let (name, redx) = match name.to_string().split_once('/') {
+                Some((name, redx)) => (name.to_string(), redx.to_string()),
+                None => {
+                    self.expect(Token::Slash)?;
+                    let redx = self.parser.eat(|x| match x {
+                        Token::Num60(numb) => Some(numb.to_string()),
+                        _ => None,
+                    })?;
+                    (name.to_string(), redx)
+                }
+            };
+            let Ok(redx) = redx.parse() else {
+                return self.parser.fail(vec![]);
+            };
+            let indx = self.scope.iter().position(|x| *x == name).unwrap_or(0);
+            let expr = self.parse_atom()?;
+            let end = self.expect(Token::RPar)?;
+            let name = Ident::new(name, start);
+            return Ok(Expr::sub(start.mix(end), name, indx, redx, expr));
+        }
+
+        if let Some(op) = operator_of(self.parser.get()) {
+            self.bump();
+            let left = self.parse_atom()?;
+            let right = self.parse_atom()?;
+            let end = self.expect(Token::RPar)?;
+            return Ok(Expr::binary(start.mix(end), op, left, right));
+        }
+
+        if self.parser.check_actual_id("let")
+            && name_of(self.parser.peek(1)).is_some()
+            && self.parser.peek(2).same_variant(&Token::Eq)
+        {
+            self.bump();
+            let name = self.parse_name()?;
+            self.expect(Token::Eq)?;
+            let val = self.parse_atom()?;
+            self.expect(Token::Semi)?;
+            self.scope.push(name.to_string());
+            let next = self.parse_atom()?;
+            self.scope.pop();
+            let end = self.expect(Token::RPar)?;
+            return Ok(Expr::let_(start.mix(end), name, val, next));
+        }
+
+        let erased = self.parser.get().same_variant(&Token::Tilde);
+        let at = if erased { 1 } else { 0 };
+
+        if name_of(self.parser.peek(at)).is_some()
+            && self.parser.peek(at + 1).same_variant(&Token::FatArrow)
+        {
+            self.parser.check_and_eat(Token::Tilde);
+            let param = self.parse_name()?;
+            self.expect(Token::FatArrow)?;
+            self.scope.push(param.to_string());
+            let body = self.parse_atom()?;
+            self.scope.pop();
+            let end = self.expect(Token::RPar)?;
+            return Ok(Expr::lambda(start.mix(end), param, body, erased));
+        }
+
+        if erased || self.is_binder(0) {
+            let expr = self.parse_all()?;
+            self.expect(Token::RPar)?;
+            return Ok(expr);
+        }
+
+        let is_name = name_of(self.parser.get()).is_some();
+        let head = self.parse_atom()?;
+
+        if self.parser.check_actual(Token::RightArrow) {
+            self.bump();
+            let param = Ident::new_static("_", head.range);
+            let body = self.parse_all()?;
+            let end = self.expect(Token::RPar)?;
+            return Ok(Expr::all(start.mix(end), param, head, body, false));
+        }
+
+        if self.parser.check_and_eat(Token::ColonColon) {
+            let typ = self.parse_atom()?;
+            let end = self.expect(Token::RPar)?;
+            return Ok(Expr::ann(start.mix(end), head, typ));
+        }
+
+        let mut args = Vec::new();
+        while !self.parser.check_actual(Token::RPar) {
+            if self.parser.check_and_eat(Token::Tilde) {
+                self.expect(Token::LPar)?;
+                let data = self.parse_atom()?;
+                self.expect(Token::RPar)?;
+                args.push(AppBinding { data, erased: true });
+            } else {
+                let data = self.parse_atom()?;
+                args.push(AppBinding {
+                    data,
+                    erased: false,
+                });
+            }
+        }
+        let range = start.mix(self.expect(Token::RPar)?);
+
+        // A name between parentheses is an application without arguments,
+        // because the names of the entries are printed without them.
+        if args.is_empty() {
+            return Ok(Expr::app(range, head, args));
+        }
+
+        let spine = || args.iter().map(|x| x.data.clone()).collect();
+        let erased_args = args.iter().any(|x| x.erased);
+
+        match head.data {
+            ExprKind::Fun {
+                name,
+                args: ref head_args,
+            } if is_name && head_args.is_empty() && !erased_args => {
+                Ok(Expr::fun(range, name, spine()))
+            }
+            ExprKind::Ctr {
+                name,
+                args: ref head_args,
+            } if is_name && head_args.is_empty() && !erased_args => {
+                Ok(Expr::ctr(range, name, spine()))
+            }
+            _ => Ok(Expr::app(range, head, args)),
+        }
+    }
+
+    fn parse_argument(&mut self) -> Result<Argument, SyntaxDiagnostic> {
+        let start = self.parser.range();
+        let (hidden, erased, close) = match self.parser.get() {
+            Token::LPar => (false, false, Token::RPar),
+            Token::Plus => (true, false, Token::Greater),
+            Token::Minus => (false, true, Token::RPar),
+            Token::Less => (true, true, Token::Greater),
+            _ => return self.parser.fail(vec![Token::LPar, Token::Less]),
+        };
+
+        self.bump();
+        if hidden && !erased {
+            self.expect(Token::Less)?;
+        } else if erased && !hidden {
+            self.expect(Token::LPar)?;
+        }
+
+        let name = self.parse_name()?;
+        self.expect(Token::Colon)?;
+        let typ = self.parse_atom()?;
+        let end = self.expect(close)?;
+
+        Ok(Argument {
+            hidden,
+            erased,
+            name,
+            typ,
+            range: start.mix(end),
+        })
+    }
+
+    fn parse_rule(&mut self, name: &QualifiedIdent) -> Result<Rule, SyntaxDiagnostic> {
+        let start = self.bump();
+
+        self.in_pattern = true;
+        let mut pats = Vec::new();
+        while !self.parser.check_actual(Token::Eq) {
+            match self.parse_atom() {
+                Ok(pat) => pats.push(pat),
+                Err(err) => {
+                    self.in_pattern = false;
+                    return Err(err);
+                }
+            }
+        }
+        self.in_pattern = false;
+
+        self.expect(Token::Eq)?;
+        let body = self.parse_atom()?;
+
+        Ok(Rule {
+            name: name.clone(),
+            pats,
+            range: start.mix(body.range),
+            body,
+        })
+    }
+
+    fn parse_entry(&mut self) -> Result<Entry, SyntaxDiagnostic> {
+        let start = self.parser.range();
+        let name = self.parse_name()?;
+        let name = QualifiedIdent::new_static(&name.to_string(), None, name.range);
+
+        let mut args = Vec::new();
+        while !self.parser.check_actual(Token::Colon) {
+            let arg = self.parse_argument()?;
+            self.scope.push(arg.name.to_string());
+            args.push(arg);
+        }
+
+        self.expect(Token::Colon)?;
+        let typ = self.parse_atom()?;
+        self.scope.clear();
+
+        let mut range = start.mix(typ.range);
+        let mut rules = Vec::new();
+
+        while self.parser.is_linebreak() && name_of(self.parser.get()) == Some(name.to_string()) {
+            let rule = self.parse_rule(&name)?;
+            self.scope.clear();
+            range = range.mix(rule.range);
+            rules.push(rule);
+        }
+
+        Ok(Entry {
+            name,
+            args,
+            typ,
+            rules,
+            attrs: Attributes::default(),
+            range,
+        })
+    }
+
+    pub fn parse_book(&mut self) -> Book {
+        let mut book = Book::default();
+
+        while !self.parser.get().is_eof() {
+            match self.parse_entry() {
+                Ok(entry) => {
+                    book.entrs.insert(entry.name.to_string(), Box::new(entry));
+                }
+                Err(err) => {
+                    self.parser.send_dignostic(err);
+                    self.scope.clear();
+                    self.in_pattern = false;
+                    // Skips to the next line that starts an entry.
+                    self.bump();
+                    while !self.parser.get().is_eof() && !self.parser.is_linebreak() {
+                        self.bump();
+                    }
+                }
+            }
+        }
+
+        book.holes = self.holes;
+        book
+    }
+}
+
+pub fn parse_core_book(
+    errs: Sender<Box<dyn Diagnostic>>,
+    ctx_id: usize,
+    input: &str,
+) -> (Book, bool) {
+    let ctx = SyntaxCtxIndex::new(ctx_id);
+    let entries = collect_entries(ctx, input);
+    let lexer = Lexer::new(input, input.chars().peekable(), ctx);
+    let mut parser = CoreParser::new(Parser::new(lexer, errs), entries);
+    let book = parser.parse_book();
+    (book, parser.parser.failed)
+}
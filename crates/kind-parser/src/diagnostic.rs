@@ -33,6 +33,8 @@ pub enum SyntaxDiagnostic {
     CannotUseUse(Range),
     ImportsCannotHaveAlias(Range),
     InvalidNumberType(String, Range),
+    MacroCannotHaveAttributes(Range),
+    InstanceTypeShouldBeAClass(Range),
 }
 
 fn encode_name(encode: EncodeSequence) -> &'static str {
@@ -64,6 +66,8 @@ impl Diagnostic for SyntaxDiagnostic {
             SyntaxDiagnostic::ImportsCannotHaveAlias(range) => Some(range.ctx),
             SyntaxDiagnostic::InvalidNumberType(_, range) => Some(range.ctx),
             SyntaxDiagnostic::MatchScrutineeShouldBeAName(range) => Some(range.ctx),
+            SyntaxDiagnostic::MacroCannotHaveAttributes(range) => Some(range.ctx),
+            SyntaxDiagnostic::InstanceTypeShouldBeAClass(range) => Some(range.ctx),
         }
     }
 
@@ -334,6 +338,34 @@ impl Diagnostic for SyntaxDiagnostic {
                     main: true,
                 }],
             },
+            SyntaxDiagnostic::MacroCannotHaveAttributes(range) => DiagnosticFrame {
+                code: 19,
+                severity: Severity::Error,
+                title: "Macros cannot have attributes".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Remove this attribute".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            SyntaxDiagnostic::InstanceTypeShouldBeAClass(range) => DiagnosticFrame {
+                code: 20,
+                severity: Severity::Error,
+                title: "The type of an instance should be a class applied to its arguments".to_string(),
+                subtitles: vec![],
+                hints: vec!["Write it like 'Monoid Data.U60'".to_string()],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
         }
     }
 
@@ -354,7 +386,9 @@ impl Diagnostic for SyntaxDiagnostic {
             | MatchScrutineeShouldBeAName(_)
             | CannotUseUse(_)
             | ImportsCannotHaveAlias(_)
-            | InvalidNumberType(_, _) => Severity::Error,
+            | InvalidNumberType(_, _)
+            | MacroCannotHaveAttributes(_)
+            | InstanceTypeShouldBeAClass(_) => Severity::Error,
             | UnusedDocString(_) => Severity::Warning,
         }
     }
@@ -1,4 +1,5 @@
 //! Crate to parse the kind2 grammar.
+mod desugared;
 mod diagnostic;
 mod expr;
 mod lexer;
@@ -15,6 +16,8 @@ use kind_tree::concrete::Module;
 use lexer::state::*;
 use state::Parser;
 
+pub use desugared::parse_core_book;
+
 pub fn parse_book(errs: Sender<Box<dyn Diagnostic>>, ctx_id: usize, input: &str) -> (Module, bool) {
     let peekable = input.chars().peekable();
     let lexer = Lexer::new(input, peekable, SyntaxCtxIndex::new(ctx_id));
@@ -3,7 +3,7 @@ use kind_tree::concrete::expr::Expr;
 use kind_tree::concrete::pat::{Pat, PatIdent, PatKind};
 
 use kind_tree::concrete::*;
-use kind_tree::symbol::QualifiedIdent;
+use kind_tree::symbol::{Ident, QualifiedIdent};
 use kind_tree::telescope::Telescope;
 
 use crate::diagnostic::SyntaxDiagnostic;
@@ -13,8 +13,15 @@ use crate::state::Parser;
 pub mod attributes;
 pub mod type_decl;
 
+/// Macros are declared at top level but they are not
+/// entries of the module.
+enum Declaration {
+    TopLevel(TopLevel),
+    Macro(MacroDecl),
+}
+
 fn is_hidden_arg(token: &Token) -> bool {
-    matches!(token, Token::Greater)
+    matches!(token, Token::Greater | Token::RBracket)
 }
 
 impl<'a> Parser<'a> {
@@ -23,6 +30,7 @@ impl<'a> Parser<'a> {
             || self.peek(1).same_variant(&Token::LPar)   // '('
             || self.peek(1).same_variant(&Token::LBrace) // '{'
             || self.peek(1).same_variant(&Token::Less)   // '<'
+            || self.peek(1).same_variant(&Token::LBracket) // '['
             || self.peek(1).same_variant(&Token::Minus)  // '-'
             || self.peek(1).same_variant(&Token::Plus)   // '+'
     }
@@ -34,6 +42,10 @@ impl<'a> Parser<'a> {
     fn is_safe_level_start(&self) -> bool {
         self.check_actual_id("type")
             || self.check_actual_id("record")
+            || self.check_actual_id("enum")
+            || self.check_actual_id("macro")
+            || self.check_actual_id("class")
+            || self.check_actual_id("instance")
             || self.get().same_variant(&Token::Hash)
             || self.get().is_doc()
     }
@@ -42,6 +54,7 @@ impl<'a> Parser<'a> {
         match self.get() {
             Token::LPar => Some(Token::RPar),
             Token::Less => Some(Token::Greater),
+            Token::LBracket => Some(Token::RBracket),
             _ => None,
         }
     }
@@ -55,10 +68,11 @@ impl<'a> Parser<'a> {
         let complement = self.complement_binding_op();
         match &complement {
             Some(_) => Ok(self.advance()),
-            None => self.fail(vec![Token::Plus, Token::Minus, Token::LPar, Token::Less]), // TODO: Add multiple
+            None => self.fail(vec![Token::Plus, Token::Minus, Token::LPar, Token::Less, Token::LBracket]), // TODO: Add multiple
         }?;
 
         let hidden = is_hidden_arg(complement.as_ref().unwrap());
+        let instance = matches!(complement, Some(Token::RBracket));
         let name = self.parse_id()?;
 
         let typ = if self.check_and_eat(Token::Colon) {
@@ -67,13 +81,15 @@ impl<'a> Parser<'a> {
             None
         };
 
-        let erased = if hidden { !keep } else { erased };
+        // Instances are values that are used at runtime
+        let erased = if instance { false } else if hidden { !keep } else { erased };
 
         let res = self.eat_variant(complement.unwrap())?.1;
         let range = start.mix(res);
         Ok(Argument {
             hidden,
             erased,
+            instance,
             name,
             typ,
             range,
@@ -241,16 +257,131 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_top_level(&mut self) -> Result<TopLevel, SyntaxDiagnostic> {
+    /// Parses a macro declaration like `macro Twice x { Pair.new x x }`.
+    fn parse_macro_def(&mut self, docs: Vec<String>) -> Result<MacroDecl, SyntaxDiagnostic> {
+        let start = self.range();
+        self.eat_id("macro")?;
+
+        let name = self.parse_upper_id()?;
+
+        let mut params = Vec::new();
+        while self.get().is_lower_id() {
+            params.push(self.parse_id()?);
+        }
+
+        let range = self.range();
+        self.eat_variant(Token::LBrace)?;
+
+        let body = self.parse_expr(true)?;
+
+        let end = self.range();
+        self.eat_closing_keyword(Token::RBrace, range)?;
+
+        Ok(MacroDecl {
+            name,
+            docs,
+            params,
+            body,
+            range: start.mix(end),
+        })
+    }
+
+    /// Parses an instance like `instance U60.monoid : Monoid Data.U60 { empty = 0 }`.
+    /// It's an entry marked with the `instance` attribute whose value
+    /// is the constructor of the class applied to the fields.
+    fn parse_instance_def(
+        &mut self,
+        docs: Vec<String>,
+        mut attrs: Vec<Attribute>,
+    ) -> Result<Entry, SyntaxDiagnostic> {
+        let start = self.range();
+        let (_, keyword) = self.eat_id("instance")?;
+
+        let name = self.parse_upper_id()?;
+
+        self.eat_variant(Token::Colon)?;
+        let typ = self.parse_expr(false)?;
+
+        let constructor = match &typ.data {
+            ExprKind::Constr { name, .. } => name.add_segment("new"),
+            _ => return Err(SyntaxDiagnostic::InstanceTypeShouldBeAClass(typ.range)),
+        };
+
+        let range = self.range();
+        self.eat_variant(Token::LBrace)?;
+
+        let mut fields = Vec::new();
+
+        while !self.get().same_variant(&Token::RBrace) && !self.get().same_variant(&Token::Eof) {
+            let field = self.parse_id()?;
+            self.eat_variant(Token::Eq)?;
+            let value = self.parse_expr(false)?;
+            self.check_and_eat(Token::Comma);
+            fields.push(Binding::Named(field.range.mix(value.range), field, value));
+        }
+
+        let end = self.range();
+        self.eat_closing_keyword(Token::RBrace, range)?;
+
+        attrs.push(Attribute {
+            name: Ident::new_static("instance", keyword),
+            args: vec![],
+            value: None,
+            range: keyword,
+        });
+
+        let body = Box::new(Expr {
+            data: ExprKind::Constr {
+                name: constructor,
+                args: fields,
+            },
+            range: range.mix(end),
+        });
+
+        Ok(Entry {
+            name: name.clone(),
+            docs,
+            args: Telescope::default(),
+            typ,
+            rules: vec![Box::new(Rule {
+                name,
+                pats: Vec::new(),
+                body,
+                range: range.mix(end),
+            })],
+            attrs,
+            range: start.mix(end),
+            generated_by: None,
+        })
+    }
+
+    fn parse_top_level(&mut self) -> Result<Declaration, SyntaxDiagnostic> {
         let docs = self.parse_docs()?;
         let attrs = self.parse_attrs()?;
 
         if self.check_actual_id("type") {
-            Ok(TopLevel::SumType(self.parse_sum_type_def(docs, attrs)?))
+            let sum = self.parse_sum_type_def(docs, attrs)?;
+            Ok(Declaration::TopLevel(TopLevel::SumType(sum)))
         } else if self.check_actual_id("record") {
-            Ok(TopLevel::RecordType(self.parse_record_def(docs, attrs)?))
+            let rec = self.parse_record_def(docs, attrs)?;
+            Ok(Declaration::TopLevel(TopLevel::RecordType(rec)))
+        } else if self.check_actual_id("class") {
+            let rec = self.parse_class_def(docs, attrs)?;
+            Ok(Declaration::TopLevel(TopLevel::RecordType(rec)))
+        } else if self.check_actual_id("instance") {
+            let entry = self.parse_instance_def(docs, attrs)?;
+            Ok(Declaration::TopLevel(TopLevel::Entry(entry)))
+        } else if self.check_actual_id("enum") {
+            let sum = self.parse_enum_def(docs, attrs)?;
+            Ok(Declaration::TopLevel(TopLevel::SumType(sum)))
+        } else if self.check_actual_id("macro") {
+            if let Some(attr) = attrs.first() {
+                return Err(SyntaxDiagnostic::MacroCannotHaveAttributes(attr.range));
+            }
+            Ok(Declaration::Macro(self.parse_macro_def(docs)?))
         } else if self.is_top_level_entry_continuation() {
-            Ok(TopLevel::Entry(self.parse_entry(docs, attrs)?))
+            let entry = self.parse_entry(docs, attrs)?;
+            Ok(Declaration::TopLevel(TopLevel::Entry(entry)))
         } else if self.check_actual_id("use") {
             Err(SyntaxDiagnostic::CannotUseUse(self.range()))
         } else {
@@ -275,6 +406,7 @@ impl<'a> Parser<'a> {
 
     pub fn parse_module(&mut self) -> Module {
         let mut entries: Vec<TopLevel> = Vec::new();
+        let mut macros: Vec<MacroDecl> = Vec::new();
         let mut uses: FxHashMap<String, String> = Default::default();
 
         while self.check_actual_id("use") {
@@ -291,7 +423,8 @@ impl<'a> Parser<'a> {
 
         while !self.get().same_variant(&Token::Eof) {
             match self.parse_top_level() {
-                Ok(entry) => entries.push(entry),
+                Ok(Declaration::TopLevel(entry)) => entries.push(entry),
+                Ok(Declaration::Macro(mac)) => macros.push(mac),
                 Err(err) => {
                     self.advance();
                     self.send_dignostic(err);
@@ -308,6 +441,10 @@ impl<'a> Parser<'a> {
             self.send_dignostic(err);
         }
 
-        Module { entries, uses }
+        Module {
+            entries,
+            uses,
+            macros,
+        }
     }
 }
@@ -1,4 +1,4 @@
-use kind_tree::concrete::{Attribute, Constructor, RecordDecl, SumTypeDecl};
+use kind_tree::concrete::{Attribute, AttributeStyle, Constructor, RecordDecl, SumTypeDecl};
 use kind_tree::symbol::Ident;
 use kind_tree::telescope::Telescope;
 
@@ -70,13 +70,92 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parses an enumeration like `enum Color { red green blue }`. It's
+    /// just a sum type with nullary constructors that derives `enum`.
+    pub fn parse_enum_def(
+        &mut self,
+        docs: Vec<String>,
+        mut attrs: Vec<Attribute>,
+    ) -> Result<SumTypeDecl, SyntaxDiagnostic> {
+        let (_, keyword) = self.eat_id("enum")?;
+
+        let name = self.parse_upper_id()?;
+
+        let range = self.range();
+        self.eat_variant(Token::LBrace)?;
+
+        let mut constructors = vec![];
+
+        while !self.get().same_variant(&Token::RBrace) && !self.get().same_variant(&Token::Eof) {
+            let attrs = self.parse_attrs()?;
+            let docs = self.parse_docs()?;
+            let name = self.parse_any_id()?;
+            self.check_and_eat(Token::Comma);
+            constructors.push(Constructor {
+                name,
+                attrs,
+                docs,
+                args: Telescope::default(),
+                typ: None,
+            });
+        }
+
+        self.eat_closing_keyword(Token::RBrace, range)?;
+
+        attrs.push(Attribute {
+            name: Ident::new_static("derive", keyword),
+            args: vec![AttributeStyle::Ident(
+                keyword,
+                Ident::new_static("enum", keyword),
+            )],
+            value: None,
+            range: keyword,
+        });
+
+        Ok(SumTypeDecl {
+            name,
+            docs,
+            parameters: Telescope::default(),
+            indices: Telescope::default(),
+            constructors,
+            attrs,
+        })
+    }
+
     pub fn parse_record_def(
         &mut self,
         docs: Vec<String>,
         attrs: Vec<Attribute>,
     ) -> Result<RecordDecl, SyntaxDiagnostic> {
         self.eat_id("record")?;
+        self.parse_record_body(docs, attrs)
+    }
 
+    /// Parses a class like `class Monoid (t: Type) { empty : t }`. It's
+    /// just a record that is marked with the `class` attribute, so the
+    /// instances of it can be searched by the desugar.
+    pub fn parse_class_def(
+        &mut self,
+        docs: Vec<String>,
+        mut attrs: Vec<Attribute>,
+    ) -> Result<RecordDecl, SyntaxDiagnostic> {
+        let (_, keyword) = self.eat_id("class")?;
+
+        attrs.push(Attribute {
+            name: Ident::new_static("class", keyword),
+            args: vec![],
+            value: None,
+            range: keyword,
+        });
+
+        self.parse_record_body(docs, attrs)
+    }
+
+    fn parse_record_body(
+        &mut self,
+        docs: Vec<String>,
+        attrs: Vec<Attribute>,
+    ) -> Result<RecordDecl, SyntaxDiagnostic> {
         let name = self.parse_upper_id()?;
 
         let parameters = self.parse_arguments()?;
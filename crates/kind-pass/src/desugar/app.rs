@@ -81,18 +81,33 @@ impl<'a> DesugarState<'a> {
         match &head.data {
             ExprKind::Constr { name, args } => {
                 let entry = self.old_book.get_count_garanteed(name.to_string().as_str());
+                let expected = self.context.expected.take();
 
                 let mut positions = FxHashMap::default();
                 let mut arguments = vec![None; entry.arguments.len()];
+                let mut sources: Vec<Option<&Expr>> = vec![None; entry.arguments.len()];
 
                 let (hidden, _erased) = entry.arguments.count_implicits();
 
+                // Instances can be given by name without the other hidden arguments
+                let named_instances = args
+                    .iter()
+                    .filter(|arg| match arg {
+                        Binding::Named(_, name, _) => entry
+                            .arguments
+                            .iter()
+                            .any(|x| x.instance && x.name.to_str() == name.to_str()),
+                        Binding::Positional(_) => false,
+                    })
+                    .count();
+
                 // Check if we should just fill all the implicits
-                let fill_hidden = args.len() == entry.arguments.len() - hidden;
+                let fill_hidden = args.len() - named_instances == entry.arguments.len() - hidden;
 
                 if fill_hidden {
                     for i in 0..entry.arguments.len() {
-                        if entry.arguments[i].hidden {
+                        // Instances are searched after all of the other arguments
+                        if entry.arguments[i].hidden && !entry.arguments[i].instance {
                             // It's not expected that positional arguments require the range so
                             // it's the reason why we are using a terrible "ghost range"
                             arguments[i] = Some((range, self.gen_hole_expr(range)))
@@ -131,6 +146,7 @@ impl<'a> DesugarState<'a> {
                             if let Some((range, _)) = arguments[pos] {
                                 self.send_err(PassDiagnostic::DuplicatedNamed(range, *r));
                             } else {
+                                sources[pos] = Some(v.as_ref());
                                 arguments[pos] = Some((*r, self.desugar_expr(v)))
                             }
                         }
@@ -145,6 +161,7 @@ impl<'a> DesugarState<'a> {
                                 if (fill_hidden && arg_decl.hidden) || arguments[i].is_some() {
                                     continue;
                                 }
+                                sources[i] = Some(v.as_ref());
                                 arguments[i] = Some((v.range, self.desugar_expr(v)));
                                 break;
                             }
@@ -153,6 +170,22 @@ impl<'a> DesugarState<'a> {
                     }
                 }
 
+                if fill_hidden {
+                    for i in 0..entry.arguments.len() {
+                        if entry.arguments[i].instance && arguments[i].is_none() {
+                            let instance = self.resolve_instance(
+                                range,
+                                name,
+                                &entry.arguments,
+                                &sources,
+                                &expected,
+                                &entry.arguments[i],
+                            );
+                            arguments[i] = Some((range, instance));
+                        }
+                    }
+                }
+
                 if arguments.iter().any(|x| x.is_none()) {
                     return Box::new(desugared::Expr {
                         data: desugared::ExprKind::Err,
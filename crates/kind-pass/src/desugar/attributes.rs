@@ -6,6 +6,18 @@ use crate::diagnostic::PassDiagnostic;
 
 use super::DesugarState;
 
+/// Checks if a list of attributes contains a `derive` with
+/// the argument `name`.
+pub fn derives(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| {
+        attr.name.to_str() == "derive"
+            && attr
+                .args
+                .iter()
+                .any(|arg| matches!(arg, AttributeStyle::Ident(_, id) if id.to_str() == name))
+    })
+}
+
 impl<'a> DesugarState<'a> {
     fn args_should_be_empty(&mut self, attr: &Attribute) {
         if !attr.args.is_empty() {
@@ -44,6 +56,11 @@ impl<'a> DesugarState<'a> {
                     self.attr_without_value(attr);
                     attributes.inlined = true;
                 }
+                "noinline" => {
+                    self.args_should_be_empty(attr);
+                    self.attr_without_value(attr);
+                    attributes.noinline = true;
+                }
                 "keep" => {
                     self.args_should_be_empty(attr);
                     self.attr_without_value(attr);
@@ -59,6 +76,16 @@ impl<'a> DesugarState<'a> {
                     self.attr_without_value(attr);
                     attributes.partial = true;
                 }
+                "class" => {
+                    self.args_should_be_empty(attr);
+                    self.attr_without_value(attr);
+                    attributes.class = true;
+                }
+                "instance" => {
+                    self.args_should_be_empty(attr);
+                    self.attr_without_value(attr);
+                    attributes.instance = true;
+                }
                 "axiom" => {
                     self.args_should_be_empty(attr);
                     self.attr_without_value(attr);
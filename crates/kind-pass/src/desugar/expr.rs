@@ -5,6 +5,7 @@ use kind_tree::symbol::{Ident, QualifiedIdent};
 
 use crate::diagnostic::{PassDiagnostic, Sugar};
 
+use super::instances::type_head;
 use super::DesugarState;
 
 impl<'a> DesugarState<'a> {
@@ -108,7 +109,7 @@ impl<'a> DesugarState<'a> {
                         return desugared::Expr::err(range);
                     }
 
-                    self.mk_desugared_ctr(
+                    self.mk_desugared_fun(
                         range,
                         name,
                         vec![
@@ -119,7 +120,7 @@ impl<'a> DesugarState<'a> {
                     )
                 });
 
-                if let desugared::ExprKind::Ctr { args, .. } = &mut result.data {
+                if let desugared::ExprKind::Fun { args, .. } = &mut result.data {
                     args[0] = self.desugar_expr(&sub.expr);
                 }
 
@@ -145,7 +146,7 @@ impl<'a> DesugarState<'a> {
                     Expr::lambda(
                         name.range,
                         value_ident.clone(),
-                        self.mk_desugared_ctr(
+                        self.mk_desugared_fun(
                             range,
                             name,
                             vec![Expr::var(value_ident.clone()), acc],
@@ -166,7 +167,7 @@ impl<'a> DesugarState<'a> {
                     return Expr::err(range);
                 };
 
-                if let desugared::ExprKind::Ctr { args, .. } = &mut result.data {
+                if let desugared::ExprKind::Fun { args, .. } = &mut result.data {
                     args[0] = self.desugar_expr(&sub.expr);
                 }
 
@@ -187,7 +188,7 @@ impl<'a> DesugarState<'a> {
                         return desugared::Expr::err(range);
                     }
 
-                    self.mk_desugared_ctr(range, name, vec![acc], false)
+                    self.mk_desugared_fun(range, name, vec![acc], false)
                 }),
         }
     }
@@ -482,6 +483,10 @@ impl<'a> DesugarState<'a> {
                 ),
             ),
             Ann { val, typ } => {
+                // The annotation is used to search the instances of a call
+                if let Constr { .. } = val.data {
+                    self.context.expected = type_head(typ);
+                }
                 desugared::Expr::ann(expr.range, self.desugar_expr(val), self.desugar_expr(typ))
             }
             Var { name } => desugared::Expr::var(name.clone()),
@@ -0,0 +1,329 @@
+//! Resolution of the instance arguments (e.g. `[m: Monoid t]`). The
+//! instances are searched by the name of the class and by the heads
+//! of the types that the class is applied to, so the call `Twice 2`
+//! of a function `Twice <t> [m: Monoid t] (x: t)` searches for an
+//! instance of `Monoid Data.U60`.
+//!
+//! The heads of the arguments are discovered only sintatically from
+//! the type annotations of the variables and the definitions that
+//! are used in the arguments.
+
+use fxhash::FxHashMap;
+use kind_span::Range;
+use kind_tree::concrete::expr::Expr;
+use kind_tree::concrete::pat::{Pat, PatKind};
+use kind_tree::concrete::{self, Argument, Binding, ExprKind, Literal, TopLevel};
+use kind_tree::desugared;
+use kind_tree::symbol::{Ident, QualifiedIdent};
+use kind_tree::telescope::Telescope;
+
+use crate::diagnostic::PassDiagnostic;
+
+use super::DesugarState;
+
+/// Where the value of an instance comes from.
+#[derive(Clone, Debug)]
+pub enum InstanceSource {
+    /// A top level definition marked with `#instance`.
+    Global(QualifiedIdent),
+    /// An instance argument of the definition that is being desugared.
+    Local(Ident),
+}
+
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub source: InstanceSource,
+    pub class: String,
+    /// `None` matches every type
+    pub heads: Vec<Option<String>>,
+    pub range: Range,
+}
+
+/// Information about the variables of the rule that is being desugared.
+#[derive(Clone, Debug, Default)]
+pub struct InstanceContext {
+    /// Heads of the types of the variables
+    pub vars: FxHashMap<String, String>,
+    pub locals: Vec<Instance>,
+    /// Head of the annotated type of the call that is being desugared
+    pub expected: Option<String>,
+}
+
+/// Head of a type (e.g. `Data.List` for `Data.List t`).
+pub fn type_head(typ: &Expr) -> Option<String> {
+    match &typ.data {
+        ExprKind::Lit { lit: Literal::Type } => Some("Type".to_string()),
+        ExprKind::Lit {
+            lit: Literal::NumTypeU60,
+        } => Some("Data.U60".to_string()),
+        ExprKind::Lit {
+            lit: Literal::NumTypeF60,
+        } => Some("Data.F60".to_string()),
+        ExprKind::Constr { name, .. } => Some(name.to_string()),
+        ExprKind::Var { name } => Some(name.to_string()),
+        ExprKind::App { fun, .. } => type_head(fun),
+        _ => None,
+    }
+}
+
+fn show_heads(class: &str, heads: &[Option<String>]) -> String {
+    let mut res = class.to_string();
+    for head in heads {
+        res.push(' ');
+        res.push_str(head.as_deref().unwrap_or("_"));
+    }
+    res
+}
+
+impl<'a> DesugarState<'a> {
+    fn is_class(&self, name: &str) -> bool {
+        match self.old_book.entries.get(name) {
+            Some(TopLevel::RecordType(rec)) => rec.attrs.iter().any(|x| x.name.to_str() == "class"),
+            _ => false,
+        }
+    }
+
+    /// Splits a type like `Monoid t` into the class and its arguments.
+    pub(crate) fn class_of<'b>(&self, typ: &'b Expr) -> Option<(String, Vec<&'b Expr>)> {
+        match &typ.data {
+            ExprKind::Constr { name, args } if self.is_class(name.to_str()) => {
+                let mut params = Vec::new();
+                for arg in args {
+                    match arg {
+                        Binding::Positional(expr) => params.push(expr.as_ref()),
+                        Binding::Named(..) => return None,
+                    }
+                }
+                Some((name.to_string(), params))
+            }
+            _ => None,
+        }
+    }
+
+    /// Collects all of the top level instances of the book.
+    pub(crate) fn collect_instances(&mut self) {
+        let book = self.old_book;
+        for top_level in book.entries.values() {
+            let TopLevel::Entry(entry) = top_level else {
+                continue;
+            };
+
+            if !entry.attrs.iter().any(|x| x.name.to_str() == "instance") {
+                continue;
+            }
+
+            if !entry.args.is_empty() {
+                self.send_err(PassDiagnostic::InstanceCannotHaveArguments(
+                    entry.name.range,
+                ));
+                continue;
+            }
+
+            match self.class_of(&entry.typ) {
+                Some((class, params)) => self.instances.push(Instance {
+                    source: InstanceSource::Global(entry.name.clone()),
+                    class,
+                    heads: params.into_iter().map(type_head).collect(),
+                    range: entry.name.range,
+                }),
+                None => self.send_err(PassDiagnostic::NotAClass(entry.typ.range)),
+            }
+        }
+    }
+
+    /// Creates the context of a rule from the arguments of the entry
+    /// and the patterns that bind each one of them.
+    pub(crate) fn enter_rule(&mut self, args: &Telescope<Argument>, pats: &[Box<Pat>]) {
+        let mut context = InstanceContext::default();
+
+        let explicit_only = pats.len() != args.len();
+        let mut pats = pats.iter();
+
+        for arg in args.iter() {
+            let name = if explicit_only && arg.hidden {
+                // Hidden arguments that are not written by the user are
+                // bound to generated names, except for the instances.
+                arg.instance.then(|| arg.name.clone())
+            } else {
+                match pats.next().map(|x| &x.data) {
+                    Some(PatKind::Var(ident)) => Some(ident.0.clone()),
+                    _ => None,
+                }
+            };
+
+            let (Some(name), Some(typ)) = (name, &arg.typ) else {
+                continue;
+            };
+
+            if let Some(head) = type_head(typ) {
+                context.vars.insert(name.to_string(), head);
+            }
+
+            if arg.instance {
+                if let Some((class, params)) = self.class_of(typ) {
+                    context.locals.push(Instance {
+                        source: InstanceSource::Local(name),
+                        class,
+                        heads: params.into_iter().map(type_head).collect(),
+                        range: arg.range,
+                    })
+                }
+            }
+        }
+
+        self.context = context;
+    }
+
+    /// Head of the type of an expression.
+    fn value_head(&self, expr: &Expr) -> Option<String> {
+        match &expr.data {
+            ExprKind::Var { name } => self.context.vars.get(name.to_str()).cloned(),
+            ExprKind::Lit { lit } => match lit {
+                Literal::NumU60(_) | Literal::Char(_) => Some("Data.U60".to_string()),
+                Literal::NumF60(_) => Some("Data.F60".to_string()),
+                Literal::NumU120(_) => Some("Data.U120".to_string()),
+                Literal::Nat(_) => Some("Data.Nat".to_string()),
+                Literal::String(_) => Some("Data.String".to_string()),
+                _ => None,
+            },
+            ExprKind::List { .. } => Some("Data.List".to_string()),
+            ExprKind::Ann { typ, .. } => type_head(typ),
+            ExprKind::Constr { name, .. } => {
+                let meta = self.old_book.meta.get(name.to_str())?;
+                if let Some(rec) = &meta.is_record_cons_of {
+                    return Some(rec.to_string());
+                }
+                match self.old_book.entries.get(name.to_str()) {
+                    // Variables in the return type are not known here
+                    Some(TopLevel::Entry(entry)) => match &entry.typ.data {
+                        ExprKind::Var { .. } => None,
+                        _ => type_head(&entry.typ),
+                    },
+                    Some(_) => Some("Type".to_string()),
+                    None if meta.is_ctr => Some(name.pop_last_segment().to_string()),
+                    None => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Head of the value that a call gives to the parameter `name`. It's
+    /// the argument itself or the type of an argument whose type is the
+    /// parameter.
+    fn param_head(
+        &self,
+        params: &Telescope<Argument>,
+        sources: &[Option<&Expr>],
+        name: &str,
+    ) -> Option<String> {
+        let pos = params.iter().position(|x| x.name.to_str() == name)?;
+
+        if let Some(expr) = sources[pos] {
+            return type_head(expr);
+        }
+
+        params
+            .iter()
+            .zip(sources)
+            .find_map(|(param, source)| match (&param.typ, source) {
+                (Some(typ), Some(expr)) => match &typ.data {
+                    ExprKind::Var { name: var } if var.to_str() == name => self.value_head(expr),
+                    _ => None,
+                },
+                _ => None,
+            })
+    }
+
+    /// Searches the instance for the argument `arg` of a call to
+    /// `callee`. The `sources` are the expressions that the call gives
+    /// to each one of the parameters and `expected` is the head of the
+    /// annotated type of the call.
+    pub(crate) fn resolve_instance(
+        &mut self,
+        range: Range,
+        callee: &QualifiedIdent,
+        params: &Telescope<Argument>,
+        sources: &[Option<&Expr>],
+        expected: &Option<String>,
+        arg: &concrete::Argument,
+    ) -> Box<desugared::Expr> {
+        // Invalid instance arguments are reported in their declaration
+        let Some((class, class_params)) = arg.typ.as_ref().and_then(|typ| self.class_of(typ))
+        else {
+            self.failed = true;
+            return desugared::Expr::err(range);
+        };
+
+        // The parameter that is the return type of the callee
+        let returns = match self.old_book.entries.get(callee.to_str()) {
+            Some(TopLevel::Entry(entry)) => match &entry.typ.data {
+                ExprKind::Var { name } => Some(name.to_str()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let wanted: Vec<Option<String>> = class_params
+            .iter()
+            .map(|param| match &param.data {
+                ExprKind::Var { name }
+                    if params.iter().any(|x| x.name.to_str() == name.to_str()) =>
+                {
+                    self.param_head(params, sources, name.to_str()).or_else(|| {
+                        if returns == Some(name.to_str()) {
+                            expected.clone()
+                        } else {
+                            None
+                        }
+                    })
+                }
+                _ => type_head(param),
+            })
+            .collect();
+
+        let candidates: Vec<&Instance> = self
+            .context
+            .locals
+            .iter()
+            .chain(self.instances.iter())
+            .filter(|inst| {
+                inst.class == class
+                    && inst.heads.len() == wanted.len()
+                    && inst.heads.iter().zip(&wanted).all(|pair| match pair {
+                        (Some(head), Some(want)) => head == want,
+                        _ => true,
+                    })
+            })
+            .collect();
+
+        match candidates.as_slice() {
+            [instance] => match &instance.source {
+                InstanceSource::Global(name) => {
+                    let mut name = name.clone();
+                    name.range = range;
+                    desugared::Expr::fun(range, name, vec![])
+                }
+                InstanceSource::Local(name) => {
+                    desugared::Expr::var(Ident::new(name.to_string(), range))
+                }
+            },
+            [] => {
+                self.send_err(PassDiagnostic::MissingInstance(
+                    range,
+                    show_heads(&class, &wanted),
+                ));
+                desugared::Expr::err(range)
+            }
+            _ => {
+                let ranges = candidates.iter().map(|x| x.range).collect();
+                self.send_err(PassDiagnostic::AmbiguousInstance(
+                    range,
+                    show_heads(&class, &wanted),
+                    ranges,
+                ));
+                desugared::Expr::err(range)
+            }
+        }
+    }
+}
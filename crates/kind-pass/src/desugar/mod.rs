@@ -18,10 +18,13 @@ use kind_tree::{
 
 use crate::diagnostic::{PassDiagnostic, GenericPassError};
 
+use self::instances::{Instance, InstanceContext};
+
 pub mod app;
 pub mod attributes;
 pub mod destruct;
 pub mod expr;
+pub mod instances;
 pub mod top_level;
 pub mod record_field;
 
@@ -31,6 +34,8 @@ pub struct DesugarState<'a> {
     pub new_book: desugared::Book,
     pub name_count: u64,
     pub failed: bool,
+    pub instances: Vec<Instance>,
+    pub context: InstanceContext,
 }
 
 pub fn desugar_book(
@@ -43,6 +48,8 @@ pub fn desugar_book(
         new_book: Default::default(),
         name_count: 0,
         failed: false,
+        instances: Vec::new(),
+        context: Default::default(),
     };
 
     state.desugar_book(book);
@@ -75,6 +82,7 @@ impl<'a> DesugarState<'a> {
     }
 
     pub fn desugar_book(&mut self, book: &concrete::Book) {
+        self.collect_instances();
         for top_level in book.entries.values() {
             self.desugar_top_level(top_level)
         }
@@ -1,4 +1,5 @@
 use kind_span::Range;
+use kind_tree::concrete::pat::Pat;
 use kind_tree::concrete::{self};
 use kind_tree::desugared::{self, ExprKind, Family};
 use kind_tree::symbol::QualifiedIdent;
@@ -6,6 +7,7 @@ use kind_tree::telescope::Telescope;
 
 use crate::diagnostic::{PassDiagnostic, Sugar};
 
+use super::attributes::derives;
 use super::DesugarState;
 
 /// States if a given expression is a type constructor
@@ -32,6 +34,16 @@ pub fn is_data_constructor_of(expr: concrete::expr::Expr, type_name: &str) -> bo
 
 impl<'a> DesugarState<'a> {
     pub fn desugar_argument(&mut self, argument: &concrete::Argument) -> desugared::Argument {
+        if argument.instance {
+            match &argument.typ {
+                Some(typ) if self.class_of(typ).is_none() => {
+                    self.send_err(PassDiagnostic::NotAClass(typ.range))
+                }
+                None => self.send_err(PassDiagnostic::NotAClass(argument.range)),
+                _ => (),
+            }
+        }
+
         let typ = match &argument.typ {
             None => desugared::Expr::typ(argument.range),
             Some(ty) => self.desugar_expr(ty),
@@ -53,13 +65,19 @@ impl<'a> DesugarState<'a> {
         let desugared_params = params.map(|arg| self.desugar_argument(arg));
         let desugared_indices = indices.map(|arg| self.desugar_argument(arg));
 
+        let type_attrs = self.desugar_attributes(&sum_type.attrs);
+
+        // A type that is kept in the targets keeps its constructors too,
+        // so they are not unboxed.
+        let unboxable = !type_attrs.keep && type_attrs.kdl_name.is_none();
+
         let type_constructor = desugared::Entry {
             name: sum_type.name.clone(),
             args: desugared_params.extend(&desugared_indices).to_vec(),
             typ: desugared::Expr::typ(sum_type.name.range),
             rules: Vec::new(),
             range: sum_type.name.range,
-            attrs: self.desugar_attributes(&sum_type.attrs),
+            attrs: type_attrs,
         };
 
         self.new_book
@@ -79,7 +97,10 @@ impl<'a> DesugarState<'a> {
             parameters: desugared_params,
         };
 
-        for cons in &sum_type.constructors {
+        // Values of enumerations are compiled to their numbers
+        let is_enum = derives(&sum_type.attrs, "enum");
+
+        for (i, cons) in sum_type.constructors.iter().enumerate() {
             let cons_ident = sum_type.name.add_segment(cons.name.to_str());
 
             let pre_indices = if cons.typ.is_none() {
@@ -126,6 +147,14 @@ impl<'a> DesugarState<'a> {
                 }
             };
 
+            let mut attrs = self.desugar_attributes(&cons.attrs);
+
+            if is_enum {
+                attrs.enum_value = Some(i as u64);
+            }
+
+            attrs.single_constructor = unboxable && sum_type.constructors.len() == 1;
+
             let data_constructor = desugared::Entry {
                 name: cons_ident.clone(),
                 args: [
@@ -136,7 +165,7 @@ impl<'a> DesugarState<'a> {
                 .concat(),
                 typ,
                 rules: Vec::new(),
-                attrs: self.desugar_attributes(&cons.attrs),
+                attrs,
                 range: cons.name.range,
             };
 
@@ -157,13 +186,16 @@ impl<'a> DesugarState<'a> {
 
         let desugared_params = params.map(|arg| self.desugar_argument(arg));
 
+        let type_attrs = self.desugar_attributes(&rec_type.attrs);
+        let unboxable = !type_attrs.keep && type_attrs.kdl_name.is_none();
+
         let type_constructor = desugared::Entry {
             name: rec_type.name.clone(),
             args: desugared_params.clone().to_vec(),
             typ: desugared::Expr::typ(rec_type.name.range),
             rules: Vec::new(),
             range: rec_type.name.range,
-            attrs: self.desugar_attributes(&rec_type.attrs),
+            attrs: type_attrs,
         };
 
         self.new_book
@@ -203,13 +235,16 @@ impl<'a> DesugarState<'a> {
             })
             .collect::<Vec<desugared::Argument>>();
 
+        let mut attrs = self.desugar_attributes(&rec_type.cons_attrs);
+        attrs.single_constructor = unboxable;
+
         let data_constructor = desugared::Entry {
             name: cons_ident.clone(),
             args: [irrelevant_params.as_slice(), fields_args.as_slice()].concat(),
             typ,
             rules: Vec::new(),
             range: rec_type.constructor.range,
-            attrs: self.desugar_attributes(&rec_type.cons_attrs),
+            attrs,
         };
 
         self.new_book
@@ -328,6 +363,8 @@ impl<'a> DesugarState<'a> {
         args: &Telescope<concrete::Argument>,
         rule: &concrete::Rule,
     ) -> desugared::Rule {
+        self.enter_rule(args, &rule.pats);
+
         let pats = rule
             .pats
             .iter()
@@ -347,7 +384,10 @@ impl<'a> DesugarState<'a> {
             let mut res_pats = Vec::new();
             let mut pat_iter = pats.iter();
             for arg in args.iter() {
-                if arg.hidden {
+                if arg.instance {
+                    // Instances are bound to their names so they can be used in the body
+                    res_pats.push(desugared::Expr::var(arg.name.clone()))
+                } else if arg.hidden {
                     res_pats.push(desugared::Expr::var(self.gen_name(arg.range)))
                 } else {
                     res_pats.push(pat_iter.next().unwrap().to_owned());
@@ -385,10 +425,16 @@ impl<'a> DesugarState<'a> {
             .map(|x| self.desugar_rule(&entry.args, x))
             .collect();
 
+        // The type can use all of the arguments by their names
+        let names = entry.args.iter().map(|x| Pat::var(x.name.clone())).collect::<Vec<_>>();
+        self.enter_rule(&entry.args, &names);
+
+        let typ = self.desugar_expr(&entry.typ);
+
         let res_entry = desugared::Entry {
             name: entry.name.clone(),
             args: entry.args.map(|x| self.desugar_argument(x)).to_vec(),
-            typ: self.desugar_expr(&entry.typ),
+            typ,
             range: entry.range,
             attrs: self.desugar_attributes(&entry.attrs),
             rules,
@@ -414,6 +460,7 @@ impl<'a> DesugarState<'a> {
     }
 
     pub fn desugar_top_level(&mut self, top_level: &concrete::TopLevel) {
+        self.context = Default::default();
         match top_level {
             concrete::TopLevel::SumType(sum) => self.desugar_sum_type(sum),
             concrete::TopLevel::RecordType(rec) => self.desugar_record_type(rec),
@@ -55,10 +55,19 @@ pub enum PassDiagnostic {
     AttributeExpectsAValue(Range),
     DuplicatedAttributeArgument(Range, Range),
     CannotDerive(String, Range),
+    InvalidDerivedName(String, String, Range),
+    DerivedNameAlreadyDefined(String, String, Range, Range),
     AttributeDoesNotExists(Range),
     NeedsAField(Range),
     CannotFindTheField(Range, String),
     CannotAccessType(Range, String),
+    NotAClass(Range),
+    InstanceCannotHaveArguments(Range),
+    MissingInstance(Range, String),
+    AmbiguousInstance(Range, String, Vec<Range>),
+    CannotInlineRecursive(Range, String, Vec<String>),
+    RelevantBecause(String, String, Range, Vec<(Range, String)>),
+    NotRelevant(String, Range),
 }
 
 // TODO: A way to build an error message with methods
@@ -89,10 +98,19 @@ impl Diagnostic for PassDiagnostic {
             PassDiagnostic::AttributeExpectsAValue(range) => Some(range.ctx),
             PassDiagnostic::DuplicatedAttributeArgument(range, _) => Some(range.ctx),
             PassDiagnostic::CannotDerive(_, range) => Some(range.ctx),
+            PassDiagnostic::InvalidDerivedName(_, _, range) => Some(range.ctx),
+            PassDiagnostic::DerivedNameAlreadyDefined(_, _, range, _) => Some(range.ctx),
             PassDiagnostic::AttributeDoesNotExists(range) => Some(range.ctx),
             PassDiagnostic::NeedsAField(range) => Some(range.ctx),
             PassDiagnostic::CannotFindTheField(range, _) => Some(range.ctx),
             PassDiagnostic::CannotAccessType(range, _) => Some(range.ctx),
+            PassDiagnostic::NotAClass(range) => Some(range.ctx),
+            PassDiagnostic::InstanceCannotHaveArguments(range) => Some(range.ctx),
+            PassDiagnostic::MissingInstance(range, _) => Some(range.ctx),
+            PassDiagnostic::AmbiguousInstance(range, _, _) => Some(range.ctx),
+            PassDiagnostic::CannotInlineRecursive(range, _, _) => Some(range.ctx),
+            PassDiagnostic::RelevantBecause(_, _, range, steps) => Some(steps.first().map(|x| x.0).unwrap_or(*range).ctx),
+            PassDiagnostic::NotRelevant(_, range) => Some(range.ctx),
         }
     }
 
@@ -555,6 +573,45 @@ impl Diagnostic for PassDiagnostic {
                 }],
             },
 
+            PassDiagnostic::InvalidDerivedName(name, entry, place) => DiagnosticFrame {
+                code: 209,
+                severity: Severity::Error,
+                title: format!("The derivation '{}' generated an invalid definition '{}'", name, entry),
+                subtitles: vec![],
+                hints: vec!["Derived definitions should be inside the namespace of the type and cannot repeat names".to_string()],
+                positions: vec![Marker {
+                    position: *place,
+                    color: Color::Fst,
+                    text: "Derived here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+
+            PassDiagnostic::DerivedNameAlreadyDefined(name, entry, place, defined) => DiagnosticFrame {
+                code: 209,
+                severity: Severity::Error,
+                title: format!("The derivation '{}' generated the definition '{}' that already exists", name, entry),
+                subtitles: vec![],
+                hints: vec!["Rename the definition or remove the derivation".to_string()],
+                positions: vec![
+                    Marker {
+                        position: *place,
+                        color: Color::Fst,
+                        text: "Derived here!".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *defined,
+                        color: Color::Snd,
+                        text: "Already defined here!".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+
             PassDiagnostic::AttributeExpectsAValue(place) => DiagnosticFrame {
                 code: 209,
                 severity: Severity::Error,
@@ -645,6 +702,140 @@ impl Diagnostic for PassDiagnostic {
                     main: true,
                 }],
             },
+            PassDiagnostic::NotAClass(range) => DiagnosticFrame {
+                code: 215,
+                severity: Severity::Error,
+                title: "This type is not a class.".to_string(),
+                subtitles: vec![],
+                hints: vec!["Classes are declared with the 'class' keyword".to_string()],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            PassDiagnostic::InstanceCannotHaveArguments(range) => DiagnosticFrame {
+                code: 216,
+                severity: Severity::Error,
+                title: "Instances cannot have arguments.".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            PassDiagnostic::MissingInstance(range, wanted) => DiagnosticFrame {
+                code: 217,
+                severity: Severity::Error,
+                title: format!("Cannot find an instance of '{}'.", wanted),
+                subtitles: vec![],
+                hints: vec!["Declare it with the 'instance' keyword or receive it as an instance argument".to_string()],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Required here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            PassDiagnostic::AmbiguousInstance(range, wanted, candidates) => DiagnosticFrame {
+                code: 218,
+                severity: Severity::Error,
+                title: format!("Ambiguous instance of '{}'.", wanted),
+                subtitles: vec![],
+                hints: vec!["Give the instance as a named argument".to_string()],
+                positions: std::iter::once(Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Required here!".to_string(),
+                    no_code: false,
+                    main: true,
+                })
+                .chain(candidates.iter().map(|range| Marker {
+                    position: *range,
+                    color: Color::Snd,
+                    text: "Candidate".to_string(),
+                    no_code: false,
+                    main: false,
+                }))
+                .collect(),
+            },
+            PassDiagnostic::CannotInlineRecursive(range, name, group) => DiagnosticFrame {
+                code: 219,
+                severity: Severity::Warning,
+                title: format!("Cannot inline the recursive function '{}'.", name),
+                subtitles: vec![],
+                hints: if group.len() > 1 {
+                    vec![format!("It calls itself through {}", group.iter().map(|x| format!("'{}'", x)).collect::<Vec<_>>().join(", "))]
+                } else {
+                    vec![]
+                },
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Marked with #inline here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            PassDiagnostic::RelevantBecause(name, entrypoint, range, steps) => {
+                let colors = [Color::Fst, Color::Snd, Color::Thr, Color::For, Color::Fft];
+
+                let positions = if steps.is_empty() {
+                    vec![Marker {
+                        position: *range,
+                        color: Color::Fst,
+                        text: "It's an entrypoint".to_string(),
+                        no_code: false,
+                        main: true,
+                    }]
+                } else {
+                    steps
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (position, text))| Marker {
+                            position: *position,
+                            color: colors[i % colors.len()].clone(),
+                            text: format!("{}. {}", i + 1, text),
+                            no_code: false,
+                            main: i == 0,
+                        })
+                        .collect()
+                };
+
+                DiagnosticFrame {
+                    code: 220,
+                    severity: Severity::Info,
+                    title: if steps.is_empty() {
+                        format!("'{}' is relevant because it's an entrypoint.", name)
+                    } else {
+                        format!("'{}' is relevant because the entrypoint '{}' uses it.", name, entrypoint)
+                    },
+                    subtitles: vec![],
+                    hints: vec![],
+                    positions,
+                }
+            }
+            PassDiagnostic::NotRelevant(name, range) => DiagnosticFrame {
+                code: 221,
+                severity: Severity::Info,
+                title: format!("'{}' is not relevant to the entrypoints, so it's erased.", name),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Defined here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
         }
     }
 
@@ -675,10 +866,18 @@ impl Diagnostic for PassDiagnostic {
             | AttributeExpectsAValue(_)
             | DuplicatedAttributeArgument(_, _)
             | CannotDerive(_, _)
+            | InvalidDerivedName(_, _, _)
+            | DerivedNameAlreadyDefined(_, _, _, _)
             | NeedsAField(_)
             | CannotFindTheField(_, _)
             | CannotAccessType(_, _)
+            | NotAClass(_)
+            | InstanceCannotHaveArguments(_)
+            | MissingInstance(_, _)
+            | AmbiguousInstance(_, _, _)
             | AttributeDoesNotExists(_) => Severity::Error,
+            CannotInlineRecursive(_, _, _) => Severity::Warning,
+            RelevantBecause(_, _, _, _) | NotRelevant(_, _) => Severity::Info,
         }
     }
 }
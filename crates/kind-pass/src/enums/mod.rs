@@ -0,0 +1,80 @@
+use fxhash::FxHashMap;
+use kind_tree::untyped;
+
+struct EnumState {
+    values: FxHashMap<String, u64>,
+}
+
+/// Replaces all the values of the types that derive `enum` by
+/// their numbers, so the targets can compile them straight to
+/// numbers, and removes their constructors from the book unless
+/// they are marked with `#keep`.
+pub fn lower_enums(book: &mut untyped::Book) {
+    let mut values = FxHashMap::default();
+
+    for entr in book.entrs.values() {
+        if let Some(numb) = entr.attrs.enum_value {
+            values.insert(entr.name.to_string(), numb);
+        }
+    }
+
+    if values.is_empty() {
+        return;
+    }
+
+    for name in values.keys() {
+        if !book.entrs[name].attrs.keep {
+            book.entrs.remove(name);
+            book.names.remove(name);
+        }
+    }
+
+    let state = EnumState { values };
+
+    for entr in &mut book.entrs {
+        state.lower_entry(entr.1)
+    }
+}
+
+impl EnumState {
+    fn lower_entry(&self, entry: &mut untyped::Entry) {
+        for rule in &mut entry.rules {
+            for pat in &mut rule.pats {
+                self.lower_expr(pat)
+            }
+            self.lower_expr(&mut rule.body)
+        }
+    }
+
+    fn lower_expr(&self, expr: &mut Box<untyped::Expr>) {
+        use untyped::ExprKind::*;
+        match &mut expr.data {
+            Lambda { body, .. } => self.lower_expr(body),
+            App { fun, args } => {
+                self.lower_expr(fun);
+                for arg in args {
+                    self.lower_expr(arg);
+                }
+            }
+            Ctr { name, args } if args.is_empty() => {
+                if let Some(numb) = self.values.get(name.to_str()) {
+                    *expr = untyped::Expr::u60(expr.range, *numb);
+                }
+            }
+            Fun { args, .. } | Ctr { args, .. } => {
+                for arg in args {
+                    self.lower_expr(arg);
+                }
+            }
+            Let { val, next, .. } => {
+                self.lower_expr(val);
+                self.lower_expr(next);
+            }
+            Binary { left, right, .. } => {
+                self.lower_expr(left);
+                self.lower_expr(right);
+            }
+            _ => (),
+        }
+    }
+}
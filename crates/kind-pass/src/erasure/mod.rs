@@ -16,15 +16,29 @@ enum Relevance {
     Irrelevant,
 }
 
+/// The relevance of the position where a definition is used.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
-enum Ambient {
+pub enum Ambient {
+    /// In the body of a definition that is not known to be relevant yet.
     Unknown,
+    /// In a type or in an erased argument.
     Irrelevant,
+    /// In the body of a relevant definition.
     Relevant,
 }
 
+impl std::fmt::Display for Ambient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ambient::Unknown => write!(f, "unknown"),
+            Ambient::Irrelevant => write!(f, "irrelevant"),
+            Ambient::Relevant => write!(f, "relevant"),
+        }
+    }
+}
+
 impl Ambient {
-    pub fn as_relevance(&self) -> Relevance {
+    fn as_relevance(&self) -> Relevance {
         match self {
             Ambient::Irrelevant => Relevance::Irrelevant,
             Ambient::Unknown | Ambient::Relevant => Relevance::Relevant,
@@ -32,6 +46,26 @@ impl Ambient {
     }
 }
 
+/// How the entrypoints of a book use a definition.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Reach {
+    /// It's used by the computation of an entrypoint, so the erasure keeps it.
+    Relevant,
+    /// It's only used by types, so the erasure removes it.
+    TypeOnly,
+    /// It's not used by any entrypoint.
+    Unused,
+}
+
+/// A use of the definition `to` by the definition `from`.
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub from: String,
+    pub to: String,
+    pub range: Range,
+    pub ambient: Ambient,
+}
+
 pub struct Edge {
     name: String,
     relevance: FxHashMap<Relevance, Vec<Range>>,
@@ -67,6 +101,48 @@ pub fn erase_book(
     state.erase_book(book, entrypoints)
 }
 
+/// Classifies each definition of the book by how the entrypoints reach
+/// it. It doesn't check if the irrelevant definitions are used in
+/// relevant positions, so it doesn't send errors.
+pub fn reachability(
+    book: &desugared::Book,
+    errs: Sender<Box<dyn Diagnostic>>,
+    entrypoints: Vec<String>,
+) -> FxHashMap<String, Reach> {
+    let mut state = ErasureState {
+        errs,
+        book,
+        edges: Default::default(),
+        names: Default::default(),
+        ctx: Default::default(),
+        failed: Default::default(),
+    };
+
+    state.reachability(book, entrypoints)
+}
+
+/// Finds the shortest chain of relevant uses from an entrypoint to the
+/// definition `name` and sends it as a diagnostic. Returns `None` if
+/// the definition is not relevant, and an empty chain if it's an
+/// entrypoint.
+pub fn relevance_chain(
+    book: &desugared::Book,
+    errs: Sender<Box<dyn Diagnostic>>,
+    entrypoints: Vec<String>,
+    name: &str,
+) -> Option<Vec<Step>> {
+    let mut state = ErasureState {
+        errs,
+        book,
+        edges: Default::default(),
+        names: Default::default(),
+        ctx: Default::default(),
+        failed: Default::default(),
+    };
+
+    state.relevance_chain(book, entrypoints, name)
+}
+
 impl<'a> ErasureState<'a> {
     fn get_edge_or_create(&mut self, name: &QualifiedIdent) -> usize {
         if let Some(id) = self.names.get(&name.to_string()) {
@@ -95,13 +171,7 @@ impl<'a> ErasureState<'a> {
         entry.push((name.range, ambient))
     }
 
-    pub fn erase_book(
-        &mut self,
-        book: &'a desugared::Book,
-        named_entrypoints: Vec<String>,
-    ) -> anyhow::Result<untyped::Book> {
-        let mut vals = FxHashMap::default();
-
+    fn collect_entrypoints(&mut self, book: &'a desugared::Book, named_entrypoints: Vec<String>) -> Vec<usize> {
         let mut entrypoints = Vec::new();
 
         for name in named_entrypoints {
@@ -129,6 +199,165 @@ impl<'a> ErasureState<'a> {
             }
         }
 
+        entrypoints
+    }
+
+    /// The definitions that can be reached from the entrypoints by the
+    /// connections of the edges or, if `relevant`, only by the
+    /// connections that are not irrelevant.
+    fn reach_from(&self, entrypoints: &[usize], relevant: bool) -> FxHashSet<usize> {
+        let mut visited = FxHashSet::default();
+        let mut queue = entrypoints.to_vec();
+
+        while let Some(fst) = queue.pop() {
+            if !visited.insert(fst) {
+                continue;
+            }
+
+            for (to, relevs) in &self.edges[fst].connections {
+                let follows = !relevant || relevs.iter().any(|(_, relev)| *relev != Ambient::Irrelevant);
+                if follows && !visited.contains(to) {
+                    queue.push(*to);
+                }
+            }
+        }
+
+        visited
+    }
+
+    pub fn reachability(
+        &mut self,
+        book: &'a desugared::Book,
+        named_entrypoints: Vec<String>,
+    ) -> FxHashMap<String, Reach> {
+        let entrypoints = self.collect_entrypoints(book, named_entrypoints);
+
+        for entr in book.entrs.values() {
+            self.erase_entry(entr);
+        }
+
+        let relevant = self.reach_from(&entrypoints, true);
+        let reached = self.reach_from(&entrypoints, false);
+
+        book.entrs
+            .keys()
+            .map(|name| {
+                let id = self.names[name].1;
+                let reach = if relevant.contains(&id) {
+                    Reach::Relevant
+                } else if reached.contains(&id) {
+                    Reach::TypeOnly
+                } else {
+                    Reach::Unused
+                };
+                (name.clone(), reach)
+            })
+            .collect()
+    }
+
+    pub fn relevance_chain(
+        &mut self,
+        book: &'a desugared::Book,
+        named_entrypoints: Vec<String>,
+        name: &str,
+    ) -> Option<Vec<Step>> {
+        let entrypoints = self.collect_entrypoints(book, named_entrypoints);
+
+        for entr in book.entrs.values() {
+            self.erase_entry(entr);
+        }
+
+        let def_range = book.entrs.get(name)?.name.range;
+        let target = self.names.get(name)?.1;
+
+        // The use that reached each definition for the first time, so
+        // the chains of the breadth-first search are the shortest ones.
+        let mut parents: FxHashMap<usize, Option<(usize, Range, Ambient)>> = FxHashMap::default();
+        let mut queue = std::collections::VecDeque::new();
+
+        for id in entrypoints {
+            if let std::collections::hash_map::Entry::Vacant(entry) = parents.entry(id) {
+                entry.insert(None);
+                queue.push_back(id);
+            }
+        }
+
+        while let Some(fst) = queue.pop_front() {
+            if fst == target {
+                break;
+            }
+
+            let mut connections: Vec<_> = self.edges[fst].connections.iter().collect();
+            connections.sort_by_key(|x| x.0);
+
+            for (to, relevs) in connections {
+                let used = relevs.iter().find(|(_, relev)| *relev != Ambient::Irrelevant);
+                if let Some((range, relev)) = used {
+                    if !parents.contains_key(to) {
+                        parents.insert(*to, Some((fst, *range, *relev)));
+                        queue.push_back(*to);
+                    }
+                }
+            }
+        }
+
+        if !parents.contains_key(&target) {
+            self.errs
+                .send(Box::new(PassDiagnostic::NotRelevant(name.to_string(), def_range)))
+                .unwrap();
+            return None;
+        }
+
+        let mut chain = Vec::new();
+        let mut current = target;
+
+        while let Some(Some((parent, range, ambient))) = parents.get(&current) {
+            chain.push(Step {
+                from: self.edges[*parent].name.clone(),
+                to: self.edges[current].name.clone(),
+                range: *range,
+                ambient: *ambient,
+            });
+            current = *parent;
+        }
+
+        chain.reverse();
+
+        let steps = chain
+            .iter()
+            .map(|step| {
+                let position = match step.ambient {
+                    Ambient::Unknown => "a position of unknown relevance",
+                    Ambient::Irrelevant => "an irrelevant position",
+                    Ambient::Relevant => "a relevant position",
+                };
+                (step.range, format!("'{}' uses '{}' in {}", step.from, step.to, position))
+            })
+            .collect();
+
+        let entrypoint = chain.first().map(|x| x.from.clone()).unwrap_or(name.to_string());
+
+        self.errs
+            .send(Box::new(PassDiagnostic::RelevantBecause(
+                name.to_string(),
+                entrypoint,
+                def_range,
+                steps,
+            )))
+            .unwrap();
+
+        Some(chain)
+    }
+
+    pub fn erase_book(
+        &mut self,
+        book: &'a desugared::Book,
+        named_entrypoints: Vec<String>,
+    ) -> anyhow::Result<untyped::Book> {
+        let mut vals = FxHashMap::default();
+
+        let entrypoints = self.collect_entrypoints(book, named_entrypoints);
+
         for entr in book.entrs.values() {
             vals.insert(entr.name.to_string(), self.erase_entry(entr));
         }
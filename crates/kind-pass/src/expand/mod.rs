@@ -1,14 +1,18 @@
 //! Expand some attributes and derivations of each construction.
-//! Currently it just derives `match` and `open` for sum type
-//! and record types respectively.
+//! Currently it derives `match`, `no_confusion` and `enum` for sum types
+//! and `match`, `getters`, `setters`, `mutters` and `no_confusion`
+//! for record types, and the derivations that are registered
+//! in a [DeriveRegistry][registry::DeriveRegistry].
 
 use std::fmt::Display;
 use std::sync::mpsc::Sender;
 
 use fxhash::FxHashMap;
+use kind_derive::enums::derive_enum;
 use kind_derive::getters::derive_getters;
 use kind_derive::matching::derive_match;
 use kind_derive::mutters::derive_mutters;
+use kind_derive::no_confusion::{derive_no_confusion, derive_no_confusion_rec};
 use kind_derive::open::derive_match_rec;
 use kind_derive::setters::derive_setters;
 use kind_report::data::Diagnostic;
@@ -19,16 +23,23 @@ use kind_tree::concrete::EntryMeta;
 use kind_tree::concrete::Module;
 use kind_tree::concrete::RecordDecl;
 use kind_tree::concrete::SumTypeDecl;
+use kind_tree::concrete::visitor::Visitor;
 use kind_tree::concrete::{Attribute, TopLevel};
+use kind_tree::symbol::QualifiedIdent;
 
 use crate::diagnostic::PassDiagnostic;
 
+use self::registry::{DeriveRegistry, DeriveResult, Relocate};
+
 /// Expands sum type and record definitions to a lot of
 /// helper definitions like eliminators and replace qualified identifiers
 /// by their module names.
 pub mod uses;
 
+pub mod registry;
+
 type Derivations = FxHashMap<Derive, Range>;
+type Defined = FxHashMap<String, Range>;
 type Channel = Sender<Box<dyn Diagnostic>>;
 
 /// Tags for each one of the possible derivations.
@@ -37,7 +48,10 @@ pub enum Derive {
     Match,
     Getters,
     Setters,
-    Mutters
+    Mutters,
+    NoConfusion,
+    Enum,
+    Custom(String),
 }
 
 impl Display for Derive {
@@ -47,6 +61,9 @@ impl Display for Derive {
             Derive::Getters => write!(f, "getters"),
             Derive::Setters => write!(f, "setters"),
             Derive::Mutters => write!(f, "mutters"),
+            Derive::NoConfusion => write!(f, "no_confusion"),
+            Derive::Enum => write!(f, "enum"),
+            Derive::Custom(name) => write!(f, "{}", name),
         }
     }
 }
@@ -63,17 +80,23 @@ pub fn insert_or_report(channel: Channel, hashmap: &mut Derivations, key: Derive
     }
 }
 
-fn string_to_derive(name: &str) -> Option<Derive> {
+pub(crate) fn string_to_derive(name: &str) -> Option<Derive> {
     match name {
         "match" => Some(Derive::Match),
         "getters" => Some(Derive::Getters),
         "setters" => Some(Derive::Setters),
         "mutters" => Some(Derive::Mutters),
+        "no_confusion" => Some(Derive::NoConfusion),
+        "enum" => Some(Derive::Enum),
         _ => None,
     }
 }
 
-pub fn expand_derive(error_channel: Channel, attrs: &[Attribute]) -> Option<Derivations> {
+pub fn expand_derive(
+    error_channel: Channel,
+    attrs: &[Attribute],
+    registry: &DeriveRegistry,
+) -> Option<Derivations> {
     use kind_tree::concrete::AttributeStyle::*;
 
     let mut failed = false;
@@ -97,6 +120,10 @@ pub fn expand_derive(error_channel: Channel, attrs: &[Attribute]) -> Option<Deri
                     let key = string_to_derive(ident.to_str()).unwrap();
                     insert_or_report(error_channel.clone(), &mut defs, key, *range)
                 }
+                Ident(range, ident) if registry.contains(ident.to_str()) => {
+                    let key = Derive::Custom(ident.to_string());
+                    insert_or_report(error_channel.clone(), &mut defs, key, *range)
+                }
                 other => {
                     let err = Box::new(PassDiagnostic::InvalidAttributeArgument(other.locate()));
                     error_channel.send(err).unwrap();
@@ -113,11 +140,76 @@ pub fn expand_derive(error_channel: Channel, attrs: &[Attribute]) -> Option<Deri
     }
 }
 
+/// Inserts the entries generated by a derivation of the registry,
+/// checking that all of them are inside the namespace of the type and
+/// that none of them is already defined in the module.
+fn insert_custom(
+    error_channel: Channel,
+    entries: &mut FxHashMap<String, (Entry, EntryMeta)>,
+    defined: &Defined,
+    derive: &str,
+    type_name: &QualifiedIdent,
+    range: Range,
+    result: Option<DeriveResult>,
+) -> bool {
+    let Some((res, errs)) = result else {
+        let err = Box::new(PassDiagnostic::CannotDerive(derive.to_string(), range));
+        error_channel.send(err).unwrap();
+        return true;
+    };
+
+    let mut failed = false;
+
+    for err in errs {
+        error_channel.send(err).unwrap();
+        failed = true;
+    }
+
+    let prefix = format!("{}.", type_name);
+
+    for mut entry in res {
+        let name = entry.name.to_string();
+
+        if !name.starts_with(&prefix) || entries.contains_key(&name) {
+            let err = Box::new(PassDiagnostic::InvalidDerivedName(
+                derive.to_string(),
+                name,
+                range,
+            ));
+            error_channel.send(err).unwrap();
+            failed = true;
+            continue;
+        }
+
+        if let Some(place) = defined.get(&name) {
+            let err = Box::new(PassDiagnostic::DerivedNameAlreadyDefined(
+                derive.to_string(),
+                name,
+                range,
+                *place,
+            ));
+            error_channel.send(err).unwrap();
+            failed = true;
+            continue;
+        }
+
+        Relocate(range).visit_entry(&mut entry);
+        entry.generated_by = Some(type_name.to_string());
+
+        let info = entry.extract_book_info();
+        entries.insert(name, (entry, info));
+    }
+
+    failed
+}
+
 pub fn expand_sum_type(
     error_channel: Channel,
     entries: &mut FxHashMap<String, (Entry, EntryMeta)>,
     sum: &SumTypeDecl,
     derivations: Derivations,
+    registry: &DeriveRegistry,
+    defined: &Defined,
 ) -> bool {
     let mut failed = false;
 
@@ -132,6 +224,40 @@ pub fn expand_sum_type(
                     failed = true;
                 }
             }
+            Derive::Enum => {
+                let (res, errs) = derive_enum(sum.name.range, sum);
+                for entry in res {
+                    let info = entry.extract_book_info();
+                    entries.insert(entry.name.to_string(), (entry, info));
+                }
+                for err in errs {
+                    error_channel.send(err).unwrap();
+                    failed = true;
+                }
+            }
+            Derive::NoConfusion => {
+                let (res, errs) = derive_no_confusion(sum.name.range, sum);
+                for entry in res {
+                    let info = entry.extract_book_info();
+                    entries.insert(entry.name.to_string(), (entry, info));
+                }
+                for err in errs {
+                    error_channel.send(err).unwrap();
+                    failed = true;
+                }
+            }
+            Derive::Custom(name) => {
+                let result = registry.get(&name).unwrap().derive_sum_type(val, sum);
+                failed |= insert_custom(
+                    error_channel.clone(),
+                    entries,
+                    defined,
+                    &name,
+                    &sum.name,
+                    val,
+                    result,
+                );
+            }
             other => {
                 error_channel
                     .send(Box::new(PassDiagnostic::CannotDerive(
@@ -148,12 +274,16 @@ pub fn expand_sum_type(
 }
 
 pub fn expand_record_type(
-    _error_channel: Channel,
+    error_channel: Channel,
     entries: &mut FxHashMap<String, (Entry, EntryMeta)>,
     rec: &RecordDecl,
     derivations: Derivations,
-) {
-    for (key, _) in derivations {
+    registry: &DeriveRegistry,
+    defined: &Defined,
+) -> bool {
+    let mut failed = false;
+
+    for (key, val) in derivations {
         match key {
             Derive::Match => {
                 let res = derive_match_rec(rec.name.range, rec);
@@ -178,27 +308,104 @@ pub fn expand_record_type(
                     entries.insert(res.name.to_string(), (res, info));
                 }
             }
+            Derive::NoConfusion => {
+                let (res, errs) = derive_no_confusion_rec(rec.name.range, rec);
+                for entry in res {
+                    let info = entry.extract_book_info();
+                    entries.insert(entry.name.to_string(), (entry, info));
+                }
+                for err in errs {
+                    error_channel.send(err).unwrap();
+                    failed = true;
+                }
+            }
+            Derive::Custom(name) => {
+                let result = registry.get(&name).unwrap().derive_record_type(val, rec);
+                failed |= insert_custom(
+                    error_channel.clone(),
+                    entries,
+                    defined,
+                    &name,
+                    &rec.name,
+                    val,
+                    result,
+                );
+            }
+            other => {
+                error_channel
+                    .send(Box::new(PassDiagnostic::CannotDerive(
+                        other.to_string(),
+                        val,
+                    )))
+                    .unwrap();
+                failed = true;
+            }
+        }
+    }
+
+    failed
+}
+
+/// Names defined by the module itself (including the constructors)
+/// with the location of their definitions.
+fn defined_names(module: &Module) -> Defined {
+    let mut defined = FxHashMap::default();
+    for entry in &module.entries {
+        match entry {
+            TopLevel::SumType(sum) => {
+                defined.insert(sum.name.to_string(), sum.name.range);
+                for cons in &sum.constructors {
+                    let name = sum.name.add_segment(cons.name.to_str());
+                    defined.insert(name.to_string(), cons.name.range);
+                }
+            }
+            TopLevel::RecordType(rec) => {
+                defined.insert(rec.name.to_string(), rec.name.range);
+                let name = rec.name.add_segment(rec.constructor.to_str());
+                defined.insert(name.to_string(), rec.constructor.range);
+            }
+            TopLevel::Entry(entry) => {
+                defined.insert(entry.name.to_string(), entry.name.range);
+            }
         }
     }
+    defined
 }
 
 pub fn expand_module(error_channel: Channel, module: &mut Module) -> bool {
+    expand_module_with(error_channel, module, &DeriveRegistry::default())
+}
+
+/// Expands a module using the derivations of the registry besides the
+/// built in ones.
+pub fn expand_module_with(
+    error_channel: Channel,
+    module: &mut Module,
+    registry: &DeriveRegistry,
+) -> bool {
     let mut failed = false;
 
     let mut entries = FxHashMap::default();
+    let defined = defined_names(module);
 
     for entry in &module.entries {
         match entry {
             TopLevel::SumType(sum) => {
-                if let Some(derive) = expand_derive(error_channel.clone(), &sum.attrs) {
-                    failed |= expand_sum_type(error_channel.clone(), &mut entries, sum, derive)
+                if let Some(derive) = expand_derive(error_channel.clone(), &sum.attrs, registry) {
+                    failed |=
+                        expand_sum_type(error_channel.clone(), &mut entries, sum, derive, registry, &defined)
                 } else {
                     failed = true;
                 }
             }
             TopLevel::RecordType(rec) => {
-                if let Some(derive) = expand_derive(error_channel.clone(), &rec.attrs) {
-                    expand_record_type(error_channel.clone(), &mut entries, rec, derive)
+                if let Some(mut derive) = expand_derive(error_channel.clone(), &rec.attrs, registry) {
+                    // Classes are always opened by the functions that receive their instances
+                    if rec.attrs.iter().any(|x| x.name.to_str() == "class") {
+                        derive.entry(Derive::Match).or_insert(rec.name.range);
+                    }
+                    failed |=
+                        expand_record_type(error_channel.clone(), &mut entries, rec, derive, registry, &defined)
                 } else {
                     failed = true;
                 }
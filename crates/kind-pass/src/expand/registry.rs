@@ -0,0 +1,76 @@
+//! Registry of derivations that are not built in the compiler. They
+//! are registered by the users of the compiler (e.g. through
+//! the driver session) and are called by the expand pass in the
+//! same way as the built in ones.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use fxhash::FxHashMap;
+use kind_report::data::Diagnostic;
+use kind_span::Range;
+use kind_tree::concrete::visitor::Visitor;
+use kind_tree::concrete::{Entry, RecordDecl, SumTypeDecl};
+
+pub type DeriveResult = (Vec<Entry>, Vec<Box<dyn Diagnostic>>);
+
+/// A derivation that generates new entries from a type
+/// declaration. `range` is the location of the derive argument,
+/// and all of the generated entries are relocated to it so the
+/// errors inside the generated code point back to the attribute.
+///
+/// Both methods return `None` if the derivation does not work for
+/// that kind of declaration.
+pub trait CustomDerive: Send + Sync {
+    fn derive_sum_type(&self, _range: Range, _sum: &SumTypeDecl) -> Option<DeriveResult> {
+        None
+    }
+
+    fn derive_record_type(&self, _range: Range, _rec: &RecordDecl) -> Option<DeriveResult> {
+        None
+    }
+}
+
+/// Maps the name that is used inside `#derive[..]` to its derivation.
+#[derive(Clone, Default)]
+pub struct DeriveRegistry {
+    derivations: FxHashMap<String, Arc<dyn CustomDerive>>,
+}
+
+impl Debug for DeriveRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.derivations.keys()).finish()
+    }
+}
+
+impl DeriveRegistry {
+    /// Registers a new derivation. The built in derivations cannot be
+    /// replaced so it returns `false` if the name is already used by
+    /// one of them.
+    pub fn register<T: CustomDerive + 'static>(&mut self, name: &str, derive: T) -> bool {
+        if super::string_to_derive(name).is_some() {
+            false
+        } else {
+            self.derivations.insert(name.to_string(), Arc::new(derive));
+            true
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn CustomDerive>> {
+        self.derivations.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.derivations.contains_key(name)
+    }
+}
+
+/// Moves all of the locations inside a generated entry to
+/// the location of the derive attribute.
+pub(crate) struct Relocate(pub Range);
+
+impl Visitor for Relocate {
+    fn visit_range(&mut self, x: &mut Range) {
+        *x = self.0;
+    }
+}
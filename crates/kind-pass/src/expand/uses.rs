@@ -55,5 +55,8 @@ pub fn expand_uses(module: &mut Module, errors: Sender<Box<dyn Diagnostic>>) ->
     for entry in module.entries.iter_mut() {
         session.visit_top_level(entry)
     }
+    for mac in module.macros.iter_mut() {
+        session.visit_expr(&mut mac.body)
+    }
     session.failed
 }
@@ -0,0 +1,341 @@
+//! Compiles the rules with nested patterns to rules that match only
+//! one level of constructors, so every target sees the same kind of
+//! pattern matching. A function like
+//!
+//! ```kind2
+//! Half (Nat.succ (Nat.succ n)) = Nat.succ (Half n)
+//! Half n                       = Nat.zero
+//! ```
+//!
+//! is turned into a decision tree of auxiliary functions
+//!
+//! ```kind2
+//! Half (Nat.succ x1) = Half0_ x1
+//! Half n             = Nat.zero
+//!
+//! Half0_ (Nat.succ n) = Nat.succ (Half n)
+//! Half0_ x2           = let n = Nat.succ x2; Nat.zero
+//! ```
+//!
+//! Each auxiliary function keeps the rules that can match the
+//! constructor that was chosen, in the same order, so the first
+//! rule that matches is always the one that runs. Functions whose
+//! rules are already flat are not changed.
+
+use fxhash::{FxHashMap, FxHashSet};
+use kind_span::Range;
+use kind_tree::symbol::{Ident, QualifiedIdent};
+use kind_tree::untyped::{Book, Entry, Expr, ExprKind, Rule};
+use linked_hash_map::LinkedHashMap;
+
+/// The head of a pattern that is not a variable.
+#[derive(Clone, PartialEq)]
+enum Head {
+    Ctr(String, usize),
+    U60(u64),
+    F60(u64),
+}
+
+impl Head {
+    fn of(pat: &Expr) -> Option<Head> {
+        match &pat.data {
+            ExprKind::Ctr { name, args } => Some(Head::Ctr(name.to_string(), args.len())),
+            ExprKind::U60 { numb } => Some(Head::U60(*numb)),
+            ExprKind::F60 { numb } => Some(Head::F60(*numb)),
+            _ => None,
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Head::Ctr(_, arity) => *arity,
+            _ => 0,
+        }
+    }
+
+    fn to_expr(&self, range: Range, fields: &[Ident]) -> Box<Expr> {
+        match self {
+            Head::Ctr(name, _) => {
+                let args = fields.iter().cloned().map(Expr::var).collect();
+                Expr::ctr(range, QualifiedIdent::new_static(name, None, range), args)
+            }
+            Head::U60(numb) => Expr::u60(range, *numb),
+            Head::F60(numb) => Box::new(Expr {
+                data: ExprKind::F60 { numb: *numb },
+                range,
+            }),
+        }
+    }
+}
+
+pub(crate) fn is_var(pat: &Expr) -> bool {
+    matches!(pat.data, ExprKind::Var { .. })
+}
+
+/// A pattern that the targets can match without flattening.
+fn is_flat(pat: &Expr) -> bool {
+    match &pat.data {
+        ExprKind::Ctr { args, .. } => args.iter().all(|x| is_var(x)),
+        _ => true,
+    }
+}
+
+/// Turns the string patterns into chains of `Data.String.cons`.
+fn expand_strings(pat: &mut Expr) {
+    match &mut pat.data {
+        ExprKind::Str { val } => {
+            let range = pat.range;
+            let nil = QualifiedIdent::new_static("Data.String.nil", None, range);
+            let cons = QualifiedIdent::new_static("Data.String.cons", None, range);
+            let chain = val.chars().rfold(Expr::ctr(range, nil, vec![]), |rest, chr| {
+                Expr::ctr(range, cons.clone(), vec![Expr::u60(range, chr as u64), rest])
+            });
+            *pat = *chain;
+        }
+        ExprKind::Ctr { args, .. } => args.iter_mut().for_each(|x| expand_strings(x)),
+        _ => (),
+    }
+}
+
+pub(crate) fn collect_names(expr: &Expr, names: &mut FxHashSet<String>) {
+    match &expr.data {
+        ExprKind::Var { name } => {
+            names.insert(name.to_string());
+        }
+        ExprKind::Lambda { param, body, .. } => {
+            names.insert(param.to_string());
+            collect_names(body, names);
+        }
+        ExprKind::Let { name, val, next } => {
+            names.insert(name.to_string());
+            collect_names(val, names);
+            collect_names(next, names);
+        }
+        ExprKind::App { fun, args } => {
+            collect_names(fun, names);
+            args.iter().for_each(|x| collect_names(x, names));
+        }
+        ExprKind::Fun { args, .. } | ExprKind::Ctr { args, .. } => {
+            args.iter().for_each(|x| collect_names(x, names))
+        }
+        ExprKind::Binary { left, right, .. } => {
+            collect_names(left, names);
+            collect_names(right, names);
+        }
+        _ => (),
+    }
+}
+
+struct Flatten<'a> {
+    entry: &'a Entry,
+    /// Names of the variables of the entry, so the generated ones
+    /// never capture them.
+    used: FxHashSet<String>,
+    fresh: usize,
+    aux: usize,
+    new_entries: Vec<Entry>,
+}
+
+impl<'a> Flatten<'a> {
+    fn fresh_name(&mut self, range: Range) -> Ident {
+        loop {
+            let name = format!("x{}", self.fresh);
+            self.fresh += 1;
+            if !self.used.contains(&name) {
+                return Ident::new(name, range);
+            }
+        }
+    }
+
+    fn fresh_names(&mut self, range: Range, count: usize) -> Vec<Ident> {
+        (0..count).map(|_| self.fresh_name(range)).collect()
+    }
+
+    fn aux_name(&mut self) -> QualifiedIdent {
+        let name = format!("{}{}_", self.entry.name, self.aux);
+        self.aux += 1;
+        QualifiedIdent::new_static(&name, None, self.entry.name.range)
+    }
+
+    /// Creates an auxiliary function that matches the rows and
+    /// returns the call to it. Without arguments the first row
+    /// always matches, so its body is used instead of the call.
+    fn aux_call(&mut self, mut rows: Vec<Rule>, args: &[Ident], range: Range) -> Box<Expr> {
+        if args.is_empty() {
+            return rows.swap_remove(0).body;
+        }
+
+        let name = self.aux_name();
+
+        let rules = self.compile(&name, args.len(), rows);
+
+        let mut attrs = self.entry.attrs.clone();
+        attrs.kdl_name = None;
+
+        self.new_entries.push(Entry {
+            name: name.clone(),
+            args: (0..args.len())
+                .map(|n| (format!("x{}", n), Range::ghost_range(), false))
+                .collect(),
+            rules,
+            attrs,
+            range: self.entry.range,
+        });
+
+        let args = args.iter().cloned().map(Expr::var).collect();
+        Expr::fun(range, name, args)
+    }
+
+    /// Compiles the pattern matrix of the function `name` to
+    /// flat rules. Each rule is a row of the matrix.
+    fn compile(&mut self, name: &QualifiedIdent, arity: usize, rows: Vec<Rule>) -> Vec<Rule> {
+        let nested = (0..arity).find(|i| rows.iter().any(|row| !is_flat(&row.pats[*i])));
+
+        let Some(column) = nested else {
+            return rows
+                .into_iter()
+                .map(|row| Rule {
+                    name: name.clone(),
+                    ..row
+                })
+                .collect();
+        };
+
+        let mut heads: Vec<Head> = Vec::new();
+        for row in &rows {
+            if let Some(head) = Head::of(&row.pats[column]) {
+                if !heads.contains(&head) {
+                    heads.push(head)
+                }
+            }
+        }
+
+        let mut rules = Vec::new();
+
+        for head in heads {
+            let range = self.entry.range;
+
+            let params = self.fresh_names(range, arity);
+            let fields = self.fresh_names(range, head.arity());
+
+            let mut args = params.clone();
+            args.splice(column..column + 1, fields.iter().cloned());
+
+            let mut pats: Vec<_> = params.into_iter().map(Expr::var).collect();
+            pats[column] = head.to_expr(range, &fields);
+
+            let mut specialized = Vec::new();
+
+            for row in &rows {
+                let pat = &row.pats[column];
+
+                let (sub_pats, body) = match &pat.data {
+                    ExprKind::Var { name } => {
+                        let vars = self.fresh_names(pat.range, head.arity());
+                        let value = head.to_expr(pat.range, &vars);
+                        let body = Expr::let_(row.range, name.clone(), value, row.body.clone());
+                        (vars.into_iter().map(Expr::var).collect(), body)
+                    }
+                    _ if Head::of(pat).as_ref() == Some(&head) => match &pat.data {
+                        ExprKind::Ctr { args, .. } => (args.clone(), row.body.clone()),
+                        _ => (vec![], row.body.clone()),
+                    },
+                    _ => continue,
+                };
+
+                let mut sub_row = row.clone();
+                sub_row.pats.splice(column..column + 1, sub_pats);
+                sub_row.body = body;
+
+                specialized.push(sub_row);
+            }
+
+            let body = self.aux_call(specialized, &args, range);
+
+            rules.push(Rule {
+                name: name.clone(),
+                pats,
+                body,
+                range,
+            });
+        }
+
+        // The rules that match any value in the column
+        let default: Vec<Rule> = rows
+            .into_iter()
+            .filter(|row| is_var(&row.pats[column]))
+            .collect();
+
+        if default.is_empty() {
+            return rules;
+        }
+
+        if default.iter().all(|row| row.pats.iter().all(|x| is_flat(x))) {
+            rules.extend(self.compile(name, arity, default));
+        } else {
+            let range = self.entry.range;
+            let params = self.fresh_names(range, arity);
+            let body = self.aux_call(default, &params, range);
+            rules.push(Rule {
+                name: name.clone(),
+                pats: params.into_iter().map(Expr::var).collect(),
+                body,
+                range,
+            });
+        }
+
+        rules
+    }
+}
+
+/// Flattens an entry, returning the auxiliary functions that it
+/// needs followed by the entry itself.
+fn flatten_entry(mut entry: Entry) -> Vec<Entry> {
+    for rule in &mut entry.rules {
+        rule.pats.iter_mut().for_each(|x| expand_strings(x));
+    }
+
+    if entry.rules.iter().all(|rule| rule.pats.iter().all(|x| is_flat(x))) {
+        return vec![entry];
+    }
+
+    let mut used = FxHashSet::default();
+    for rule in &entry.rules {
+        rule.pats.iter().for_each(|x| collect_names(x, &mut used));
+        collect_names(&rule.body, &mut used);
+    }
+
+    let rows = entry.rules.clone();
+
+    let mut ctx = Flatten {
+        entry: &entry,
+        used,
+        fresh: 0,
+        aux: 0,
+        new_entries: Vec::new(),
+    };
+
+    let rules = ctx.compile(&entry.name, entry.args.len(), rows);
+
+    let mut new_entries = ctx.new_entries;
+
+    new_entries.push(Entry { rules, ..entry });
+    new_entries
+}
+
+/// Flattens the patterns of all of the rules of the book.
+pub fn flatten(book: Book) -> Book {
+    let mut book = book;
+    let mut names = FxHashMap::default();
+    let mut entrs = LinkedHashMap::default();
+
+    for name in book.names.keys() {
+        let entry = book.entrs.remove(name).unwrap();
+        for entry in flatten_entry(*entry) {
+            names.insert(entry.name.to_string(), entrs.len());
+            entrs.insert(entry.name.to_string(), Box::new(entry));
+        }
+    }
+
+    Book { names, entrs }
+}
@@ -0,0 +1,109 @@
+//! Helpers to build the graphs of the definitions of a book: the
+//! names that an untyped expression uses and the strongly connected
+//! components of a graph.
+
+use kind_tree::untyped::{Expr, ExprKind};
+
+/// Collects the names of the functions and constructors that are
+/// used by an expression, including the constructors that the
+/// targets compile the strings to.
+pub fn collect_refs<E: Extend<String>>(expr: &Expr, refs: &mut E) {
+    match &expr.data {
+        ExprKind::Lambda { body, .. } => collect_refs(body, refs),
+        ExprKind::App { fun, args } => {
+            collect_refs(fun, refs);
+            args.iter().for_each(|x| collect_refs(x, refs));
+        }
+        ExprKind::Fun { name, args } | ExprKind::Ctr { name, args } => {
+            refs.extend(Some(name.to_string()));
+            args.iter().for_each(|x| collect_refs(x, refs));
+        }
+        ExprKind::Let { val, next, .. } => {
+            collect_refs(val, refs);
+            collect_refs(next, refs);
+        }
+        ExprKind::Binary { left, right, .. } => {
+            collect_refs(left, refs);
+            collect_refs(right, refs);
+        }
+        ExprKind::Str { .. } => {
+            refs.extend(["Data.String.cons".to_string(), "Data.String.nil".to_string()]);
+        }
+        _ => (),
+    }
+}
+
+/// The strongly connected components of a graph with the nodes from
+/// `0` to `size`, in the order of the Tarjan's algorithm, so each
+/// component goes after the ones that it reaches. It doesn't recurse
+/// on the edges, so long chains of definitions cannot overflow the
+/// stack.
+pub fn strongly_connected_components<I>(size: usize, edges: impl Fn(usize) -> I) -> Vec<Vec<usize>>
+where
+    I: IntoIterator<Item = usize>,
+{
+    let mut index: Vec<Option<usize>> = vec![None; size];
+    let mut lowlink = vec![0; size];
+    let mut on_stack = vec![false; size];
+    let mut stack = Vec::new();
+    let mut counter = 0;
+    let mut components = Vec::new();
+
+    // The nodes that are being visited, with the edges that are left.
+    let mut visiting: Vec<(usize, I::IntoIter)> = Vec::new();
+
+    for root in 0..size {
+        if index[root].is_some() {
+            continue;
+        }
+
+        index[root] = Some(counter);
+        lowlink[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        visiting.push((root, edges(root).into_iter()));
+
+        while let Some((node, nexts)) = visiting.last_mut() {
+            let node = *node;
+
+            if let Some(next) = nexts.next() {
+                match index[next] {
+                    None => {
+                        index[next] = Some(counter);
+                        lowlink[next] = counter;
+                        counter += 1;
+                        stack.push(next);
+                        on_stack[next] = true;
+                        visiting.push((next, edges(next).into_iter()));
+                    }
+                    Some(next_index) if on_stack[next] => {
+                        lowlink[node] = lowlink[node].min(next_index);
+                    }
+                    Some(_) => (),
+                }
+                continue;
+            }
+
+            visiting.pop();
+
+            if let Some((parent, _)) = visiting.last() {
+                lowlink[*parent] = lowlink[*parent].min(lowlink[node]);
+            }
+
+            if Some(lowlink[node]) == index[node] {
+                let mut component = Vec::new();
+                while let Some(other) = stack.pop() {
+                    on_stack[other] = false;
+                    component.push(other);
+                    if other == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components
+}
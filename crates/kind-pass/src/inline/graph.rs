@@ -0,0 +1,64 @@
+//! Call graph of the untyped book. It's used to find the groups
+//! of mutually recursive functions (the strongly connected
+//! components of the graph) that cannot be inlined.
+
+use fxhash::{FxHashMap, FxHashSet};
+use kind_tree::untyped;
+
+use crate::graph::{collect_refs, strongly_connected_components};
+
+pub struct CallGraph {
+    names: Vec<String>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl CallGraph {
+    pub fn new(book: &untyped::Book) -> CallGraph {
+        let names: Vec<String> = book.entrs.keys().cloned().collect();
+
+        let ids: FxHashMap<&str, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect();
+
+        let edges = book
+            .entrs
+            .values()
+            .map(|entry| {
+                let mut calls = FxHashSet::default();
+                for rule in &entry.rules {
+                    collect_refs(&rule.body, &mut calls);
+                }
+                calls
+                    .iter()
+                    .filter_map(|name| ids.get(name.as_str()).cloned())
+                    .collect()
+            })
+            .collect();
+
+        CallGraph { names, edges }
+    }
+
+    /// Returns the recursive functions of the book, each one with
+    /// the group of functions that it calls itself through.
+    pub fn recursive_groups(&self) -> FxHashMap<String, Vec<String>> {
+        let components =
+            strongly_connected_components(self.names.len(), |node| self.edges[node].iter().cloned());
+
+        let mut groups = FxHashMap::default();
+
+        for mut component in components {
+            let recursive = component.len() > 1 || self.edges[component[0]].contains(&component[0]);
+            if recursive {
+                component.sort();
+                let group: Vec<String> = component.iter().map(|x| self.names[*x].clone()).collect();
+                for node in component {
+                    groups.insert(self.names[node].clone(), group.clone());
+                }
+            }
+        }
+
+        groups
+    }
+}
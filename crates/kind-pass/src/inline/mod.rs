@@ -1,16 +1,81 @@
-use fxhash::FxHashMap;
+//! Inlines the functions that are marked with `#inline` and, if a
+//! size threshold is given, the small functions that are not marked
+//! with `#noinline`. Recursive functions are never inlined because
+//! it would not terminate.
+
+use std::sync::mpsc::Sender;
+
+use fxhash::{FxHashMap, FxHashSet};
+use kind_report::data::Diagnostic;
 use kind_tree::untyped;
 
+use crate::diagnostic::PassDiagnostic;
+use crate::graph::collect_refs;
 use crate::unbound::subst::subst_on_expr;
 
+use self::graph::CallGraph;
+
+pub mod graph;
+
 struct Inlinable {
     names: Vec<String>,
     body: Box<untyped::Expr>,
+    /// Variables that are bound inside of the body. An argument that
+    /// uses one of them cannot be substituted without renaming.
+    binders: FxHashSet<String>,
 }
+
 struct InlineState {
     funs: FxHashMap<String, Inlinable>,
 }
 
+/// Number of nodes of an expression.
+fn size(expr: &untyped::Expr) -> usize {
+    use untyped::ExprKind::*;
+    1 + match &expr.data {
+        Lambda { body, .. } => size(body),
+        App { fun, args } => size(fun) + args.iter().map(|x| size(x)).sum::<usize>(),
+        Fun { args, .. } | Ctr { args, .. } => args.iter().map(|x| size(x)).sum(),
+        Let { val, next, .. } => size(val) + size(next),
+        Binary { left, right, .. } => size(left) + size(right),
+        _ => 0,
+    }
+}
+
+/// Collects the names of the variables and of the binders of an
+/// expression, counting how many times each of them occurs.
+fn collect_vars(
+    expr: &untyped::Expr,
+    vars: &mut FxHashMap<String, usize>,
+    binders: &mut FxHashSet<String>,
+) {
+    use untyped::ExprKind::*;
+    match &expr.data {
+        Var { name } => *vars.entry(name.to_string()).or_default() += 1,
+        Lambda { param, body, .. } => {
+            binders.insert(param.to_string());
+            collect_vars(body, vars, binders)
+        }
+        App { fun, args } => {
+            collect_vars(fun, vars, binders);
+            args.iter().for_each(|x| collect_vars(x, vars, binders))
+        }
+        Fun { args, .. } | Ctr { args, .. } => {
+            args.iter().for_each(|x| collect_vars(x, vars, binders))
+        }
+        Let { name, val, next } => {
+            binders.insert(name.to_string());
+            collect_vars(val, vars, binders);
+            collect_vars(next, vars, binders)
+        }
+        Binary { left, right, .. } => {
+            collect_vars(left, vars, binders);
+            collect_vars(right, vars, binders)
+        }
+        _ => (),
+    }
+}
+
 fn inlinable(entry: &untyped::Entry) -> Option<Inlinable> {
     if entry.rules.len() == 1 {
         let mut names = Vec::new();
@@ -20,33 +85,83 @@ fn inlinable(entry: &untyped::Entry) -> Option<Inlinable> {
                 _ => return None,
             }
         }
-        // TODO: Check if is recursive
+
+        let mut vars = FxHashMap::default();
+        let mut binders = FxHashSet::default();
+        collect_vars(&entry.rules[0].body, &mut vars, &mut binders);
+
         Some(Inlinable {
             names,
             body: entry.rules[0].body.clone(),
+            binders,
         })
     } else {
         None
     }
 }
 
-pub fn inline_book(book: &mut untyped::Book) {
+/// Checks if a function is small enough to be inlined without
+/// being marked with `#inline`. It should not use any argument more
+/// than once, so the inlining never duplicates work.
+fn is_small(entry: &untyped::Entry, inlinable: &Inlinable, threshold: usize) -> bool {
+    if entry.attrs.trace.is_some() || size(&inlinable.body) > threshold {
+        return false;
+    }
+
+    let mut vars = FxHashMap::default();
+    collect_vars(&inlinable.body, &mut vars, &mut FxHashSet::default());
+
+    inlinable
+        .names
+        .iter()
+        .all(|name| vars.get(name).cloned().unwrap_or_default() <= 1)
+}
+
+/// Inlines the functions of the book. The functions that are marked
+/// with `#inline` are removed after it, unless some call to them
+/// could not be inlined. The `threshold` enables the inlining of
+/// the functions whose body has at most that number of nodes.
+pub fn inline_book(
+    book: &mut untyped::Book,
+    sender: Sender<Box<dyn Diagnostic>>,
+    threshold: Option<usize>,
+) {
+    let recursive = CallGraph::new(book).recursive_groups();
+
     let mut funs = FxHashMap::default();
 
-    let mut to_remove = Vec::new();
+    let mut to_remove = FxHashSet::default();
 
     for entr in book.entrs.values() {
-        if entr.attrs.inlined {
-            if let Some(inlinable) = inlinable(entr) {
-                funs.insert(entr.name.to_string(), inlinable);
-                to_remove.push(entr.name.to_string());
+        let name = entr.name.to_string();
+
+        if let Some(group) = recursive.get(&name) {
+            if entr.attrs.inlined {
+                sender
+                    .send(Box::new(PassDiagnostic::CannotInlineRecursive(
+                        entr.name.range,
+                        name,
+                        group.clone(),
+                    )))
+                    .unwrap();
             }
+            continue;
         }
-    }
 
-    for name in &to_remove {
-        book.entrs.remove(name);
-        book.names.remove(name);
+        if entr.attrs.noinline {
+            continue;
+        }
+
+        let Some(inlinable) = inlinable(entr) else {
+            continue;
+        };
+
+        if entr.attrs.inlined {
+            funs.insert(name.clone(), inlinable);
+            to_remove.insert(name);
+        } else if matches!(threshold, Some(threshold) if is_small(entr, &inlinable, threshold)) {
+            funs.insert(name, inlinable);
+        }
     }
 
     let mut state = InlineState { funs };
@@ -54,6 +169,30 @@ pub fn inline_book(book: &mut untyped::Book) {
     for entr in &mut book.entrs {
         state.inline_entry(entr.1)
     }
+
+    // The calls that could not be inlined keep the function alive.
+    loop {
+        let mut used = FxHashSet::default();
+        for (name, entr) in book.entrs.iter() {
+            if !to_remove.contains(name) {
+                for rule in &entr.rules {
+                    collect_refs(&rule.body, &mut used);
+                }
+            }
+        }
+
+        let before = to_remove.len();
+        to_remove.retain(|name| !used.contains(name));
+
+        if before == to_remove.len() {
+            break;
+        }
+    }
+
+    for name in &to_remove {
+        book.entrs.remove(name);
+        book.names.remove(name);
+    }
 }
 
 impl InlineState {
@@ -63,6 +202,19 @@ impl InlineState {
         }
     }
 
+    fn can_inline(inlinable: &Inlinable, args: &[Box<untyped::Expr>]) -> bool {
+        if inlinable.names.len() != args.len() {
+            return false;
+        }
+
+        let mut vars = FxHashMap::default();
+        for arg in args {
+            collect_vars(arg, &mut vars, &mut FxHashSet::default());
+        }
+
+        vars.keys().all(|var| !inlinable.binders.contains(var))
+    }
+
     fn inline_expr(&mut self, expr: &mut Box<untyped::Expr>) {
         use untyped::ExprKind::*;
         match &mut expr.data {
@@ -73,19 +225,20 @@ impl InlineState {
                     self.inline_expr(arg);
                 }
             }
-            Fun { name, args } | Ctr { name, args } => {
-                if let Some(inlinable) = self.funs.get(name.to_str()) {
+            Fun { name, args } | Ctr { name, args } => match self.funs.get(name.to_str()) {
+                Some(inlinable) if Self::can_inline(inlinable, args) => {
                     let subst =
                         FxHashMap::from_iter(inlinable.names.iter().cloned().zip(args.clone()));
                     *expr = inlinable.body.clone();
                     subst_on_expr(expr, subst);
                     self.inline_expr(expr)
-                } else {
+                }
+                _ => {
                     for arg in args {
                         self.inline_expr(arg);
                     }
                 }
-            }
+            },
             Let { val, next, .. } => {
                 self.inline_expr(val);
                 self.inline_expr(next);
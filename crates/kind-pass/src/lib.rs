@@ -3,12 +3,22 @@
 //! * [erasure][erasure] - Erases all of the definitions that are marked as erased from the runtime.
 //! * [expand][expand]   - Expand some attributes and derivations of each construction.
 //! * [unbound][unbound] - Collects all of the unbound definitions and check the linearity of them.
-//! * [inline][inline]   - Inlines expressions
+//! * [inline][inline]   - Inlines the functions that are not recursive
+//! * [graph][graph]     - Collects the uses of the definitions and the cycles between them
+//! * [enums][enums]     - Compiles the values of enumerations to numbers
+//! * [flatten][flatten] - Compiles nested patterns to rules that match a single constructor
+//! * [nat][nat]         - Compiles the natural numbers to native numbers
+//! * [unbox][unbox]     - Replaces the values of types with a single field by the field
 
 pub mod desugar;
 pub mod erasure;
 mod diagnostic;
+pub mod enums;
 pub mod expand;
+pub mod flatten;
+pub mod graph;
 pub mod inline;
+pub mod nat;
+pub mod unbox;
 pub mod unbound;
 pub mod subst;
\ No newline at end of file
@@ -0,0 +1,424 @@
+//! Changes the representation of `Data.Nat` to numbers, like the
+//! targets do with `Data.String`, so the arithmetic over natural
+//! numbers does not run in unary. The constructors become numbers
+//! and additions, the standard operations become native operators
+//! and the patterns on `Data.Nat.succ` become numeric tests. A
+//! function like
+//!
+//! ```kind2
+//! Pred (Data.Nat.succ n) = n
+//! Pred n                 = Data.Nat.zero
+//! ```
+//!
+//! is turned into
+//!
+//! ```kind2
+//! Pred 0  = let n = 0; 0
+//! Pred n0 = let n = (- n0 1); n
+//! Pred n  = 0
+//! ```
+//!
+//! where the rules that can match a zero are copied before the one
+//! that matches the `Data.Nat.succ`. When a rule matches more than
+//! one `Data.Nat.succ`, it calls an auxiliary function that tests
+//! if all of the numbers are not zero and that goes to the rest of
+//! the rules otherwise. The book is flattened before it, so every
+//! `Data.Nat.succ` pattern has only a variable inside of it.
+//!
+//! The comparisons are not compiled to operators because they return
+//! a `Data.Bool`, and neither are the division and the modulo, whose
+//! results for a zero divisor come from the library. Their rules are
+//! lowered like the others, so they run one `Data.Nat.succ` at a time.
+
+use fxhash::FxHashSet;
+use kind_span::Range;
+use kind_tree::symbol::{Ident, QualifiedIdent};
+use kind_tree::untyped::{Book, Entry, Expr, ExprKind, Rule};
+use kind_tree::Operator;
+
+use crate::flatten::{collect_names, flatten, is_var};
+
+const ZERO: &str = "Data.Nat.zero";
+const SUCC: &str = "Data.Nat.succ";
+
+fn is_zero(pat: &Expr) -> bool {
+    matches!(&pat.data, ExprKind::Ctr { name, args } if name.to_str() == ZERO && args.is_empty())
+}
+
+fn succ_var(pat: &Expr) -> Option<Ident> {
+    match &pat.data {
+        ExprKind::Ctr { name, args } if name.to_str() == SUCC && args.len() == 1 => {
+            match &args[0].data {
+                ExprKind::Var { name } => Some(name.clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Checks if a rule matches every call whose argument in the
+/// position `column` is zero.
+fn matches_zero(rule: &Rule, column: usize) -> bool {
+    rule.pats.iter().enumerate().all(|(i, pat)| {
+        if i == column {
+            is_zero(pat)
+        } else {
+            is_var(pat)
+        }
+    })
+}
+
+/// Copies the rules that can match a zero in the `column`, with a
+/// zero in the place of the pattern.
+fn specialize_zero(rules: &[Rule], column: usize) -> Vec<Rule> {
+    let mut zeros = Vec::new();
+
+    for rule in rules {
+        let pat = &rule.pats[column];
+        let range = pat.range;
+        let zero = || Expr::ctr(range, QualifiedIdent::new_static(ZERO, None, range), vec![]);
+
+        let mut rule = rule.clone();
+
+        match &pat.data {
+            _ if is_zero(pat) => (),
+            ExprKind::Var { name } => {
+                rule.body = Expr::let_(rule.range, name.clone(), zero(), rule.body);
+                rule.pats[column] = zero();
+            }
+            _ => continue,
+        }
+
+        zeros.push(rule);
+    }
+
+    zeros
+}
+
+struct Lower<'a> {
+    entry: &'a Entry,
+    used: FxHashSet<String>,
+    fresh: usize,
+    aux: usize,
+    new_entries: Vec<Entry>,
+}
+
+impl<'a> Lower<'a> {
+    fn fresh_name(&mut self, range: Range) -> Ident {
+        loop {
+            let name = format!("n{}", self.fresh);
+            self.fresh += 1;
+            if !self.used.contains(&name) {
+                return Ident::new(name, range);
+            }
+        }
+    }
+
+    fn aux_name(&mut self, kind: &str) -> QualifiedIdent {
+        let name = format!("{}.{}{}_", self.entry.name, kind, self.aux);
+        self.aux += 1;
+        QualifiedIdent::new_static(&name, None, self.entry.name.range)
+    }
+
+    fn push_entry(&mut self, name: QualifiedIdent, arity: usize, rules: Vec<Rule>) {
+        let mut attrs = self.entry.attrs.clone();
+        attrs.kdl_name = None;
+
+        self.new_entries.push(Entry {
+            name,
+            args: (0..arity)
+                .map(|n| (format!("x{}", n), Range::ghost_range(), false))
+                .collect(),
+            rules,
+            attrs,
+            range: self.entry.range,
+        });
+    }
+
+    /// Subtraction that stops at zero, like the one of `Data.Nat`.
+    fn saturating_sub(&mut self, range: Range, left: Box<Expr>, right: Box<Expr>) -> Box<Expr> {
+        let a = self.fresh_name(range);
+        let b = self.fresh_name(range);
+        let var = |name: &Ident| Expr::var(name.clone());
+        let test = Expr::binary(range, Operator::Gtn, var(&a), var(&b));
+        let diff = Expr::binary(range, Operator::Sub, var(&a), var(&b));
+        let res = Expr::binary(range, Operator::Mul, test, diff);
+        Expr::let_(range, a, left, Expr::let_(range, b, right, res))
+    }
+
+    /// Compiles the standard operations over natural numbers.
+    fn native_op(&mut self, name: &str, args: &[Box<Expr>], range: Range) -> Option<Box<Expr>> {
+        let binary = |op| Expr::binary(range, op, args[0].clone(), args[1].clone());
+        match (name, args.len()) {
+            ("Data.Nat.add", 2) => Some(binary(Operator::Add)),
+            ("Data.Nat.mul", 2) => Some(binary(Operator::Mul)),
+            ("Data.Nat.sub", 2) => {
+                Some(self.saturating_sub(range, args[0].clone(), args[1].clone()))
+            }
+            ("Data.Nat.pred", 1) => {
+                Some(self.saturating_sub(range, args[0].clone(), Expr::u60(range, 1)))
+            }
+            ("Data.Nat.to_u60", 1) | ("Data.U60.to_nat", 1) => Some(args[0].clone()),
+            _ => None,
+        }
+    }
+
+    fn lower_expr(&mut self, expr: &mut Box<Expr>) {
+        match &mut expr.data {
+            ExprKind::Lambda { body, .. } => self.lower_expr(body),
+            ExprKind::App { fun, args } => {
+                self.lower_expr(fun);
+                args.iter_mut().for_each(|x| self.lower_expr(x));
+            }
+            ExprKind::Ctr { name, args } if name.to_str() == ZERO && args.is_empty() => {
+                *expr = Expr::u60(expr.range, 0);
+            }
+            ExprKind::Ctr { name, args } if name.to_str() == SUCC && args.len() == 1 => {
+                let mut pred = args.pop().unwrap();
+                self.lower_expr(&mut pred);
+                *expr = match pred.data {
+                    ExprKind::U60 { numb } => Expr::u60(expr.range, numb + 1),
+                    _ => Expr::binary(expr.range, Operator::Add, pred, Expr::u60(expr.range, 1)),
+                };
+            }
+            ExprKind::Fun { name, args } => {
+                args.iter_mut().for_each(|x| self.lower_expr(x));
+                let name = name.to_string();
+                if let Some(res) = self.native_op(&name, args, expr.range) {
+                    *expr = res;
+                }
+            }
+            ExprKind::Ctr { args, .. } => args.iter_mut().for_each(|x| self.lower_expr(x)),
+            ExprKind::Let { val, next, .. } => {
+                self.lower_expr(val);
+                self.lower_expr(next);
+            }
+            ExprKind::Binary { left, right, .. } => {
+                self.lower_expr(left);
+                self.lower_expr(right);
+            }
+            _ => (),
+        }
+    }
+
+    fn lower_rules(&mut self, name: &QualifiedIdent, arity: usize, rules: &[Rule]) -> Vec<Rule> {
+        let mut rows = rules.to_vec();
+
+        // Rows whose zeros were already copied before them.
+        let mut specialized = vec![false; rows.len()];
+
+        let mut result = Vec::new();
+
+        let mut k = 0;
+
+        while k < rows.len() {
+            let range = rows[k].range;
+
+            let succs: Vec<usize> = (0..arity)
+                .filter(|i| succ_var(&rows[k].pats[*i]).is_some())
+                .collect();
+
+            // The zero does not need a test only if a rule before it
+            // already matches the zero in each column. The last rule needs
+            // it too, so a partial function doesn't match a zero.
+            let needs_test = succs
+                .iter()
+                .any(|i| !rows[..k].iter().any(|x| matches_zero(x, *i)));
+
+            let zeros = match succs.as_slice() {
+                [column] if needs_test && !specialized[k] => {
+                    specialize_zero(&rows[k + 1..], *column)
+                }
+                _ => Vec::new(),
+            };
+
+            // With a single column, the rows that can match the zero are
+            // copied before this one, so it may not need a test anymore.
+            if !zeros.is_empty() {
+                let count = zeros.len();
+                rows.splice(k..k, zeros);
+                specialized.splice(k..k, vec![true; count]);
+                specialized[k + count] = true;
+                continue;
+            }
+
+            let mut pats = rows[k].pats.clone();
+            let mut preds = Vec::new();
+
+            for (i, pat) in pats.iter_mut().enumerate() {
+                if is_zero(pat) {
+                    *pat = Expr::u60(pat.range, 0);
+                } else if let Some(pred) = succ_var(pat) {
+                    let numb = self.fresh_name(pat.range);
+                    *pat = Expr::var(numb.clone());
+                    preds.push((i, pred, numb));
+                }
+            }
+
+            let mut body = rows[k].body.clone();
+            self.lower_expr(&mut body);
+
+            for (_, pred, numb) in preds.iter().rev() {
+                let val = Expr::binary(
+                    range,
+                    Operator::Sub,
+                    Expr::var(numb.clone()),
+                    Expr::u60(range, 1),
+                );
+                body = Expr::let_(range, pred.clone(), val, body);
+            }
+
+            if needs_test {
+                body = self.numeric_test(arity, &pats, &preds, body, &rows[k + 1..], range);
+            }
+
+            result.push(Rule {
+                name: name.clone(),
+                pats,
+                body,
+                range,
+            });
+
+            k += 1;
+        }
+
+        result
+    }
+
+    /// Creates a function that runs the `body` if all of the numbers
+    /// that matched `Data.Nat.succ` are not zero and that goes to the
+    /// `rest` of the rules otherwise. Without a rest, the function has
+    /// no rule for the zero, so the call gets stuck on it like the
+    /// call of a partial function over `Data.Nat` does.
+    fn numeric_test(
+        &mut self,
+        arity: usize,
+        pats: &[Box<Expr>],
+        preds: &[(usize, Ident, Ident)],
+        body: Box<Expr>,
+        rest: &[Rule],
+        range: Range,
+    ) -> Box<Expr> {
+        let mut vars = FxHashSet::default();
+        pats.iter().for_each(|x| collect_names(x, &mut vars));
+        let mut vars: Vec<String> = vars.into_iter().collect();
+        vars.sort();
+        let vars: Vec<Ident> = vars.into_iter().map(|x| Ident::new(x, range)).collect();
+
+        let var_exprs = || vars.iter().cloned().map(Expr::var);
+
+        let test_name = self.aux_name("nat");
+        let cond = self.fresh_name(range);
+
+        let is_not_zero = |numb: &Ident| {
+            Expr::binary(
+                range,
+                Operator::Neq,
+                Expr::var(numb.clone()),
+                Expr::u60(range, 0),
+            )
+        };
+
+        let test = match preds {
+            [(_, _, numb)] if rest.is_empty() => is_not_zero(numb),
+            [(_, _, numb)] => Expr::var(numb.clone()),
+            [(_, _, first), others @ ..] => {
+                others.iter().fold(is_not_zero(first), |acc, (_, _, numb)| {
+                    Expr::binary(range, Operator::And, acc, is_not_zero(numb))
+                })
+            }
+            [] => unreachable!(),
+        };
+
+        let success = |cond| Rule {
+            name: test_name.clone(),
+            pats: std::iter::once(cond).chain(var_exprs()).collect(),
+            body,
+            range,
+        };
+
+        let rules = if rest.is_empty() {
+            vec![success(Expr::u60(range, 1))]
+        } else {
+            let rest_name = self.aux_name("rest");
+            let rest_rules = self.lower_rules(&rest_name, arity, rest);
+            self.push_entry(rest_name.clone(), arity, rest_rules);
+
+            let fallback = Rule {
+                name: test_name.clone(),
+                pats: std::iter::once(Expr::u60(range, 0))
+                    .chain(var_exprs())
+                    .collect(),
+                body: Expr::fun(range, rest_name, pats.to_vec()),
+                range,
+            };
+
+            vec![fallback, success(Expr::var(cond))]
+        };
+
+        self.push_entry(test_name.clone(), vars.len() + 1, rules);
+
+        Expr::fun(
+            range,
+            test_name,
+            std::iter::once(test).chain(var_exprs()).collect(),
+        )
+    }
+}
+
+fn lower_entry(entry: Entry) -> Vec<Entry> {
+    let mut used = FxHashSet::default();
+    for rule in &entry.rules {
+        rule.pats.iter().for_each(|x| collect_names(x, &mut used));
+        collect_names(&rule.body, &mut used);
+    }
+
+    let mut ctx = Lower {
+        entry: &entry,
+        used,
+        fresh: 0,
+        aux: 0,
+        new_entries: Vec::new(),
+    };
+
+    let rules = ctx.lower_rules(&entry.name, entry.args.len(), &entry.rules);
+
+    let mut new_entries = ctx.new_entries;
+    new_entries.push(Entry { rules, ..entry });
+    new_entries
+}
+
+/// Compiles the natural numbers of the book to numbers and removes
+/// the constructors of `Data.Nat` unless they are marked with
+/// `#keep`. The book is flattened only if it uses `Data.Nat`.
+pub fn lower_nats(book: Book) -> Book {
+    if !book.entrs.contains_key(ZERO) && !book.entrs.contains_key(SUCC) {
+        return book;
+    }
+
+    let mut book = flatten(book);
+
+    let names: Vec<String> = book.entrs.keys().cloned().collect();
+
+    for name in names {
+        let entry = book.entrs.remove(&name).unwrap();
+
+        if (name == ZERO || name == SUCC) && !entry.attrs.keep {
+            continue;
+        }
+
+        for entry in lower_entry(*entry) {
+            book.entrs.insert(entry.name.to_string(), Box::new(entry));
+        }
+    }
+
+    book.names = book
+        .entrs
+        .keys()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), i))
+        .collect();
+
+    book
+}
@@ -84,9 +84,10 @@ impl Subst {
                 }
             }
             Let { name, val, next } => {
+                // The value is outside of the scope of the name.
+                self.subst_expr(val);
                 let backup = self.ctx.clone();
                 self.ctx.insert(name.to_string());
-                self.subst_expr(val);
                 self.subst_expr(next);
                 self.ctx = backup;
             }
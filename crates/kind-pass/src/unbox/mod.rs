@@ -0,0 +1,98 @@
+//! Unboxes the newtypes, the types with a single constructor that has
+//! a single relevant field. A value like `(Meters.new 3)` is compiled
+//! to `3` and a pattern like `(Meters.new n)` to `n`, so the targets
+//! don't allocate a node to hold the field. It runs after the erasure,
+//! so the fields that are only used by types don't count.
+//!
+//! The constructors are removed from the compiled code, so the result
+//! that the targets read back shows the unwrapped value: a program that
+//! returned `(NoTypeVar.new (Identity.new 300))` now returns `300`. The
+//! types marked with `#keep` or `#kdl_name` keep their constructors.
+
+use fxhash::FxHashSet;
+use kind_tree::untyped;
+
+struct UnboxState {
+    ctrs: FxHashSet<String>,
+}
+
+/// Checks if the constructor can be replaced by its field. The
+/// constructors that are marked with `#keep` or `#kdl_name` are
+/// part of the interface of the program, so they are never removed.
+fn is_newtype(entry: &untyped::Entry) -> bool {
+    entry.attrs.single_constructor
+        && entry.args.len() == 1
+        && !entry.attrs.keep
+        && entry.attrs.kdl_name.is_none()
+}
+
+/// Replaces the constructors of the types that have a single
+/// constructor with a single relevant field by the field itself, in
+/// the values and in the patterns, and removes them from the book.
+/// It saves a node for each value of these types in the targets.
+pub fn unbox_newtypes(book: &mut untyped::Book) {
+    let ctrs: FxHashSet<String> = book
+        .entrs
+        .values()
+        .filter(|entr| is_newtype(entr))
+        .map(|entr| entr.name.to_string())
+        .collect();
+
+    if ctrs.is_empty() {
+        return;
+    }
+
+    for name in &ctrs {
+        book.entrs.remove(name);
+        book.names.remove(name);
+    }
+
+    let state = UnboxState { ctrs };
+
+    for entr in &mut book.entrs {
+        state.unbox_entry(entr.1)
+    }
+}
+
+impl UnboxState {
+    fn unbox_entry(&self, entry: &mut untyped::Entry) {
+        for rule in &mut entry.rules {
+            for pat in &mut rule.pats {
+                self.unbox_expr(pat)
+            }
+            self.unbox_expr(&mut rule.body)
+        }
+    }
+
+    fn unbox_expr(&self, expr: &mut Box<untyped::Expr>) {
+        use untyped::ExprKind::*;
+        match &mut expr.data {
+            Lambda { body, .. } => self.unbox_expr(body),
+            App { fun, args } => {
+                self.unbox_expr(fun);
+                for arg in args {
+                    self.unbox_expr(arg);
+                }
+            }
+            Ctr { name, args } if args.len() == 1 && self.ctrs.contains(name.to_str()) => {
+                let mut field = args.pop().unwrap();
+                self.unbox_expr(&mut field);
+                *expr = field;
+            }
+            Fun { args, .. } | Ctr { args, .. } => {
+                for arg in args {
+                    self.unbox_expr(arg);
+                }
+            }
+            Let { val, next, .. } => {
+                self.unbox_expr(val);
+                self.unbox_expr(next);
+            }
+            Binary { left, right, .. } => {
+                self.unbox_expr(left);
+                self.unbox_expr(right);
+            }
+            _ => (),
+        }
+    }
+}
@@ -72,6 +72,8 @@ pub enum Log {
     Checked(Duration),
     Compiled(Duration),
     Rewrites(u64),
+    /// Rewrites done by the optimizer, already formatted.
+    Optimized(String),
     Failed(Duration, u64, u64),
     Empty,
 }
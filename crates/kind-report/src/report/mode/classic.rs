@@ -390,6 +390,14 @@ impl Renderable<Classic> for Log {
                     u64
                 )
             }
+            Log::Optimized(stats) => {
+                writeln!(
+                    fmt,
+                    "     {} Optimized: {}",
+                    Paint::new(" STATS ").bg(yansi::Color::Green).bold(),
+                    stats
+                )
+            }
             Log::Empty => writeln!(fmt),
         }
     }
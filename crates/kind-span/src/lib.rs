@@ -1,3 +1,7 @@
+use std::cell::RefCell;
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
 /// Position in a syntax context.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub struct Pos {
@@ -47,6 +51,12 @@ impl Range {
         Range::new(Pos { index: 0 }, Pos { index: 0 }, SyntaxCtxIndex(0))
     }
 
+    /// If it's the range of code that the compiler made without a
+    /// place in the sources.
+    pub fn is_ghost(&self) -> bool {
+        *self == Range::ghost_range()
+    }
+
     /// Joins two ranges. It keeps the syntax context
     /// of the first one.
     #[inline]
@@ -78,6 +88,88 @@ impl Range {
     }
 }
 
+/// A line and a column (in bytes), both starting at 1.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Point {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A range resolved to the file that contains it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub file: String,
+    pub start: Point,
+    pub end: Point,
+}
+
+pub type Locator = Box<dyn Fn(&Range) -> Option<Location>>;
+
+thread_local! {
+    static LOCATOR: RefCell<Option<Locator>> = RefCell::new(None);
+}
+
+/// Runs the action with the ranges being serialized as the locations
+/// given by the locator, as only the one that loaded the sources knows
+/// their files.
+pub fn with_locator<T>(locator: Locator, action: impl FnOnce() -> T) -> T {
+    struct Reset(Option<Locator>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let old = self.0.take();
+            LOCATOR.with(|cell| *cell.borrow_mut() = old);
+        }
+    }
+
+    let old = LOCATOR.with(|cell| cell.borrow_mut().replace(locator));
+    let _reset = Reset(old);
+    action()
+}
+
+impl Serialize for Point {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut point = serializer.serialize_struct("Point", 2)?;
+        point.serialize_field("line", &self.line)?;
+        point.serialize_field("column", &self.column)?;
+        point.end()
+    }
+}
+
+impl Serialize for Location {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut location = serializer.serialize_struct("Location", 3)?;
+        location.serialize_field("file", &self.file)?;
+        location.serialize_field("start", &self.start)?;
+        location.serialize_field("end", &self.end)?;
+        location.end()
+    }
+}
+
+/// Ranges are serialized as the location given by the locator of
+/// [with_locator], or `null` if they are ghost ranges or the locator
+/// doesn't know their file. Without a locator they are serialized with
+/// the index of their syntax context and their byte offsets.
+impl Serialize for Range {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_ghost() {
+            return serializer.serialize_none();
+        }
+        let located = LOCATOR.with(|cell| cell.borrow().as_ref().map(|locator| locator(self)));
+        match located {
+            Some(Some(location)) => location.serialize(serializer),
+            Some(None) => serializer.serialize_none(),
+            None => {
+                let mut range = serializer.serialize_struct("Range", 3)?;
+                range.serialize_field("ctx", &self.ctx.0)?;
+                range.serialize_field("start", &self.start.index)?;
+                range.serialize_field("end", &self.end.index)?;
+                range.end()
+            }
+        }
+    }
+}
+
 impl EncodedRange {
     /// Transforms a encoded span back into a range.
     pub fn to_range(&self) -> Range {
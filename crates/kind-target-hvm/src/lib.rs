@@ -4,15 +4,83 @@ use kind_tree::untyped;
 
 use hvm::syntax::{File, Rule, Term};
 
+pub use source_map::{Function, SourceMap, StuckApp};
+pub use trace::TraceOptions;
+
+pub mod source_map;
+pub mod trace;
+
 pub fn compile_book(book: untyped::Book, trace: bool) -> File {
+    let trace = TraceOptions {
+        all: trace,
+        ..Default::default()
+    };
+    compile_book_with_map(book, &trace).0
+}
+
+/// Compiles the book and maps each of the generated functions and
+/// rules to the place in the source that generated it.
+pub fn compile_book_with_map(book: untyped::Book, trace: &TraceOptions) -> (File, SourceMap) {
     let mut file = File {
         rules: Default::default(),
         smaps: Default::default(),
     };
-    for (_, entry) in book.entrs {
-        compile_entry(&mut file, entry, trace);
+    let mut map = SourceMap::default();
+
+    let cut_rules = if trace.depth.is_some() {
+        trace.cut_rules(&book)
+    } else {
+        Vec::new()
+    };
+
+    let origins: Vec<_> = book
+        .entrs
+        .values()
+        .map(|entry| origin(&book, entry))
+        .collect();
+
+    for ((_, entry), origin) in book.entrs.into_iter().zip(origins) {
+        compile_entry(&mut file, &mut map, entry, origin, trace);
+    }
+
+    file.rules.extend(cut_rules);
+
+    (file, map)
+}
+
+/// The entry that the user wrote for an entry of the book. The
+/// helpers of the flattening and of the lowering of `Data.Nat` are
+/// named like `{entry}0_` and `{entry}.nat0_`, and they keep the
+/// range of the name of the entry that they come from.
+fn origin(book: &untyped::Book, entry: &untyped::Entry) -> String {
+    let name = entry.name.to_str();
+
+    if !name.ends_with('_') {
+        return name.to_string();
     }
-    file
+
+    (1..name.len())
+        .rev()
+        .filter(|i| name.is_char_boundary(*i))
+        .filter_map(|i| book.entrs.get(&name[..i]))
+        .find(|parent| {
+            parent.name.range == entry.name.range && !parent.name.to_str().ends_with('_')
+        })
+        .map_or_else(|| name.to_string(), |parent| parent.name.to_string())
+}
+
+/// The arguments that some rule matches against a pattern that is
+/// not a variable.
+fn strict_args(entry: &untyped::Entry) -> Vec<bool> {
+    (0..entry.args.len())
+        .map(|i| {
+            entry.rules.iter().any(|rule| {
+                rule.pats
+                    .get(i)
+                    .is_some_and(|pat| !matches!(pat.data, untyped::ExprKind::Var { .. }))
+            })
+        })
+        .collect()
 }
 
 pub fn compile_str(val: &str) -> Box<Term> {
@@ -79,12 +147,23 @@ fn compile_rule(name: String, rule: untyped::Rule) -> Rule {
     }
 }
 
-fn compile_entry(file: &mut File, entry: Box<untyped::Entry>, trace: bool) {
-    if entry.attrs.trace.is_some() || trace {
-        let _with_args = entry.attrs.trace.unwrap_or(false);
-
+fn compile_entry(
+    file: &mut File,
+    map: &mut SourceMap,
+    entry: Box<untyped::Entry>,
+    origin: String,
+    trace: &TraceOptions,
+) {
+    if let Some(with_args) = trace.of_entry(&entry) {
         let name_trace = format!("{}__trace", entry.name);
+        map.funs.push(Function {
+            name: name_trace.clone(),
+            origin: origin.clone(),
+            range: entry.name.range,
+            strict: strict_args(&entry),
+        });
         for rule in entry.rules {
+            map.rules.push((name_trace.clone(), rule.range));
             file.rules.push(compile_rule(name_trace.clone(), rule))
         }
 
@@ -99,6 +178,13 @@ fn compile_entry(file: &mut File, entry: Box<untyped::Entry>, trace: bool) {
             })
             .collect::<Vec<_>>();
 
+        map.funs.push(Function {
+            name: entry.name.to_string(),
+            origin,
+            range: entry.name.range,
+            strict: vec![false; args.len()],
+        });
+        map.rules.push((entry.name.to_string(), entry.range));
         file.rules.push(Rule {
             lhs: Box::new(Term::Ctr {
                 name: entry.name.to_string(),
@@ -107,7 +193,7 @@ fn compile_entry(file: &mut File, entry: Box<untyped::Entry>, trace: bool) {
             rhs: Box::new(Term::Ctr {
                 name: "Apps.HVM.log".to_string(),
                 args: vec![
-                    compile_str(entry.name.to_str()),
+                    trace.log_term(entry.name.to_str(), &args, with_args),
                     Box::new(Term::Ctr {
                         name: name_trace,
                         args,
@@ -117,7 +203,16 @@ fn compile_entry(file: &mut File, entry: Box<untyped::Entry>, trace: bool) {
         })
     } else {
         let name = entry.name.to_string();
+        if !entry.rules.is_empty() {
+            map.funs.push(Function {
+                name: name.clone(),
+                origin,
+                range: entry.name.range,
+                strict: strict_args(&entry),
+            });
+        }
         for rule in entry.rules {
+            map.rules.push((name.clone(), rule.range));
             file.rules.push(compile_rule(name.clone(), rule))
         }
     }
@@ -0,0 +1,143 @@
+//! Source maps from the generated HVM code to the Kind source. The
+//! `smaps` of the HVM file are strictness maps, so the positions are
+//! kept in a structure of their own.
+
+use std::fmt::Write;
+
+use hvm::syntax::Term;
+use kind_report::data::FileCache;
+use kind_report::report::LineGuide;
+use kind_span::Range;
+
+/// A function of the generated file.
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    /// The entry that the user wrote, that is not the name of the
+    /// function for the helpers that the flattening creates.
+    pub origin: String,
+    /// Range of the entry that generated the function.
+    pub range: Range,
+    /// The arguments that some rule matches against a pattern, so
+    /// they are reduced before a rule is chosen.
+    pub strict: Vec<bool>,
+}
+
+/// Positions of the functions and of the rules of a generated file.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    pub funs: Vec<Function>,
+    /// Range of each rule, in the same order as the rules of the file.
+    pub rules: Vec<(String, Range)>,
+}
+
+/// An application of a function that is in the normal form, so none
+/// of the rules of the function matched its arguments.
+#[derive(Clone, Debug)]
+pub struct StuckApp {
+    pub name: String,
+    pub term: String,
+}
+
+impl SourceMap {
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.funs.iter().find(|x| x.name == name)
+    }
+
+    pub fn rules_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Range> + 'a {
+        self.rules.iter().filter(move |x| x.0 == name).map(|x| x.1)
+    }
+
+    /// Writes the sidecar file, with a line for each function and for
+    /// each rule, like `fun Main ./Main.kind2:3:1` and
+    /// `rule Main 0 ./Main.kind2:4:1`.
+    pub fn render(&self, cache: &dyn FileCache) -> String {
+        let mut guides = Vec::new();
+        let mut locate = |range: Range| {
+            let Some((path, code)) = cache.fetch(range.ctx) else {
+                return "?".to_string();
+            };
+            if guides.len() <= range.ctx.0 {
+                guides.resize_with(range.ctx.0 + 1, || None);
+            }
+            let guide = guides[range.ctx.0].get_or_insert_with(|| LineGuide::get(code));
+            format!("{}:{}", path.display(), guide.find(range.start))
+        };
+
+        let mut out = String::new();
+
+        for fun in &self.funs {
+            writeln!(out, "fun {} {}", fun.name, locate(fun.range)).unwrap();
+        }
+
+        let mut index = 0;
+        for (i, (name, range)) in self.rules.iter().enumerate() {
+            if i > 0 && self.rules[i - 1].0 != *name {
+                index = 0;
+            }
+            writeln!(out, "rule {} {} {}", name, index, locate(*range)).unwrap();
+            index += 1;
+        }
+
+        out
+    }
+
+    /// Finds the applications of the functions of the map in the
+    /// result of the evaluation of a program. An application is only
+    /// stuck if its strict arguments are in head normal form, as the
+    /// ones that are not (like a variable under a lambda) could still
+    /// match a rule once they are known.
+    pub fn stuck_apps(&self, result: &str) -> Vec<StuckApp> {
+        let mut apps = Vec::new();
+        if let Ok(term) = hvm::syntax::read_term(result) {
+            self.find_stuck(&term, &mut apps);
+        }
+        apps
+    }
+
+    fn find_stuck(&self, term: &Term, apps: &mut Vec<StuckApp>) {
+        match term {
+            Term::Var { .. } | Term::U6O { .. } | Term::F6O { .. } => (),
+            Term::Dup { expr, body, .. } | Term::Let { expr, body, .. } => {
+                self.find_stuck(expr, apps);
+                self.find_stuck(body, apps);
+            }
+            Term::Lam { body, .. } => self.find_stuck(body, apps),
+            Term::App { func, argm } => {
+                self.find_stuck(func, apps);
+                self.find_stuck(argm, apps);
+            }
+            Term::Sup { val0, val1 } | Term::Op2 { val0, val1, .. } => {
+                self.find_stuck(val0, apps);
+                self.find_stuck(val1, apps);
+            }
+            Term::Ctr { name, args } => {
+                if let Some(fun) = self.get(name) {
+                    let ready = args
+                        .iter()
+                        .zip(&fun.strict)
+                        .all(|(arg, strict)| !strict || self.is_head_normal(arg));
+                    if ready {
+                        apps.push(StuckApp {
+                            name: name.clone(),
+                            term: term.to_string(),
+                        });
+                    }
+                }
+                for arg in args {
+                    self.find_stuck(arg, apps);
+                }
+            }
+        }
+    }
+
+    /// A term whose head cannot be reduced anymore, so a pattern can
+    /// be matched against it.
+    fn is_head_normal(&self, term: &Term) -> bool {
+        match term {
+            Term::U6O { .. } | Term::F6O { .. } | Term::Lam { .. } => true,
+            Term::Ctr { name, .. } => self.get(name).is_none(),
+            _ => false,
+        }
+    }
+}
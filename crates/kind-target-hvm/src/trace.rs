@@ -0,0 +1,141 @@
+//! Traces of the evaluation. A traced function is compiled to a
+//! wrapper that logs the call with `Apps.HVM.log` and then calls the
+//! original rules, that are renamed to `{name}__trace`.
+
+use hvm::syntax::{Rule, Term};
+use kind_tree::untyped;
+
+use crate::compile_str;
+
+/// Function that cuts the arguments of the traces at the depth that
+/// is given to it, so the log does not need to normalize all of them.
+const CUT: &str = "Kind.Trace.cut_";
+
+/// Prefix of the constructors of the logs that print the arguments,
+/// so a call to `Double 3` is printed as `(Kind.Trace.Double 3)`.
+const CALL: &str = "Kind.Trace";
+
+/// Which functions are traced and how.
+#[derive(Clone, Debug, Default)]
+pub struct TraceOptions {
+    /// Traces all of the functions and not only the ones that are
+    /// marked with `#trace`.
+    pub all: bool,
+    /// Traces only the functions whose names match the pattern, so the
+    /// functions marked with `#trace` that don't match it are not
+    /// traced. A `*` matches any sequence of characters.
+    pub filter: Option<String>,
+    /// Prints the arguments of all of the traced functions up to this
+    /// depth. The functions marked with `#trace = true` print all of
+    /// their arguments if it's not given.
+    pub depth: Option<u64>,
+}
+
+/// Matches a name against a pattern where `*` matches any sequence
+/// of characters.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| matches(rest, &name[i..]))
+        }
+    }
+}
+
+impl TraceOptions {
+    /// Returns `None` if the entry is not traced and `Some(true)` if
+    /// the trace prints its arguments. Constructors are never traced,
+    /// otherwise the patterns would not match the renamed ones.
+    pub fn of_entry(&self, entry: &untyped::Entry) -> Option<bool> {
+        if entry.rules.is_empty() {
+            return None;
+        }
+
+        let traced = match &self.filter {
+            Some(pattern) => matches(pattern, entry.name.to_str()),
+            None => self.all || entry.attrs.trace.is_some(),
+        };
+
+        traced.then(|| self.depth.is_some() || entry.attrs.trace.unwrap_or(false))
+    }
+
+    /// The term that is logged by a call to a traced function.
+    pub fn log_term(&self, name: &str, args: &[Box<Term>], with_args: bool) -> Box<Term> {
+        if !with_args {
+            return compile_str(name);
+        }
+
+        let args = args.iter().map(|arg| match self.depth {
+            Some(depth) => Box::new(Term::Ctr {
+                name: CUT.to_string(),
+                args: vec![Box::new(Term::U6O { numb: depth }), arg.clone()],
+            }),
+            None => arg.clone(),
+        });
+
+        Box::new(Term::Ctr {
+            name: format!("{}.{}", CALL, name),
+            args: args.collect(),
+        })
+    }
+
+    /// Rules of the function that cuts the arguments at a depth. It
+    /// copies each one of the constructors of the book and turns the
+    /// values that are too deep into `"..."`.
+    pub fn cut_rules(&self, book: &untyped::Book) -> Vec<Rule> {
+        let var = |name: &str| {
+            Box::new(Term::Var {
+                name: name.to_string(),
+            })
+        };
+        let cut = |depth: Box<Term>, term: Box<Term>| {
+            Box::new(Term::Ctr {
+                name: CUT.to_string(),
+                args: vec![depth, term],
+            })
+        };
+
+        let mut rules = vec![Rule {
+            lhs: cut(Box::new(Term::U6O { numb: 0 }), var("x")),
+            rhs: compile_str("..."),
+        }];
+
+        for entry in book.entrs.values().filter(|x| x.rules.is_empty()) {
+            let name = entry.name.to_string();
+
+            let fields: Vec<String> = (0..entry.args.len()).map(|i| format!("x{}", i)).collect();
+
+            let depth = Box::new(Term::Op2 {
+                oper: hvm::syntax::Oper::Sub,
+                val0: var("n"),
+                val1: Box::new(Term::U6O { numb: 1 }),
+            });
+
+            rules.push(Rule {
+                lhs: cut(
+                    var("n"),
+                    Box::new(Term::Ctr {
+                        name: name.clone(),
+                        args: fields.iter().map(|x| var(x)).collect(),
+                    }),
+                ),
+                rhs: Box::new(Term::Ctr {
+                    name,
+                    args: fields.iter().map(|x| cut(depth.clone(), var(x))).collect(),
+                }),
+            })
+        }
+
+        rules.push(Rule {
+            lhs: cut(var("n"), var("x")),
+            rhs: var("x"),
+        });
+
+        rules
+    }
+}
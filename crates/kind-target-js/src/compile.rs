@@ -0,0 +1,413 @@
+use std::fmt::Display;
+use std::sync::mpsc::Sender;
+
+use kind_report::data::Diagnostic;
+use kind_tree::untyped::{self, Expr, ExprKind};
+use kind_tree::Operator;
+use linked_hash_map::LinkedHashMap;
+
+use crate::diagnostic::JsDiagnostic;
+use crate::GenericCompilationToJSError;
+
+const RUNTIME: &str = include_str!("runtime.js");
+
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// A compiled ES module. Every definition is stored by its
+/// name in Kind.
+pub struct File {
+    /// Names of the fields of `Data.String.cons`
+    pub string_fields: (String, String),
+    pub ctrs: LinkedHashMap<String, String>,
+    pub funs: LinkedHashMap<String, String>,
+}
+
+pub struct CompileCtx<'a> {
+    file: File,
+    book: &'a untyped::Book,
+    sender: Sender<Box<dyn Diagnostic>>,
+    failed: bool,
+}
+
+/// Name of a definition. The names of Kind cannot contain `$`
+/// so they never collide with the names of the runtime.
+fn global_name(name: &str) -> String {
+    format!("${}", name.replace('.', "$"))
+}
+
+/// Name of a local variable.
+fn local_name(name: &str) -> String {
+    let name = name.replace('.', "$");
+    if RESERVED.contains(&name.as_str()) {
+        format!("{}$", name)
+    } else {
+        name
+    }
+}
+
+fn compile_oper(op: &Operator, left: String, right: String) -> String {
+    use Operator::*;
+    match op {
+        Add => format!("(({} + {}) & $mask)", left, right),
+        Sub => format!("(({} - {}) & $mask)", left, right),
+        Mul => format!("(({} * {}) & $mask)", left, right),
+        Shl => format!("(({} << {}) & $mask)", left, right),
+        Div => format!("({} / {})", left, right),
+        Mod => format!("({} % {})", left, right),
+        And => format!("({} & {})", left, right),
+        Or => format!("({} | {})", left, right),
+        Xor => format!("({} ^ {})", left, right),
+        Shr => format!("({} >> {})", left, right),
+        Ltn => format!("({} < {} ? 1n : 0n)", left, right),
+        Lte => format!("({} <= {} ? 1n : 0n)", left, right),
+        Eql => format!("({} === {} ? 1n : 0n)", left, right),
+        Gte => format!("({} >= {} ? 1n : 0n)", left, right),
+        Gtn => format!("({} > {} ? 1n : 0n)", left, right),
+        Neq => format!("({} !== {} ? 1n : 0n)", left, right),
+    }
+}
+
+pub fn compile_book(
+    book: &untyped::Book,
+    sender: Sender<Box<dyn Diagnostic>>,
+) -> Result<File, GenericCompilationToJSError> {
+    let mut ctx = CompileCtx {
+        file: File {
+            string_fields: ("x".to_string(), "xs".to_string()),
+            ctrs: Default::default(),
+            funs: Default::default(),
+        },
+        book,
+        sender,
+        failed: false,
+    };
+
+    if let Some(cons) = book.entrs.get("Data.String.cons") {
+        if let [head, tail] = cons.args.as_slice() {
+            ctx.file.string_fields = (head.0.clone(), tail.0.clone());
+        }
+    }
+
+    for entry in book.entrs.values() {
+        ctx.compile_entry(entry);
+    }
+
+    if ctx.failed {
+        return Err(GenericCompilationToJSError);
+    }
+
+    Ok(ctx.file)
+}
+
+impl<'a> CompileCtx<'a> {
+    fn send_err(&mut self, err: Box<dyn Diagnostic>) {
+        self.sender.send(err).unwrap();
+        self.failed = true;
+    }
+
+    /// Names of the fields of a constructor.
+    fn fields(&self, name: &str, arity: usize) -> Vec<String> {
+        match self.book.entrs.get(name) {
+            Some(entry) if entry.args.len() == arity => {
+                entry.args.iter().map(|x| x.0.clone()).collect()
+            }
+            _ => (0..arity).map(|i| format!("_{}", i)).collect(),
+        }
+    }
+
+    fn compile_object(&self, name: &str, args: Vec<String>) -> String {
+        let fields = self.fields(name, args.len());
+        let mut res = format!("{{ $: \"{}\"", name);
+        for (field, arg) in fields.iter().zip(args) {
+            res.push_str(&format!(", {}: {}", field, arg));
+        }
+        res.push_str(" }");
+        res
+    }
+
+    /// Compiles the application of a definition. If it's not
+    /// saturated then it's turned into a curried function. The
+    /// calls of functions are delayed, so the arguments that are
+    /// never matched are never computed.
+    fn compile_call(&mut self, name: &str, args: &[Box<Expr>], is_ctr: bool) -> String {
+        let arity = self
+            .book
+            .entrs
+            .get(name)
+            .map(|x| x.args.len())
+            .unwrap_or(args.len());
+
+        let mut args: Vec<String> = args.iter().map(|x| self.compile_expr(x)).collect();
+        let extra = if args.len() > arity {
+            args.split_off(arity)
+        } else {
+            Vec::new()
+        };
+
+        let missing: Vec<String> = (args.len()..arity).map(|i| format!("$a{}", i)).collect();
+        args.extend(missing.iter().cloned());
+
+        let mut res = if is_ctr {
+            self.compile_object(name, args)
+        } else {
+            format!("{}({})", global_name(name), args.join(", "))
+        };
+
+        for param in missing.iter().rev() {
+            res = format!("(({}) => {})", param, res);
+        }
+
+        if extra.is_empty() && (is_ctr || !missing.is_empty()) {
+            return res;
+        }
+
+        for arg in extra {
+            res = format!("$force({})({})", res, arg);
+        }
+
+        format!("$lazy(() => {})", res)
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) -> String {
+        match &expr.data {
+            ExprKind::Var { name } => local_name(name.to_str()),
+            ExprKind::Lambda { param, body, .. } => {
+                format!(
+                    "(({}) => {})",
+                    local_name(param.to_str()),
+                    self.compile_expr(body)
+                )
+            }
+            ExprKind::App { fun, args } => {
+                let mut res = self.compile_expr(fun);
+                for arg in args {
+                    res = format!("$force({})({})", res, self.compile_expr(arg));
+                }
+                format!("$lazy(() => {})", res)
+            }
+            ExprKind::Fun { name, args } => self.compile_call(name.to_str(), args, false),
+            ExprKind::Ctr { name, args } => self.compile_call(name.to_str(), args, true),
+            ExprKind::Let { name, val, next } => format!(
+                "(({}) => {})({})",
+                local_name(name.to_str()),
+                self.compile_expr(next),
+                self.compile_expr(val)
+            ),
+            ExprKind::U60 { numb } => format!("{}n", numb),
+            ExprKind::F60 { .. } => {
+                self.send_err(Box::new(JsDiagnostic::FloatUsed(expr.range)));
+                "undefined".to_string()
+            }
+            ExprKind::Binary { op, left, right } => {
+                let left = self.compile_expr(left);
+                let right = self.compile_expr(right);
+                let oper = compile_oper(op, "$l".to_string(), "$r".to_string());
+                format!("$binary({}, {}, ($l, $r) => {})", left, right, oper)
+            }
+            ExprKind::Str { val } => format!("$str({:?})", val),
+            ExprKind::Err => unreachable!("Internal Error: 'ERR' cannot be a relevant term"),
+        }
+    }
+
+    /// Compiles a pattern that matches the value in `path` to the
+    /// conditions that it checks and the variables that it binds. The
+    /// book is flattened, so the fields of the constructors are always
+    /// variables, and the value was already forced.
+    fn compile_pat(
+        &mut self,
+        path: String,
+        pat: &Expr,
+        conds: &mut Vec<String>,
+        binds: &mut Vec<(String, String)>,
+    ) {
+        match &pat.data {
+            ExprKind::Var { name } if name.to_str() == "_" => (),
+            ExprKind::Var { name } => binds.push((local_name(name.to_str()), path)),
+            ExprKind::Ctr { name, args } | ExprKind::Fun { name, args } => {
+                conds.push(format!("{}.$ === \"{}\"", path, name));
+                let fields = self.fields(name.to_str(), args.len());
+                for (field, arg) in fields.iter().zip(args) {
+                    self.compile_pat(format!("{}.{}", path, field), arg, conds, binds)
+                }
+            }
+            ExprKind::U60 { numb } => conds.push(format!("{} === {}n", path, numb)),
+            ExprKind::F60 { .. } => self.send_err(Box::new(JsDiagnostic::FloatUsed(pat.range))),
+            _ => unreachable!("Internal Error: Invalid pattern"),
+        }
+    }
+
+    fn compile_rule(&mut self, rule: &untyped::Rule) -> (bool, String) {
+        let mut conds = Vec::new();
+        let mut binds = Vec::new();
+
+        for (i, pat) in rule.pats.iter().enumerate() {
+            self.compile_pat(format!("${}", i), pat, &mut conds, &mut binds);
+        }
+
+        // Rules without conditions are written directly in the function
+        let indent = if conds.is_empty() { "  " } else { "    " };
+
+        let mut body = String::new();
+        for (name, path) in binds {
+            body.push_str(&format!("{}const {} = {};\n", indent, name, path));
+        }
+        body.push_str(&format!(
+            "{}return {};\n",
+            indent,
+            self.compile_expr(&rule.body)
+        ));
+
+        if conds.is_empty() {
+            (true, body)
+        } else {
+            (
+                false,
+                format!("  if ({}) {{\n{}  }}\n", conds.join(" && "), body),
+            )
+        }
+    }
+
+    fn compile_entry(&mut self, entry: &untyped::Entry) {
+        let name = entry.name.to_string();
+
+        if entry.rules.is_empty() {
+            let params: Vec<String> = entry.args.iter().map(|x| local_name(&x.0)).collect();
+            let res = format!(
+                "function {}({}) {{\n  return {};\n}}\n",
+                global_name(&name),
+                params.join(", "),
+                self.compile_object(&name, params.clone())
+            );
+            self.file.ctrs.insert(name, res);
+            return;
+        }
+
+        let params: Vec<String> = (0..entry.args.len()).map(|i| format!("${}", i)).collect();
+
+        let mut res = format!(
+            "function {}({}) {{\n",
+            global_name(&name),
+            params.join(", ")
+        );
+
+        // The arguments that some rule matches against a pattern are
+        // forced before the rules are tried.
+        for (i, param) in params.iter().enumerate() {
+            let strict = entry.rules.iter().any(|rule| {
+                !matches!(
+                    rule.pats.get(i).map(|x| &x.data),
+                    Some(ExprKind::Var { .. }) | None
+                )
+            });
+            if strict {
+                res.push_str(&format!("  {} = $force({});\n", param, param));
+            }
+        }
+
+        let mut exhaustive = false;
+
+        for rule in &entry.rules {
+            let (total, code) = self.compile_rule(rule);
+            res.push_str(&code);
+            if total {
+                exhaustive = true;
+                break;
+            }
+        }
+
+        if !exhaustive {
+            res.push_str(&format!(
+                "  return $fail(\"{}\", [{}]);\n",
+                name,
+                params.join(", ")
+            ));
+        }
+
+        res.push_str("}\n");
+
+        self.file.funs.insert(name, res);
+    }
+}
+
+impl Display for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "const $str_head = \"{}\";", self.string_fields.0)?;
+        writeln!(f, "const $str_tail = \"{}\";\n", self.string_fields.1)?;
+        writeln!(f, "{}", RUNTIME)?;
+
+        for ctr in self.ctrs.values() {
+            writeln!(f, "{}", ctr)?;
+        }
+
+        for fun in self.funs.values() {
+            writeln!(f, "{}", fun)?;
+        }
+
+        let names: Vec<&String> = self.ctrs.keys().chain(self.funs.keys()).collect();
+
+        for name in &names {
+            writeln!(
+                f,
+                "const $js{} = $export({});",
+                global_name(name),
+                global_name(name)
+            )?;
+        }
+
+        writeln!(f, "\nexport {{")?;
+        for name in &names {
+            writeln!(f, "  $js{} as \"{}\",", global_name(name), name)?;
+        }
+        writeln!(f, "}};")
+    }
+}
@@ -0,0 +1,40 @@
+use kind_report::{
+    data::{Color, Diagnostic, DiagnosticFrame, Marker, Severity},
+    RenderConfig,
+};
+use kind_span::Range;
+
+pub enum JsDiagnostic {
+    FloatUsed(Range),
+}
+
+impl Diagnostic for JsDiagnostic {
+    fn get_syntax_ctx(&self) -> Option<kind_span::SyntaxCtxIndex> {
+        match self {
+            JsDiagnostic::FloatUsed(range) => Some(range.ctx),
+        }
+    }
+
+    fn to_diagnostic_frame(&self, _: &RenderConfig) -> DiagnosticFrame {
+        match self {
+            JsDiagnostic::FloatUsed(range) => DiagnosticFrame {
+                code: 700,
+                severity: Severity::Error,
+                title: "Found F60 in JavaScript program".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+        }
+    }
+
+    fn get_severity(&self) -> Severity {
+        Severity::Error
+    }
+}
@@ -0,0 +1,47 @@
+//! Compiles the untyped book to an ES module. The rules become
+//! functions that try each one of the patterns, the constructors
+//! become tagged objects (e.g. `{ $: "Data.Maybe.some", val: 2n }`)
+//! and the U60 values become BigInts masked to 60 bits.
+//!
+//! The evaluation is lazy like in the HVM: the calls are thunks that
+//! are forced only when a pattern or an operator needs their values,
+//! so `Const 7 (Loop 0)` returns `7`. The calls in the tail of a
+//! function are returned as thunks, so the recursion in the tail
+//! runs in the loop of `$force` and does not grow the stack.
+//!
+//! Every definition is exported through a wrapper that converts
+//! the `Data.String` values to JavaScript strings and the numbers
+//! to BigInts, so the module can be used from the outside like
+//!
+//! ```js
+//! import { "Main" as main } from "./main.mjs";
+//! console.log(main());
+//! ```
+
+use std::{error::Error, fmt::Display, sync::mpsc::Sender};
+
+use kind_report::data::Diagnostic;
+use kind_tree::untyped;
+
+pub use compile::File;
+
+mod compile;
+mod diagnostic;
+
+#[derive(Debug)]
+pub struct GenericCompilationToJSError;
+
+impl Display for GenericCompilationToJSError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "generic compilation to js error")
+    }
+}
+
+impl Error for GenericCompilationToJSError {}
+
+pub fn compile_book(
+    book: untyped::Book,
+    sender: Sender<Box<dyn Diagnostic>>,
+) -> Result<File, GenericCompilationToJSError> {
+    compile::compile_book(&book, sender)
+}
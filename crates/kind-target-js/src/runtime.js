@@ -0,0 +1,101 @@
+const $mask = 0xFFFFFFFFFFFFFFFn;
+
+const $str = (text) => {
+  const chars = Array.from(text);
+  let res = { $: "Data.String.nil" };
+  for (let i = chars.length - 1; i >= 0; i--) {
+    res = { $: "Data.String.cons", [$str_head]: BigInt(chars[i].codePointAt(0)), [$str_tail]: res };
+  }
+  return res;
+};
+
+// A value that is computed only when it's needed, like the arguments
+// of the functions in the HVM. The calls in the tail of a function
+// are returned as thunks too, so forcing them runs in a loop instead
+// of growing the stack.
+class $Thunk {
+  constructor(run) {
+    this.run = run;
+  }
+}
+
+const $lazy = (run) => new $Thunk(run);
+
+const $force = (value) => {
+  const pending = [];
+  while (value instanceof $Thunk) {
+    if (value.run === null) {
+      value = value.value;
+      continue;
+    }
+    const run = value.run;
+    value.run = null;
+    pending.push(value);
+    value = run();
+  }
+  for (const thunk of pending) {
+    thunk.value = value;
+  }
+  return value;
+};
+
+// The operators are computed right away when their operands are
+// already known, so an accumulator does not become a long chain of
+// thunks that is too deep to be forced.
+const $binary = (left, right, op) =>
+  left instanceof $Thunk || right instanceof $Thunk
+    ? $lazy(() => op($force(left), $force(right)))
+    : op(left, right);
+
+const $to_js = (value) => {
+  value = $force(value);
+  if (typeof value !== "object" || value === null) {
+    return value;
+  }
+  if (value.$ === "Data.String.cons" || value.$ === "Data.String.nil") {
+    let text = "";
+    while (value.$ === "Data.String.cons") {
+      text += String.fromCodePoint(Number($force(value[$str_head])));
+      value = $force(value[$str_tail]);
+    }
+    return text;
+  }
+  const res = {};
+  for (const key in value) {
+    res[key] = key === "$" ? value[key] : $to_js(value[key]);
+  }
+  return res;
+};
+
+const $from_js = (value) => {
+  switch (typeof value) {
+    case "string":
+      return $str(value);
+    case "number":
+      return BigInt(value) & $mask;
+    case "object": {
+      if (value === null) {
+        return value;
+      }
+      const res = {};
+      for (const key in value) {
+        res[key] = key === "$" ? value[key] : $from_js(value[key]);
+      }
+      return res;
+    }
+    default:
+      return value;
+  }
+};
+
+const $export = (fun) => (...args) => $to_js(fun(...args.map($from_js)));
+
+// The error keeps the application that no rule matches in `term`,
+// like the one that the HVM leaves in the result.
+const $fail = (name, args) => {
+  const error = new Error(`No rule of '${name}' matches the arguments`);
+  const term = { $: name };
+  args.forEach((arg, i) => (term[`_${i}`] = arg));
+  error.term = $to_js(term);
+  throw error;
+};
@@ -1,6 +1,6 @@
 use std::{fmt::Display, sync::mpsc::Sender};
 
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use kind_report::data::Diagnostic;
 use kind_tree::{symbol::QualifiedIdent, untyped};
 use kindelia_lang::ast::Name;
@@ -9,7 +9,9 @@ use tiny_keccak::Hasher;
 
 pub use kindelia_lang::ast as kdl;
 
-use crate::{diagnostic::KdlDiagnostic, GenericCompilationToHVMError};
+use crate::contract::references;
+use crate::manifest::{Manifest, ManifestEntry, StatementKind};
+use crate::{diagnostic::KdlDiagnostic, GenericCompilationToKDLError};
 
 pub const KDL_NAME_LEN: usize = 12;
 const U60_MAX: kdl::U120 = kdl::U120(0xFFFFFFFFFFFFFFF);
@@ -39,17 +41,18 @@ pub fn from_str(name_txt: &str) -> Result<Name, String> {
     Ok(Name(num))
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct File {
     pub ctrs: LinkedHashMap<String, kdl::Statement>,
     pub funs: LinkedHashMap<String, kdl::Statement>,
-    pub runs: Vec<kdl::Statement>,
+    pub runs: LinkedHashMap<String, kdl::Statement>,
+    /// Names of the compiled entries.
+    pub manifest: Manifest,
 }
 
 pub struct CompileCtx<'a> {
     file: File,
     kdl_names: FxHashMap<String, kdl::Name>,
-    kdl_states: Vec<String>,
     book: &'a untyped::Book,
 
     kdl_used_names: im_rc::HashSet<String>,
@@ -65,9 +68,9 @@ impl<'a> CompileCtx<'a> {
                 ctrs: Default::default(),
                 funs: Default::default(),
                 runs: Default::default(),
+                manifest: Default::default(),
             },
             kdl_names: Default::default(),
-            kdl_states: Default::default(),
             kdl_used_names: Default::default(),
             book,
             sender,
@@ -122,37 +125,101 @@ fn name_shortener(name: &QualifiedIdent, namespace: &str) -> QualifiedIdent {
     }
 }
 
+/// Name of the entry in the KDL. It's the one given by `#kdl_name`
+/// or the name of the entry, shortened to fit with the namespace.
+pub fn kdl_name(entry: &untyped::Entry, namespace: &str) -> String {
+    entry
+        .attrs
+        .kdl_name
+        .clone()
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| name_shortener(&entry.name, namespace).to_string())
+}
+
 pub fn compile_book(
     book: &untyped::Book,
     sender: Sender<Box<dyn Diagnostic>>,
     namespace: &str,
-) -> Result<File, GenericCompilationToHVMError> {
+) -> Result<File, GenericCompilationToKDLError> {
     let mut ctx = CompileCtx::new(book, sender);
 
+    let mut manifest = Manifest {
+        namespace: namespace.to_string(),
+        entries: Vec::new(),
+    };
+
+    let mut owners: FxHashMap<String, &untyped::Entry> = Default::default();
+
+    // A run is executed by itself when the contract is deployed, so
+    // using it from another entry would execute it again.
+    for entry in book.entrs.values().filter(|x| !x.attrs.kdl_erase) {
+        let refs: FxHashSet<String> = references(entry).into_iter().collect();
+        for run in book.entrs.values().filter(|x| x.attrs.kdl_run && refs.contains(x.name.to_str())) {
+            ctx.send_err(Box::new(KdlDiagnostic::RunIsUsed(
+                run.name.to_string(),
+                entry.name.range,
+                run.name.range,
+            )));
+        }
+    }
+
     for (name, entry) in &book.entrs {
-        let new_name = entry
-            .attrs
-            .kdl_name
-            .clone()
-            .map(|x| x.to_string())
-            .unwrap_or_else(|| name_shortener(&entry.name, namespace).to_string());
-
-        if let Ok(new_name) = from_str(&new_name) {
-            ctx.kdl_names.insert(name.clone(), new_name);
+        let new_name = kdl_name(entry, namespace);
+        let hashed = entry.attrs.kdl_name.is_none() && new_name != entry.name.to_str();
+
+        if let Ok(kdl_ident) = from_str(&new_name) {
+            ctx.kdl_names.insert(name.clone(), kdl_ident);
         } else {
             ctx.send_err(Box::new(KdlDiagnostic::InvalidVarName(
                 entry.name.to_string(),
                 entry.name.range,
             )));
+            continue;
+        }
+
+        // Only these entries become statements with a name.
+        if entry.attrs.kdl_erase || entry.attrs.kdl_run {
+            continue;
+        }
+
+        if let Some(other) = owners.get(&new_name) {
+            ctx.send_err(Box::new(KdlDiagnostic::NameCollision(
+                new_name.clone(),
+                other.name.to_string(),
+                entry.name.to_string(),
+                other.name.range,
+                entry.name.range,
+            )));
+        } else {
+            owners.insert(new_name.clone(), entry);
         }
+
+        manifest.entries.push(ManifestEntry {
+            kind_name: name.clone(),
+            kdl_name: new_name,
+            kind: if entry.rules.is_empty() {
+                StatementKind::Constructor
+            } else {
+                StatementKind::Function
+            },
+            arity: entry.args.len(),
+            hashed,
+        });
     }
 
-    for (_name, entry) in &book.entrs {
+    manifest.entries.sort_by(|a, b| a.kind_name.cmp(&b.kind_name));
+    ctx.file.manifest = manifest;
+
+    // The runs are kept in the order of the book, after all of the
+    // statements that they can use. They cannot use each other, and
+    // each one can change the states that the next ones read, so the
+    // order of the book is the order of their execution.
+    for entry in book.entrs.values() {
         compile_entry(&mut ctx, entry);
     }
 
     if ctx.failed {
-        return Err(GenericCompilationToHVMError);
+        return Err(GenericCompilationToKDLError);
     }
 
     Ok(ctx.file)
@@ -490,7 +557,11 @@ pub fn compile_entry(ctx: &mut CompileCtx, entry: &untyped::Entry) {
         } else {
             let expr = compile_expr(ctx, &entry.rules[0].body);
             let statement = kdl::Statement::Run { expr, sign: None };
-            ctx.file.runs.push(statement);
+            ctx.file.runs.insert(entry.name.to_string(), statement);
+
+            if entry.attrs.kdl_state.is_some() {
+                ctx.send_err(Box::new(KdlDiagnostic::StateOnRun(entry.range)));
+            }
         }
     } else {
         match entry.name.to_str() {
@@ -516,6 +587,10 @@ fn compile_common_function(ctx: &mut CompileCtx, entry: &untyped::Entry) {
     }
 
     if entry.rules.is_empty() {
+        if let Some(state_name) = &entry.attrs.kdl_state {
+            ctx.send_err(Box::new(KdlDiagnostic::StateOnConstructor(state_name.range)));
+        }
+
         // Functions with no rules become Ctr
         let sttm = kdl::Statement::Ctr {
             name,
@@ -541,8 +616,14 @@ fn compile_common_function(ctx: &mut CompileCtx, entry: &untyped::Entry) {
                 } else if entry.rules.len() != 1 {
                     ctx.send_err(Box::new(KdlDiagnostic::ShouldHaveOnlyOneRule(entry.range)));
                     None
+                } else if entry.attrs.kdl_run || entry.attrs.kdl_erase || entry.attrs.kdl_state.is_some() {
+                    ctx.send_err(Box::new(KdlDiagnostic::InvalidStateInit(
+                        state_name.to_string(),
+                        state_name.range,
+                        entry.range,
+                    )));
+                    None
                 } else {
-                    ctx.kdl_states.push(state_name.to_string());
                     Some(compile_expr(ctx, &entry.rules[0].body))
                 }
             } else {
@@ -614,7 +695,7 @@ impl Display for File {
             writeln!(f, "{}", fun.1)?;
         }
         for run in &self.runs {
-            writeln!(f, "{}", run)?;
+            writeln!(f, "{}", run.1)?;
         }
         Ok(())
     }
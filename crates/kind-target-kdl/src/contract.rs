@@ -0,0 +1,387 @@
+//! Contracts that are deployed to Kindelia. A contract is the KDL
+//! file of a book with the functions sorted so each one of them goes
+//! after the ones that it calls, and with a summary of the functions
+//! that it exports and of their states.
+//!
+//! The runs go after all of the functions in the order of the book.
+//! They cannot use each other, so no order comes from their
+//! dependencies, and each one can change the states that the next
+//! ones read, so they run in the order that they were written.
+
+use std::fmt::Display;
+use std::sync::mpsc::Sender;
+
+use fxhash::{FxHashMap, FxHashSet};
+use kind_pass::graph::collect_refs;
+use kind_pass::subst::subst_on_expr;
+use kind_report::data::Diagnostic;
+use kind_tree::{desugared, untyped};
+use linked_hash_map::LinkedHashMap;
+
+use crate::compile::{kdl::Statement, File};
+use crate::diagnostic::KdlDiagnostic;
+use crate::manifest::StatementKind;
+
+/// A function of the contract that can be called after it's deployed.
+#[derive(Clone, Debug)]
+pub struct Export {
+    pub kind_name: String,
+    pub kdl_name: String,
+    pub arity: usize,
+    /// The entry that has the initial value of the state.
+    pub state: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Contract {
+    pub file: File,
+    pub exports: Vec<Export>,
+}
+
+/// Checks the states of the functions with the types of the book,
+/// that are lost after the erasure. A state is a value, so its entry
+/// cannot take arguments and its type cannot be a function, even if
+/// it's given by the name of another function, and it must have the
+/// type that the function returns, after unfolding the aliases of
+/// types. The other properties of the states are checked in the
+/// compilation.
+pub fn check_states(book: &desugared::Book, sender: &Sender<Box<dyn Diagnostic>>) -> bool {
+    let mut failed = false;
+
+    for entry in book.entrs.values() {
+        let Some(state) = &entry.attrs.kdl_state else {
+            continue;
+        };
+        let Some(init) = book.entrs.get(state.to_str()) else {
+            continue;
+        };
+
+        let err = if let Some(arg) = init.args.first() {
+            KdlDiagnostic::StateWithArguments(state.to_string(), state.range, arg.range)
+        } else if let desugared::ExprKind::All { .. } = &init.typ.data {
+            KdlDiagnostic::StateIsAFunction(state.to_string(), state.range, init.typ.range)
+        } else if !same_type(book, &init.typ, &entry.typ) {
+            KdlDiagnostic::StateTypeMismatch(
+                state.to_string(),
+                entry.typ.to_string(),
+                init.typ.to_string(),
+                state.range,
+                init.typ.range,
+            )
+        } else {
+            continue;
+        };
+
+        sender.send(Box::new(err)).unwrap();
+        failed = true;
+    }
+
+    !failed
+}
+
+/// Times that the definitions are unfolded while two types are
+/// compared, so the ones that unfold forever are taken as different.
+const UNFOLD_FUEL: usize = 64;
+
+/// Unfolds the head of a type while it's the application of a
+/// definition with a single rule that only binds variables, like the
+/// aliases of types, and removes the `let`s and the annotations.
+fn unfold(book: &desugared::Book, expr: &desugared::Expr, fuel: &mut usize) -> desugared::Expr {
+    use desugared::ExprKind::*;
+
+    let mut expr = expr.clone();
+    while *fuel > 0 {
+        *fuel -= 1;
+        expr = match expr.data {
+            Fun { name, args } => {
+                let Some(entry) = book.entrs.get(name.to_str()) else {
+                    return desugared::Expr {
+                        data: Fun { name, args },
+                        range: expr.range,
+                    };
+                };
+                let params: Option<Vec<&str>> = match &entry.rules[..] {
+                    [rule] if rule.pats.len() == args.len() => rule
+                        .pats
+                        .iter()
+                        .map(|pat| match &pat.data {
+                            Var { name } => Some(name.to_str()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => None,
+                };
+                let Some(params) = params else {
+                    return desugared::Expr {
+                        data: Fun { name, args },
+                        range: expr.range,
+                    };
+                };
+                let substs = params
+                    .into_iter()
+                    .map(|x| x.to_string())
+                    .zip(args)
+                    .collect();
+                let mut body = *entry.rules[0].body.clone();
+                subst_on_expr(&mut body, substs);
+                body
+            }
+            Let {
+                name,
+                val,
+                mut next,
+            } => {
+                let substs = [(name.to_string(), val)].into_iter().collect();
+                subst_on_expr(&mut next, substs);
+                *next
+            }
+            Ann { expr, .. } => *expr,
+            data => {
+                return desugared::Expr {
+                    data,
+                    range: expr.range,
+                }
+            }
+        };
+    }
+    expr
+}
+
+/// Compares two types after unfolding the definitions that they
+/// use, up to the names of the variables that they bind.
+struct TypeComparer<'a> {
+    book: &'a desugared::Book,
+    /// Pairs of the variables bound at the same place in each type.
+    binds: Vec<(String, String)>,
+    fuel: usize,
+}
+
+impl<'a> TypeComparer<'a> {
+    fn same_under(
+        &mut self,
+        (lp, lb): (&str, &desugared::Expr),
+        (rp, rb): (&str, &desugared::Expr),
+    ) -> bool {
+        self.binds.push((lp.to_string(), rp.to_string()));
+        let res = self.same(lb, rb);
+        self.binds.pop();
+        res
+    }
+
+    fn same(&mut self, left: &desugared::Expr, right: &desugared::Expr) -> bool {
+        use desugared::ExprKind::*;
+
+        let left = unfold(self.book, left, &mut self.fuel);
+        let right = unfold(self.book, right, &mut self.fuel);
+
+        match (&left.data, &right.data) {
+            (Hole { .. } | Err, _) | (_, Hole { .. } | Err) => true,
+            (Var { name: l }, Var { name: r }) => {
+                let (l, r) = (l.to_str(), r.to_str());
+                match self.binds.iter().rev().find(|(x, y)| x == l || y == r) {
+                    Some((x, y)) => x == l && y == r,
+                    None => l == r,
+                }
+            }
+            (
+                All {
+                    param: lp,
+                    typ: lt,
+                    body: lb,
+                    erased: le,
+                },
+                All {
+                    param: rp,
+                    typ: rt,
+                    body: rb,
+                    erased: re,
+                },
+            ) => {
+                le == re
+                    && self.same(lt, rt)
+                    && self.same_under((lp.to_str(), lb), (rp.to_str(), rb))
+            }
+            (
+                Lambda {
+                    param: lp,
+                    body: lb,
+                    erased: le,
+                },
+                Lambda {
+                    param: rp,
+                    body: rb,
+                    erased: re,
+                },
+            ) => le == re && self.same_under((lp.to_str(), lb), (rp.to_str(), rb)),
+            (App { fun: lf, args: la }, App { fun: rf, args: ra }) => {
+                la.len() == ra.len()
+                    && self.same(lf, rf)
+                    && la
+                        .iter()
+                        .zip(ra)
+                        .all(|(l, r)| l.erased == r.erased && self.same(&l.data, &r.data))
+            }
+            (Fun { name: ln, args: la }, Fun { name: rn, args: ra })
+            | (Ctr { name: ln, args: la }, Ctr { name: rn, args: ra }) => {
+                ln.to_str() == rn.to_str()
+                    && la.len() == ra.len()
+                    && la.iter().zip(ra).all(|(l, r)| self.same(l, r))
+            }
+            (
+                Binary {
+                    op: lo,
+                    left: ll,
+                    right: lr,
+                },
+                Binary {
+                    op: ro,
+                    left: rl,
+                    right: rr,
+                },
+            ) => lo == ro && self.same(ll, rl) && self.same(lr, rr),
+            (Typ, Typ) | (NumTypeU60, NumTypeU60) | (NumTypeF60, NumTypeF60) => true,
+            (NumU60 { numb: l }, NumU60 { numb: r }) | (NumF60 { numb: l }, NumF60 { numb: r }) => {
+                l == r
+            }
+            (Str { val: l }, Str { val: r }) => l == r,
+            _ => false,
+        }
+    }
+}
+
+fn same_type(book: &desugared::Book, left: &desugared::Expr, right: &desugared::Expr) -> bool {
+    let mut comparer = TypeComparer {
+        book,
+        binds: Vec::new(),
+        fuel: UNFOLD_FUEL,
+    };
+    comparer.same(left, right)
+}
+
+/// Names of the entries that are used by the rules of an entry.
+pub(crate) fn references(entry: &untyped::Entry) -> Vec<String> {
+    let mut refs = Vec::new();
+    for rule in &entry.rules {
+        rule.pats.iter().for_each(|x| collect_refs(x, &mut refs));
+        collect_refs(&rule.body, &mut refs);
+    }
+    refs
+}
+
+/// Sorts the functions of the file so the functions that are called
+/// by another one go before it. The ones that call each other stay in
+/// the order of the book.
+pub(crate) fn sort_functions(file: &mut File, book: &untyped::Book) {
+    fn visit(
+        name: &str,
+        book: &untyped::Book,
+        funs: &mut LinkedHashMap<String, Statement>,
+        visited: &mut FxHashSet<String>,
+        sorted: &mut LinkedHashMap<String, Statement>,
+    ) {
+        if !visited.insert(name.to_string()) {
+            return;
+        }
+        if let Some(entry) = book.entrs.get(name) {
+            for dep in references(entry) {
+                visit(&dep, book, funs, visited, sorted);
+            }
+        }
+        if let Some(stmt) = funs.remove(name) {
+            sorted.insert(name.to_string(), stmt);
+        }
+    }
+
+    let mut funs = std::mem::take(&mut file.funs);
+    let mut visited = FxHashSet::default();
+    let mut sorted = LinkedHashMap::new();
+
+    for name in book.entrs.keys() {
+        visit(name, book, &mut funs, &mut visited, &mut sorted);
+    }
+
+    sorted.extend(funs);
+    file.funs = sorted;
+}
+
+impl Contract {
+    pub fn new(file: File, book: &untyped::Book) -> Contract {
+        let states: FxHashMap<&str, String> = book
+            .entrs
+            .iter()
+            .filter_map(|(name, entry)| {
+                Some((name.as_str(), entry.attrs.kdl_state.as_ref()?.to_string()))
+            })
+            .collect();
+
+        let exports = file
+            .manifest
+            .entries
+            .iter()
+            .filter(|x| x.kind == StatementKind::Function)
+            .map(|x| Export {
+                kind_name: x.kind_name.clone(),
+                kdl_name: x.kdl_name.clone(),
+                arity: x.arity,
+                state: states.get(x.kind_name.as_str()).cloned(),
+            })
+            .collect();
+
+        Contract { file, exports }
+    }
+
+    /// The KDL file that is deployed.
+    pub fn bundle(&self) -> String {
+        self.file.to_string()
+    }
+}
+
+/// Summary of the contract, with a line for each exported function
+/// and the runs in the order that they are executed.
+impl Display for Contract {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural =
+            |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+
+        writeln!(
+            f,
+            "{}, {} and {}",
+            plural(self.exports.len(), "function"),
+            plural(self.file.ctrs.len(), "constructor"),
+            plural(self.file.runs.len(), "run")
+        )?;
+
+        if !self.file.manifest.namespace.is_empty() {
+            writeln!(f, "namespace {}", self.file.manifest.namespace)?;
+        }
+
+        if !self.exports.is_empty() {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{:<12} {:>5}  {:<20} function",
+                "kdl name", "arity", "state"
+            )?;
+            for export in &self.exports {
+                writeln!(
+                    f,
+                    "{:<12} {:>5}  {:<20} {}",
+                    export.kdl_name,
+                    export.arity,
+                    export.state.as_deref().unwrap_or("-"),
+                    export.kind_name
+                )?;
+            }
+        }
+
+        if !self.file.runs.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "runs, in the order that they are executed")?;
+            for (index, name) in self.file.runs.keys().enumerate() {
+                writeln!(f, "{:>5}  {}", index + 1, name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use kind_report::{data::{Color, Diagnostic, DiagnosticFrame, Marker, Severity}, RenderConfig};
+use kind_report::{data::{Color, Diagnostic, DiagnosticFrame, Marker, Severity, Subtitle, Word}, RenderConfig};
 use kind_span::Range;
 
 pub enum KdlDiagnostic {
@@ -7,6 +7,16 @@ pub enum KdlDiagnostic {
     ShouldHaveOnlyOneRule(Range),
     NoInitEntry(Range),
     FloatUsed(Range),
+    NameCollision(String, String, String, Range, Range),
+    StateOnConstructor(Range),
+    StateOnRun(Range),
+    InvalidStateInit(String, Range, Range),
+    StateIsAFunction(String, Range, Range),
+    StateTypeMismatch(String, String, String, Range, Range),
+    StateWithArguments(String, Range, Range),
+    RunIsUsed(String, Range, Range),
+    OverBudget(String, &'static str, u64, u64, bool, Range),
+    OverTotalBudget(u64, u64, bool),
 }
 
 impl Diagnostic for KdlDiagnostic {
@@ -17,6 +27,16 @@ impl Diagnostic for KdlDiagnostic {
             KdlDiagnostic::ShouldHaveOnlyOneRule(range) => Some(range.ctx),
             KdlDiagnostic::NoInitEntry(range) => Some(range.ctx),
             KdlDiagnostic::FloatUsed(range) => Some(range.ctx),
+            KdlDiagnostic::NameCollision(_, _, _, _, range) => Some(range.ctx),
+            KdlDiagnostic::StateOnConstructor(range) => Some(range.ctx),
+            KdlDiagnostic::StateOnRun(range) => Some(range.ctx),
+            KdlDiagnostic::InvalidStateInit(_, range, _) => Some(range.ctx),
+            KdlDiagnostic::StateIsAFunction(_, range, _) => Some(range.ctx),
+            KdlDiagnostic::StateTypeMismatch(_, _, _, range, _) => Some(range.ctx),
+            KdlDiagnostic::StateWithArguments(_, range, _) => Some(range.ctx),
+            KdlDiagnostic::RunIsUsed(_, range, _) => Some(range.ctx),
+            KdlDiagnostic::OverBudget(_, _, _, _, _, range) => Some(range.ctx),
+            KdlDiagnostic::OverTotalBudget(_, _, _) => None,
         }
     }
 
@@ -92,6 +112,209 @@ impl Diagnostic for KdlDiagnostic {
                     main: true,
                 }],
             },
+            KdlDiagnostic::NameCollision(kdl_name, fst, snd, fst_range, snd_range) => DiagnosticFrame {
+                code: 606,
+                severity: Severity::Error,
+                title: format!("The names '{}' and '{}' are both compiled to '{}'.", fst, snd, kdl_name),
+                subtitles: vec![],
+                hints: vec!["Use the 'kdl_name' attribute to choose another name for one of them".to_string()],
+                positions: vec![
+                    Marker {
+                        position: *snd_range,
+                        color: Color::Fst,
+                        text: "Here!".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *fst_range,
+                        color: Color::Snd,
+                        text: "It has the same name as this one".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+            KdlDiagnostic::StateOnConstructor(range) => DiagnosticFrame {
+                code: 607,
+                severity: Severity::Error,
+                title: "This entry has no rules, so it cannot have a state.".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            KdlDiagnostic::StateOnRun(range) => DiagnosticFrame {
+                code: 608,
+                severity: Severity::Error,
+                title: "A run cannot have a state.".to_string(),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            KdlDiagnostic::InvalidStateInit(name, range, init) => DiagnosticFrame {
+                code: 609,
+                severity: Severity::Error,
+                title: format!("The state '{}' cannot be a run, an erased entry or a function with a state.", name),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![
+                    Marker {
+                        position: *range,
+                        color: Color::Fst,
+                        text: "Here!".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *init,
+                        color: Color::Snd,
+                        text: "Defined here".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+            KdlDiagnostic::StateIsAFunction(name, range, value) => DiagnosticFrame {
+                code: 610,
+                severity: Severity::Error,
+                title: format!("The state '{}' must be a value and not a function.", name),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![
+                    Marker {
+                        position: *range,
+                        color: Color::Fst,
+                        text: "Here!".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *value,
+                        color: Color::Snd,
+                        text: "Its type is a function".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+            KdlDiagnostic::StateTypeMismatch(name, expected, got, range, typ) => DiagnosticFrame {
+                code: 614,
+                severity: Severity::Error,
+                title: format!("The state '{}' has a different type than the function.", name),
+                subtitles: vec![
+                    Subtitle::Phrase(
+                        Color::Fst,
+                        vec![
+                            Word::White("Expected :".to_string()),
+                            Word::Painted(Color::Fst, expected.to_string()),
+                        ],
+                    ),
+                    Subtitle::Phrase(
+                        Color::Snd,
+                        vec![
+                            Word::White("Got      :".to_string()),
+                            Word::Painted(Color::Snd, got.to_string()),
+                        ],
+                    ),
+                ],
+                hints: vec![],
+                positions: vec![
+                    Marker {
+                        position: *range,
+                        color: Color::Fst,
+                        text: "Here!".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *typ,
+                        color: Color::Snd,
+                        text: "The type of the state".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+            KdlDiagnostic::StateWithArguments(name, range, arg) => DiagnosticFrame {
+                code: 615,
+                severity: Severity::Error,
+                title: format!("The state '{}' must be a value and cannot take arguments.", name),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![
+                    Marker {
+                        position: *range,
+                        color: Color::Fst,
+                        text: "Here!".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *arg,
+                        color: Color::Snd,
+                        text: "The first argument".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
+            KdlDiagnostic::OverBudget(name, measure, value, budget, deny, range) => DiagnosticFrame {
+                code: 612,
+                severity: if *deny { Severity::Error } else { Severity::Warning },
+                title: format!("The {} of '{}' is {}, over the budget of {}.", measure, name, value, budget),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![Marker {
+                    position: *range,
+                    color: Color::Fst,
+                    text: "Here!".to_string(),
+                    no_code: false,
+                    main: true,
+                }],
+            },
+            KdlDiagnostic::OverTotalBudget(value, budget, deny) => DiagnosticFrame {
+                code: 613,
+                severity: if *deny { Severity::Error } else { Severity::Warning },
+                title: format!("The size of the file is {}, over the budget of {}.", value, budget),
+                subtitles: vec![],
+                hints: vec![],
+                positions: vec![],
+            },
+            KdlDiagnostic::RunIsUsed(name, range, run) => DiagnosticFrame {
+                code: 611,
+                severity: Severity::Error,
+                title: format!("The run '{}' cannot be used by other entries.", name),
+                subtitles: vec![],
+                hints: vec!["The runs are executed once when the contract is deployed, so move the code that is shared to a function".to_string()],
+                positions: vec![
+                    Marker {
+                        position: *range,
+                        color: Color::Fst,
+                        text: "It's used here".to_string(),
+                        no_code: false,
+                        main: true,
+                    },
+                    Marker {
+                        position: *run,
+                        color: Color::Snd,
+                        text: "The run".to_string(),
+                        no_code: false,
+                        main: false,
+                    },
+                ],
+            },
         }
     }
 
@@ -102,7 +325,22 @@ impl Diagnostic for KdlDiagnostic {
             | ShouldNotHaveArguments(_)
             | ShouldHaveOnlyOneRule(_)
             | NoInitEntry(_)
-            | FloatUsed(_) => Severity::Error
+            | FloatUsed(_)
+            | NameCollision(_, _, _, _, _)
+            | StateOnConstructor(_)
+            | StateOnRun(_)
+            | InvalidStateInit(_, _, _)
+            | StateIsAFunction(_, _, _)
+            | StateTypeMismatch(_, _, _, _, _)
+            | StateWithArguments(_, _, _)
+            | RunIsUsed(_, _, _) => Severity::Error,
+            OverBudget(_, _, _, _, deny, _) | OverTotalBudget(_, _, deny) => {
+                if *deny {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                }
+            }
         }
     }
 }
@@ -1,35 +1,38 @@
 use std::{sync::mpsc::Sender, fmt::Display, error::Error};
 
-use flatten::flatten;
+use kind_pass::flatten::flatten;
 use kind_report::data::Diagnostic;
 use kind_tree::untyped;
 
-pub use compile::File;
+pub use compile::{kdl_name, File};
+pub use contract::{check_states, Contract, Export};
+pub use linearize::{linearize_file, linearize_file_with_lets};
+pub use stats::{Budgets, StatementStats, Stats};
+pub use manifest::{Manifest, ManifestEntry, StatementKind};
 
 mod compile;
+mod contract;
 mod diagnostic;
-mod flatten;
 mod linearize;
-mod subst;
+mod manifest;
+mod stats;
 
 #[derive(Debug)]
-pub struct GenericCompilationToHVMError;
+pub struct GenericCompilationToKDLError;
 
-impl Display for GenericCompilationToHVMError {
+impl Display for GenericCompilationToKDLError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "generic compilation to hvm error")
+        write!(f, "generic compilation to kdl error")
     }
 }
 
-impl Error for GenericCompilationToHVMError { }
+impl Error for GenericCompilationToKDLError { }
 
 pub fn compile_book(
     book: untyped::Book,
     sender: Sender<Box<dyn Diagnostic>>,
     namespace: &str,
-) -> Result<compile::File, GenericCompilationToHVMError> {
-    // TODO: Remove kdl_states (maybe check if they're ever called?)
-    // TODO: Convert to some sort of Kindelia.Contract
+) -> Result<compile::File, GenericCompilationToKDLError> {
     let flattened = flatten(book);
 
     let file = compile::compile_book(&flattened, sender, namespace)?;
@@ -37,3 +40,50 @@ pub fn compile_book(
     let file = linearize::linearize_file(file);
     Ok(file)
 }
+
+/// Compiles the book without making its variables linear, so it can
+/// be compared with the result of `linearize_file`.
+pub fn compile_book_unlinearized(
+    book: untyped::Book,
+    sender: Sender<Box<dyn Diagnostic>>,
+    namespace: &str,
+) -> Result<compile::File, GenericCompilationToKDLError> {
+    let flattened = flatten(book);
+    compile::compile_book(&flattened, sender, namespace)
+}
+
+/// Compiles the book to a contract, with the functions sorted by
+/// their dependencies.
+pub fn compile_contract(
+    book: untyped::Book,
+    sender: Sender<Box<dyn Diagnostic>>,
+    namespace: &str,
+) -> Result<Contract, GenericCompilationToKDLError> {
+    let flattened = flatten(book);
+
+    let mut file = compile::compile_book(&flattened, sender, namespace)?;
+    contract::sort_functions(&mut file, &flattened);
+
+    let file = linearize::linearize_file(file);
+    Ok(Contract::new(file, &flattened))
+}
+
+/// Compiles the book and measures the statements of the file,
+/// reporting the ones that exceed the budgets.
+pub fn compile_stats(
+    book: untyped::Book,
+    sender: Sender<Box<dyn Diagnostic>>,
+    namespace: &str,
+    budgets: &Budgets,
+) -> Result<Stats, GenericCompilationToKDLError> {
+    let flattened = flatten(book);
+
+    let file = compile::compile_book(&flattened, sender.clone(), namespace)?;
+
+    let file = linearize::linearize_file(file);
+    let stats = Stats::measure(&file);
+
+    budgets.check(&stats, &flattened, &sender);
+
+    Ok(stats)
+}
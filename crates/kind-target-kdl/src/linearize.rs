@@ -2,15 +2,13 @@
 // - All variables are renamed to have a global unique name.
 // - All variables are linearized.
 //   - If they're used more than once, dups are inserted.
-//   - If they're used once, nothing changes.
+//   - If they're used once, the binder gets the name of the usage.
 //   - If they're never used, their name is changed to "*"
 // Example:
-//   - sanitizing: `(Foo a b) = (+ a a)`
-//   - results in: `(Foo x0 *) = dup x0.0 x0.1 = x0; (+ x0.0 x0.1)`
+//   - sanitizing: `(Foo a b c) = (+ a (+ a c))`
+//   - results in: `(Foo x0 * x2.0) = dup x0.0 x0.1 = x0; (+ x0.0 (+ x0.1 x2.0))`
 // The algorithm was copied from the hvm
 
-// TODO: This is inserting unneeded `let`s for all linear rule variables
-
 use crate::File;
 use fxhash::FxHashMap;
 use kindelia_lang::ast::{Func, Name, Rule, Statement, Term};
@@ -20,6 +18,8 @@ pub struct LinearizeCtx {
     uses: FxHashMap<Name, u64>,
     name_table: LinkedHashMap<Name, Name>,
     name_count: u64,
+    // Wraps the variables used once in `let`s instead of renaming their binders
+    lets: bool,
 }
 
 impl LinearizeCtx {
@@ -29,11 +29,12 @@ impl LinearizeCtx {
         name
     }
 
-    fn new() -> Self {
+    fn new(lets: bool) -> Self {
         LinearizeCtx {
             uses: Default::default(),
             name_table: Default::default(),
             name_count: 0,
+            lets,
         }
     }
 
@@ -75,15 +76,26 @@ impl LinearizeCtx {
 }
 
 pub fn linearize_file(file: File) -> File {
-    let mut runs = Vec::new();
-    for stmt in file.runs {
+    linearize_file_go(file, false)
+}
+
+// Linearizes the file wrapping the variables used once in `let`s, like
+// it was done before they were substituted. It's only used to measure
+// how much smaller the substitution makes the statements.
+pub fn linearize_file_with_lets(file: File) -> File {
+    linearize_file_go(file, true)
+}
+
+fn linearize_file_go(file: File, lets: bool) -> File {
+    let mut runs: LinkedHashMap<_, _> = Default::default();
+    for (kind_name, stmt) in file.runs {
         if let Statement::Run { expr, sign: _ } = stmt {
-            let expr = linearize_term_independent(&expr);
+            let expr = linearize_term_independent(&expr, lets);
             let stmt = Statement::Run {
                 expr: *expr,
                 sign: None,
             };
-            runs.push(stmt);
+            runs.insert(kind_name, stmt);
         } else {
             unreachable!();
         }
@@ -98,10 +110,10 @@ pub fn linearize_file(file: File) -> File {
             sign: _,
         } = stmt
         {
-            let init = init.map(|x| *linearize_term_independent(&x));
+            let init = init.map(|x| *linearize_term_independent(&x, lets));
             let mut rules: Vec<_> = Default::default();
             for rule in func.rules {
-                let rule = linearize_rule(rule);
+                let rule = linearize_rule(rule, lets);
                 rules.push(rule);
             }
             let func = Func { rules };
@@ -118,11 +130,17 @@ pub fn linearize_file(file: File) -> File {
         }
     }
     let ctrs = file.ctrs;
-    File { ctrs, funs, runs }
+    let manifest = file.manifest;
+    File {
+        ctrs,
+        funs,
+        runs,
+        manifest,
+    }
 }
 
-pub fn linearize_rule(rule: Rule) -> Rule {
-    let mut ctx = LinearizeCtx::new();
+pub fn linearize_rule(rule: Rule, lets: bool) -> Rule {
+    let mut ctx = LinearizeCtx::new(lets);
     ctx.create_param_names(&rule);
     let mut rhs = linearize_term(&mut ctx, &rule.rhs, false);
     let lhs = linearize_term(&mut ctx, &rule.lhs, true);
@@ -141,8 +159,8 @@ pub fn linearize_term(ctx: &mut LinearizeCtx, term: &Term, lhs: bool) -> Box<Ter
     let term = match term {
         Term::Var { name } => {
             if lhs {
-                let mut name = *ctx.name_table.get(name).unwrap_or(name);
-                rename_erased(ctx, &mut name);
+                let name = *ctx.name_table.get(name).unwrap_or(name);
+                let name = binder_name(ctx, &name);
                 Term::Var { name }
             } else {
                 // create a var with the name generated before
@@ -170,7 +188,7 @@ pub fn linearize_term(ctx: &mut LinearizeCtx, term: &Term, lhs: bool) -> Box<Ter
             let new_nam1 = ctx.create_name();
             let expr = linearize_term(ctx, expr, lhs);
             let got_0 = ctx.name_table.remove(nam0);
-            let got_1 = ctx.name_table.remove(nam0);
+            let got_1 = ctx.name_table.remove(nam1);
             ctx.name_table.insert(*nam0, new_nam0);
             ctx.name_table.insert(*nam1, new_nam1);
             let body = linearize_term(ctx, body, lhs);
@@ -182,8 +200,10 @@ pub fn linearize_term(ctx: &mut LinearizeCtx, term: &Term, lhs: bool) -> Box<Ter
             if let Some(x) = got_1 {
                 ctx.name_table.insert(*nam1, x);
             }
-            let nam0 = Name::from_str_unsafe(&format!("{}{}", new_nam0, ".0"));
-            let nam1 = Name::from_str_unsafe(&format!("{}{}", new_nam1, ".0"));
+            let body = dup_var(ctx, &new_nam0, Box::new(Term::Var { name: new_nam0 }), body);
+            let body = dup_var(ctx, &new_nam1, Box::new(Term::Var { name: new_nam1 }), body);
+            let nam0 = binder_name(ctx, &new_nam0);
+            let nam1 = binder_name(ctx, &new_nam1);
             Term::Dup {
                 nam0,
                 nam1,
@@ -192,7 +212,7 @@ pub fn linearize_term(ctx: &mut LinearizeCtx, term: &Term, lhs: bool) -> Box<Ter
             }
         }
         Term::Lam { name, body } => {
-            let mut new_name = ctx.create_name();
+            let new_name = ctx.create_name();
             let got_name = ctx.name_table.remove(name);
             ctx.name_table.insert(*name, new_name);
             let body = linearize_term(ctx, body, lhs);
@@ -204,9 +224,8 @@ pub fn linearize_term(ctx: &mut LinearizeCtx, term: &Term, lhs: bool) -> Box<Ter
                 name: new_name,
             });
             let body = dup_var(ctx, &new_name, expr, body);
-            rename_erased(ctx, &mut new_name);
             Term::Lam {
-                name: new_name,
+                name: binder_name(ctx, &new_name),
                 body,
             }
         }
@@ -254,13 +273,18 @@ pub fn linearize_term(ctx: &mut LinearizeCtx, term: &Term, lhs: bool) -> Box<Ter
 }
 
 // Linearize a term that is not part of a rule, so it doesn't need a shared context
-pub fn linearize_term_independent(term: &Term) -> Box<Term> {
-    linearize_term(&mut LinearizeCtx::new(), term, false)
+pub fn linearize_term_independent(term: &Term, lets: bool) -> Box<Term> {
+    linearize_term(&mut LinearizeCtx::new(lets), term, false)
 }
 
-pub fn rename_erased(ctx: &LinearizeCtx, name: &mut Name) {
-    if ctx.uses.get(name).copied() <= Some(0) {
-        *name = Name::NONE;
+// Name of the binder of a variable. It's erased if the variable is
+// never used and it's the name of the only usage if it's used once, so
+// no `let` is needed to rename it. Otherwise it's duplicated by `dup_var`.
+pub fn binder_name(ctx: &LinearizeCtx, name: &Name) -> Name {
+    match ctx.uses.get(name).copied() {
+        None | Some(0) => Name::NONE,
+        Some(1) if !ctx.lets => Name::from_str_unsafe(&format!("{}.0", name)),
+        Some(_) => *name,
     }
 }
 
@@ -273,12 +297,14 @@ pub fn dup_var(ctx: &mut LinearizeCtx, name: &Name, expr: Box<Term>, body: Box<T
             // if not used nothing is done
             0 => body,
             // if used once just make a let (lambda then app)
-            1 => {
-                let name = Name::from_str_unsafe(&format!("{}.0", name)); // TODO: handle err
+            1 if ctx.lets => {
+                let name = Name::from_str_unsafe(&format!("{}.0", name));
                 let func = Box::new(Term::Lam { name, body });
                 let term = Term::App { func, argm: expr };
                 Box::new(term)
             }
+            // if used once otherwise nothing is done, the binder is renamed
+            1 => body,
             // if used more than once, duplicate
             _ => {
                 let dup_times = amount - 1;
@@ -0,0 +1,36 @@
+//! Manifest that maps the names of the Kind entries to the names of
+//! the compiled KDL statements, so the contracts can still be called
+//! by their Kind names after the shortening.
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementKind {
+    Function,
+    Constructor,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ManifestEntry {
+    #[serde(rename = "name")]
+    pub kind_name: String,
+    pub kdl_name: String,
+    pub kind: StatementKind,
+    pub arity: usize,
+    /// If the name was too long and was replaced by a hash of it.
+    pub hashed: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Manifest {
+    pub namespace: String,
+    /// Entries sorted by their Kind names.
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn to_json(&self) -> String {
+        format!("{}\n", serde_json::to_string_pretty(self).unwrap())
+    }
+}
@@ -0,0 +1,260 @@
+//! Sizes of the statements of a KDL file, to find out what a contract
+//! costs before it's deployed. The sizes are an estimate that follows
+//! the serialization of Kindelia:
+//!
+//! - Each term has a tag of 3 bits and statements have a tag of 2 bits.
+//! - Names have 4 bits with their length and 6 bits per letter.
+//! - Numbers have 8 bits for each group of 7 bits of their value.
+//! - Constructors, functions and statements have 4 bits with their
+//!   arity, operators have 4 bits and rules have 8 bits with their count.
+
+use std::fmt::Display;
+use std::sync::mpsc::Sender;
+
+use kind_report::data::Diagnostic;
+use kind_span::Range;
+use kind_tree::untyped;
+
+use crate::compile::kdl::{Name, Statement, Term};
+use crate::compile::File;
+use crate::diagnostic::KdlDiagnostic;
+
+/// Measures of a statement of the file.
+#[derive(Clone, Debug)]
+pub struct StatementStats {
+    /// `ctr`, `fun` or `run`.
+    pub kind: &'static str,
+    pub kind_name: String,
+    /// Name of the statement or `-` for the runs.
+    pub kdl_name: String,
+    /// Estimated size of the serialized statement in bytes.
+    pub size: u64,
+    pub rules: u64,
+    /// Duplications that the linearization added to the statement.
+    pub dups: u64,
+    /// Number of letters of the name of the statement.
+    pub name_len: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub statements: Vec<StatementStats>,
+}
+
+/// Limits of the measures. Each one that is exceeded is reported as
+/// a warning, or as an error if `deny` is set.
+#[derive(Clone, Debug, Default)]
+pub struct Budgets {
+    pub max_size: Option<u64>,
+    pub max_total_size: Option<u64>,
+    pub max_rules: Option<u64>,
+    pub max_dups: Option<u64>,
+    pub max_name_len: Option<u64>,
+    pub deny: bool,
+}
+
+fn name_letters(name: &Name) -> u64 {
+    let bits = 128 - name.0.leading_zeros() as u64;
+    bits.div_ceil(6)
+}
+
+fn name_bits(name: &Name) -> u64 {
+    4 + 6 * name_letters(name)
+}
+
+fn number_bits(numb: u128) -> u64 {
+    let bits = (128 - numb.leading_zeros() as u64).max(1);
+    8 * bits.div_ceil(7)
+}
+
+/// Size of a term in bits and the number of duplications in it.
+fn term_bits(term: &Term) -> (u64, u64) {
+    match term {
+        Term::Var { name } => (3 + name_bits(name), 0),
+        Term::Dup {
+            nam0,
+            nam1,
+            expr,
+            body,
+        } => {
+            let (expr, expr_dups) = term_bits(expr);
+            let (body, body_dups) = term_bits(body);
+            (
+                3 + name_bits(nam0) + name_bits(nam1) + expr + body,
+                1 + expr_dups + body_dups,
+            )
+        }
+        Term::Lam { name, body } => {
+            let (body, dups) = term_bits(body);
+            (3 + name_bits(name) + body, dups)
+        }
+        Term::App { func, argm } => {
+            let (func, func_dups) = term_bits(func);
+            let (argm, argm_dups) = term_bits(argm);
+            (3 + func + argm, func_dups + argm_dups)
+        }
+        Term::Ctr { name, args } | Term::Fun { name, args } => args.iter().map(term_bits).fold(
+            (3 + name_bits(name) + 4, 0),
+            |(bits, dups), (arg, arg_dups)| (bits + arg, dups + arg_dups),
+        ),
+        Term::Num { numb } => (3 + number_bits(numb.0), 0),
+        Term::Op2 { val0, val1, .. } => {
+            let (val0, val0_dups) = term_bits(val0);
+            let (val1, val1_dups) = term_bits(val1);
+            (3 + 4 + val0 + val1, val0_dups + val1_dups)
+        }
+    }
+}
+
+fn measure_statement(kind_name: &str, statement: &Statement) -> StatementStats {
+    let sign_bits = |sign: &Option<_>| 1 + if sign.is_some() { 65 * 8 } else { 0 };
+    let args_bits = |args: &[Name]| 4 + args.iter().map(name_bits).sum::<u64>();
+
+    let (kind, name, bits, rules, dups) = match statement {
+        Statement::Fun {
+            name,
+            args,
+            func,
+            init,
+            sign,
+        } => {
+            let mut bits = 2 + name_bits(name) + args_bits(args) + 8 + sign_bits(sign) + 1;
+            let mut dups = 0;
+            for rule in &func.rules {
+                let (lhs, lhs_dups) = term_bits(&rule.lhs);
+                let (rhs, rhs_dups) = term_bits(&rule.rhs);
+                bits += lhs + rhs;
+                dups += lhs_dups + rhs_dups;
+            }
+            if let Some(init) = init {
+                let (init, init_dups) = term_bits(init);
+                bits += init;
+                dups += init_dups;
+            }
+            ("fun", Some(name), bits, func.rules.len() as u64, dups)
+        }
+        Statement::Ctr { name, args, sign } => (
+            "ctr",
+            Some(name),
+            2 + name_bits(name) + args_bits(args) + sign_bits(sign),
+            0,
+            0,
+        ),
+        Statement::Run { expr, sign } => {
+            let (expr, dups) = term_bits(expr);
+            ("run", None, 2 + expr + sign_bits(sign), 0, dups)
+        }
+        Statement::Reg { name, ownr, sign } => (
+            "reg",
+            Some(name),
+            2 + name_bits(name) + number_bits(ownr.0) + sign_bits(sign),
+            0,
+            0,
+        ),
+    };
+
+    StatementStats {
+        kind,
+        kind_name: kind_name.to_string(),
+        kdl_name: name
+            .map(|x| x.to_string())
+            .unwrap_or_else(|| "-".to_string()),
+        size: bits.div_ceil(8),
+        rules,
+        dups,
+        name_len: name.map(name_letters).unwrap_or(0),
+    }
+}
+
+impl Stats {
+    pub fn measure(file: &File) -> Stats {
+        let statements = file
+            .ctrs
+            .iter()
+            .chain(file.funs.iter())
+            .chain(file.runs.iter())
+            .map(|(kind_name, statement)| measure_statement(kind_name, statement))
+            .collect();
+        Stats { statements }
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.statements.iter().map(|x| x.size).sum()
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "kind {:<12} {:>6} {:>6} {:>5} {:>5}  statement",
+            "kdl name", "size", "rules", "dups", "name"
+        )?;
+        for stats in &self.statements {
+            writeln!(
+                f,
+                "{:<4} {:<12} {:>6} {:>6} {:>5} {:>5}  {}",
+                stats.kind,
+                stats.kdl_name,
+                stats.size,
+                stats.rules,
+                stats.dups,
+                stats.name_len,
+                stats.kind_name
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<17} {:>6} {:>6} {:>5}",
+            "total",
+            self.total_size(),
+            self.statements.iter().map(|x| x.rules).sum::<u64>(),
+            self.statements.iter().map(|x| x.dups).sum::<u64>()
+        )
+    }
+}
+
+impl Budgets {
+    /// Reports the measures that exceed the budgets.
+    pub fn check(&self, stats: &Stats, book: &untyped::Book, sender: &Sender<Box<dyn Diagnostic>>) {
+        let report = |err: KdlDiagnostic| sender.send(Box::new(err)).unwrap();
+
+        for stats in &stats.statements {
+            let range = book
+                .entrs
+                .get(&stats.kind_name)
+                .map(|x| x.name.range)
+                .unwrap_or_else(Range::ghost_range);
+
+            let measures = [
+                ("size", stats.size, self.max_size),
+                ("number of rules", stats.rules, self.max_rules),
+                ("number of duplications", stats.dups, self.max_dups),
+                ("name length", stats.name_len, self.max_name_len),
+            ];
+
+            for (measure, value, budget) in measures {
+                match budget {
+                    Some(budget) if value > budget => report(KdlDiagnostic::OverBudget(
+                        stats.kind_name.clone(),
+                        measure,
+                        value,
+                        budget,
+                        self.deny,
+                        range,
+                    )),
+                    _ => (),
+                }
+            }
+        }
+
+        match self.max_total_size {
+            Some(budget) if stats.total_size() > budget => report(KdlDiagnostic::OverTotalBudget(
+                stats.total_size(),
+                budget,
+                self.deny,
+            )),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! A small evaluator of the compiled KDL, used to check that the
+//! KDL target computes the same results as the HVM one. It's lazy
+//! like the runtime of Kindelia, so the arguments are evaluated only
+//! when a rule matches against them, and the values of the `dup`s
+//! are shared. The normal form is read back to a HVM term with the
+//! Kind names of the book, so it can be compared with the result of
+//! the HVM.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use hvm::syntax::{Oper as HvmOper, Term as HvmTerm};
+use kind_pass::flatten::flatten;
+use kind_target_kdl::File;
+use kind_tree::untyped;
+use kindelia_lang::ast::{Name, Oper, Statement, Term};
+
+/// Numbers of KDL have 120 bits.
+const MASK: u128 = (1 << 120) - 1;
+
+type Thunk = Rc<RefCell<Cell>>;
+
+enum Cell {
+    Pending(Term, Env),
+    Done(Value),
+}
+
+#[derive(Clone, Default)]
+struct Env(Option<Rc<(Name, Thunk, Env)>>);
+
+impl Env {
+    fn bind(&self, name: Name, value: Thunk) -> Env {
+        if name.is_none() {
+            return self.clone();
+        }
+        Env(Some(Rc::new((name, value, self.clone()))))
+    }
+
+    fn get(&self, name: &Name) -> Thunk {
+        let mut env = self;
+        while let Some(bind) = &env.0 {
+            if bind.0 == *name {
+                return bind.1.clone();
+            }
+            env = &bind.2;
+        }
+        panic!("Unbound variable '{}' in the KDL", name)
+    }
+}
+
+/// A term in the weak head normal form. The applications that cannot
+/// be reduced keep their arguments as they are.
+#[derive(Clone)]
+enum Value {
+    Num(u128),
+    Ctr(Name, Vec<Thunk>),
+    Lam(Name, Rc<Term>, Env),
+    Var(String),
+    App(Box<Value>, Thunk),
+    Fun(Name, Vec<Thunk>),
+    Op2(Oper, Box<Value>, Box<Value>),
+}
+
+fn done(value: Value) -> Thunk {
+    Rc::new(RefCell::new(Cell::Done(value)))
+}
+
+fn delay(term: &Term, env: &Env) -> Thunk {
+    Rc::new(RefCell::new(Cell::Pending(term.clone(), env.clone())))
+}
+
+struct Machine<'a> {
+    funs: HashMap<Name, &'a [kindelia_lang::ast::Rule]>,
+    names: HashMap<String, String>,
+    fresh: usize,
+}
+
+impl<'a> Machine<'a> {
+    fn force(&self, thunk: &Thunk) -> Value {
+        let pending = match &*thunk.borrow() {
+            Cell::Done(value) => return value.clone(),
+            Cell::Pending(term, env) => (term.clone(), env.clone()),
+        };
+        let value = self.eval(&pending.0, pending.1);
+        *thunk.borrow_mut() = Cell::Done(value.clone());
+        value
+    }
+
+    fn eval(&self, term: &Term, mut env: Env) -> Value {
+        let mut term = term.clone();
+        loop {
+            let (next, next_env) = match &term {
+                Term::Var { name } => return self.force(&env.get(name)),
+                Term::Dup {
+                    nam0,
+                    nam1,
+                    expr,
+                    body,
+                } => {
+                    let shared = delay(expr, &env);
+                    let env = env.bind(*nam0, shared.clone()).bind(*nam1, shared);
+                    ((**body).clone(), env)
+                }
+                Term::Lam { name, body } => {
+                    return Value::Lam(*name, Rc::new((**body).clone()), env)
+                }
+                Term::App { func, argm } => {
+                    let argm = delay(argm, &env);
+                    match self.eval(func, env.clone()) {
+                        Value::Lam(name, body, lam_env) => {
+                            ((*body).clone(), lam_env.bind(name, argm))
+                        }
+                        func => return Value::App(Box::new(func), argm),
+                    }
+                }
+                Term::Ctr { name, args } => {
+                    return Value::Ctr(*name, args.iter().map(|x| delay(x, &env)).collect())
+                }
+                Term::Fun { name, args } => {
+                    let args: Vec<Thunk> = args.iter().map(|x| delay(x, &env)).collect();
+                    match self.call(name, &args) {
+                        Some(next) => next,
+                        None => return Value::Fun(*name, args),
+                    }
+                }
+                Term::Num { numb } => return Value::Num(**numb),
+                Term::Op2 { oper, val0, val1 } => {
+                    let val0 = self.eval(val0, env.clone());
+                    let val1 = self.eval(val1, env.clone());
+                    return operate(*oper, val0, val1);
+                }
+            };
+            term = next;
+            env = next_env;
+        }
+    }
+
+    /// Finds the first rule of the function that matches the arguments
+    /// and returns its body with the variables of the patterns.
+    fn call(&self, name: &Name, args: &[Thunk]) -> Option<(Term, Env)> {
+        let rules = self.funs.get(name)?;
+
+        'rules: for rule in rules.iter() {
+            let Term::Fun { args: pats, .. } = &rule.lhs else {
+                continue;
+            };
+
+            let mut env = Env::default();
+
+            for (pat, arg) in pats.iter().zip(args) {
+                match pat {
+                    Term::Var { name } => env = env.bind(*name, arg.clone()),
+                    Term::Num { numb } => match self.force(arg) {
+                        Value::Num(value) if value == **numb => (),
+                        Value::Num(_) => continue 'rules,
+                        _ => return None,
+                    },
+                    Term::Ctr { name, args: fields } => match self.force(arg) {
+                        Value::Ctr(ctr, values) if ctr == *name && values.len() == fields.len() => {
+                            for (field, value) in fields.iter().zip(values) {
+                                if let Term::Var { name } = field {
+                                    env = env.bind(*name, value);
+                                }
+                            }
+                        }
+                        Value::Ctr(..) | Value::Num(_) | Value::Lam(..) => continue 'rules,
+                        _ => return None,
+                    },
+                    _ => return None,
+                }
+            }
+
+            return Some((rule.rhs.clone(), env));
+        }
+
+        None
+    }
+
+    fn name(&self, name: &Name) -> String {
+        let name = name.to_string();
+        self.names.get(&name).cloned().unwrap_or(name)
+    }
+
+    /// Reads back the normal form of a value, naming the variables of
+    /// the lambdas in the order that the HVM does.
+    fn readback(&mut self, value: Value) -> Box<HvmTerm> {
+        let readback_all = |this: &mut Self, args: Vec<Thunk>| {
+            args.iter()
+                .map(|x| {
+                    let value = this.force(x);
+                    this.readback(value)
+                })
+                .collect()
+        };
+
+        Box::new(match value {
+            Value::Num(numb) => HvmTerm::U6O { numb: numb as u64 },
+            Value::Ctr(name, args) | Value::Fun(name, args) => HvmTerm::Ctr {
+                name: self.name(&name),
+                args: readback_all(self, args),
+            },
+            Value::Lam(name, body, env) => {
+                let var = if name.is_none() {
+                    "*".to_string()
+                } else {
+                    self.fresh += 1;
+                    format!("x{}", self.fresh - 1)
+                };
+                let env = env.bind(name, done(Value::Var(var.clone())));
+                let body = self.eval(&body, env);
+                HvmTerm::Lam {
+                    name: var,
+                    body: self.readback(body),
+                }
+            }
+            Value::Var(name) => HvmTerm::Var { name },
+            Value::App(func, argm) => {
+                let func = self.readback(*func);
+                let argm = self.force(&argm);
+                HvmTerm::App {
+                    func,
+                    argm: self.readback(argm),
+                }
+            }
+            Value::Op2(oper, val0, val1) => HvmTerm::Op2 {
+                oper: hvm_oper(oper),
+                val0: self.readback(*val0),
+                val1: self.readback(*val1),
+            },
+        })
+    }
+}
+
+fn operate(oper: Oper, val0: Value, val1: Value) -> Value {
+    let (Value::Num(a), Value::Num(b)) = (&val0, &val1) else {
+        return Value::Op2(oper, Box::new(val0), Box::new(val1));
+    };
+    let (a, b) = (*a, *b);
+    let numb = match oper {
+        Oper::Add => a.wrapping_add(b) & MASK,
+        Oper::Sub => a.wrapping_sub(b) & MASK,
+        Oper::Mul => a.wrapping_mul(b) & MASK,
+        Oper::Div | Oper::Mod if b == 0 => return Value::Op2(oper, Box::new(val0), Box::new(val1)),
+        Oper::Div => a / b,
+        Oper::Mod => a % b,
+        Oper::And => a & b,
+        Oper::Or => a | b,
+        Oper::Xor => a ^ b,
+        Oper::Shl => (a << (b % 120)) & MASK,
+        Oper::Shr => a >> (b % 120),
+        Oper::Ltn => (a < b) as u128,
+        Oper::Lte => (a <= b) as u128,
+        Oper::Eql => (a == b) as u128,
+        Oper::Gte => (a >= b) as u128,
+        Oper::Gtn => (a > b) as u128,
+        Oper::Neq => (a != b) as u128,
+    };
+    Value::Num(numb)
+}
+
+fn hvm_oper(oper: Oper) -> HvmOper {
+    match oper {
+        Oper::Add => HvmOper::Add,
+        Oper::Sub => HvmOper::Sub,
+        Oper::Mul => HvmOper::Mul,
+        Oper::Div => HvmOper::Div,
+        Oper::Mod => HvmOper::Mod,
+        Oper::And => HvmOper::And,
+        Oper::Or => HvmOper::Or,
+        Oper::Xor => HvmOper::Xor,
+        Oper::Shl => HvmOper::Shl,
+        Oper::Shr => HvmOper::Shr,
+        Oper::Ltn => HvmOper::Ltn,
+        Oper::Lte => HvmOper::Lte,
+        Oper::Eql => HvmOper::Eql,
+        Oper::Gte => HvmOper::Gte,
+        Oper::Gtn => HvmOper::Gtn,
+        Oper::Neq => HvmOper::Neq,
+    }
+}
+
+/// Evaluates the `Main` of the file compiled from the book to its
+/// normal form and prints it like the HVM does.
+pub fn eval_main(file: &File, book: untyped::Book) -> String {
+    let funs = file
+        .funs
+        .values()
+        .filter_map(|statement| match statement {
+            Statement::Fun { name, func, .. } => Some((*name, func.rules.as_slice())),
+            _ => None,
+        })
+        .collect();
+
+    // The KDL target compiles the flattened book, so the helpers of
+    // the flattening have names too.
+    let names = flatten(book)
+        .entrs
+        .values()
+        .map(|entry| (kind_target_kdl::kdl_name(entry, ""), entry.name.to_string()))
+        .collect();
+
+    let mut machine = Machine {
+        funs,
+        names,
+        fresh: 0,
+    };
+
+    // A `Main` that is marked with `#kdl_run` is compiled to a run.
+    let main = match file.runs.get("Main") {
+        Some(Statement::Run { expr, .. }) => expr.clone(),
+        _ => Term::Fun {
+            name: Name::from_str_unsafe("Main"),
+            args: vec![],
+        },
+    };
+    let value = machine.eval(&main, Env::default());
+    machine.readback(value).to_string()
+}
@@ -1,24 +1,36 @@
 #![feature(result_flattening)]
+#![feature(internal_output_capture)]
 
-use kind_driver::session::Session;
+use kind_driver::session::{CustomDerive, DeriveResult, Session};
+use kind_driver::target::{self, HvmTarget, JsTarget, KdlTarget, TargetOptions};
+use kindelia_lang::ast::{Name, Statement, Term};
 use kind_report::data::Diagnostic;
 use kind_report::report::{Renderable, Classic};
 use kind_report::RenderConfig;
+use kind_span::Range;
+use kind_tree::concrete::expr::{Expr, ExprKind, Literal};
+use kind_tree::concrete::{Entry, RecordDecl, Rule, SumTypeDecl};
+use kind_tree::symbol::QualifiedIdent;
+use kind_tree::untyped;
 
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+mod kdl_eval;
 
 use ntest::timeout;
 use pretty_assertions::assert_eq;
 use walkdir::{Error, WalkDir};
 
 use kind_driver as driver;
+use kind_pass::erasure;
 
-fn golden_test(path: &Path, run: &dyn Fn(&Path) -> String) {
+fn golden_test(path: &Path, extension: &str, run: &dyn Fn(&Path) -> String) {
     let result = run(path);
 
-    let golden_path = path.with_extension("golden");
+    let golden_path = path.with_extension(extension);
     if let Ok(to_check) = fs::read_to_string(golden_path.clone()) {
         assert_eq!(result, to_check, "Testing file '{}'", path.display());
     } else {
@@ -28,11 +40,21 @@ fn golden_test(path: &Path, run: &dyn Fn(&Path) -> String) {
 }
 
 fn test_kind2(path: &Path, run: fn(&PathBuf, &mut Session) -> Option<String>) -> Result<(), Error> {
+    test_kind2_golden(path, "golden", run)
+}
+
+/// Same as `test_kind2` but with a custom extension for the golden files,
+/// so the same programs can be tested by more than one backend.
+fn test_kind2_golden(
+    path: &Path,
+    extension: &str,
+    run: fn(&PathBuf, &mut Session) -> Option<String>,
+) -> Result<(), Error> {
     for entry in WalkDir::new(path).follow_links(true) {
         let entry = entry?;
         let path = entry.path();
         if path.is_file() && path.extension().map(|x| x == "kind2").unwrap_or(false) {
-            golden_test(path, &|path| {
+            golden_test(path, extension, &|path| {
                 let (rx, tx) = std::sync::mpsc::channel();
                 let root = PathBuf::from("./suite/lib").canonicalize().unwrap();
                 let mut session = Session::new(root, rx, false);
@@ -84,17 +106,286 @@ fn test_checker_issues() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+#[timeout(30000)]
+fn test_core_round_trip() -> Result<(), Error> {
+    test_kind2_golden(Path::new("./suite/checker"), "core.golden", |path, session| {
+        let core = driver::desugar_book(session, path).ok()?.to_string();
+
+        let (book, failed) = kind_parser::parse_core_book(session.diagnostic_sender.clone(), 0, &core);
+        assert!(!failed, "Parsing the core of '{}'", path.display());
+        assert_eq!(book.to_string(), core, "Printing the core of '{}'", path.display());
+
+        let core_path = path.canonicalize().unwrap().with_extension("core");
+        let check = driver::check_core_source(session, core_path, core, Some(1));
+        check.map(|_| "Ok!".to_string()).ok()
+    })?;
+    Ok(())
+}
+
+/// Compiles the book with the HVM target and runs its `Main`.
+fn run_hvm(session: &Session, book: untyped::Book, options: &TargetOptions) -> Option<(String, u64)> {
+    let sender = session.diagnostic_sender.clone();
+    let files = target::compile_with_target(&HvmTarget, book, options, sender).ok()?;
+    driver::execute_file(&files[0].contents, Some(1)).ok()
+}
+
 #[test]
 #[timeout(30000)]
 fn test_run() -> Result<(), Error> {
     test_kind2(Path::new("./suite/run"), |path, session| {
         let entrypoints = vec!["Main".to_string()];
-        let check = driver::erase_book(session, path, entrypoints)
-            .map(|file| driver::compile_book_to_hvm(file, false))
-            .map(|file| driver::execute_file(&file.to_string(), Some(1)))
-            .flatten();
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        run_hvm(session, book, &TargetOptions::default()).map(|x| x.0)
+    })?;
+    Ok(())
+}
 
-        check.ok().map(|x| x.0)
+/// The HVM and KDL targets run the rules after the flattening of
+/// the patterns, so it should not change the result of the HVM's own
+/// pattern matching. The KDL is evaluated by a small interpreter of
+/// its own and should give the same result, and it's kept as a
+/// golden too.
+#[test]
+#[timeout(30000)]
+fn test_run_flattened() -> Result<(), Error> {
+    test_kind2_golden(Path::new("./suite/run"), "kdl.golden", |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+
+        let mut lowered = book.clone();
+        kind_pass::enums::lower_enums(&mut lowered);
+        kind_pass::unbox::unbox_newtypes(&mut lowered);
+
+        let nested = kind_target_hvm::compile_book(lowered.clone(), false);
+        let nested = driver::execute_file(&nested.to_string(), Some(1)).ok()?;
+
+        let flat = run_hvm(session, book, &TargetOptions::default())?;
+
+        assert_eq!(nested.0, flat.0, "Testing file '{}'", path.display());
+
+        let sender = session.diagnostic_sender.clone();
+        let file = kind_target_kdl::compile_book(lowered.clone(), sender, "").ok()?;
+
+        let code = file.to_string();
+        let result = std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(move || kdl_eval::eval_main(&file, lowered))
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(flat.0, result, "Testing file '{}'", path.display());
+
+        Some(code)
+    })?;
+    Ok(())
+}
+
+/// The optimizations cannot change the result of a program and
+/// should not make it take more rewrites to run.
+#[test]
+#[timeout(30000)]
+fn test_run_optimized() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/run"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints.clone()).ok()?;
+
+        let mut optimized = book.clone();
+        let options = kind_optimization::Options::from_level(2);
+        let sender = session.diagnostic_sender.clone();
+        kind_pass::inline::inline_book(&mut optimized, sender, options.inline_threshold);
+        kind_optimization::optimize_book(&mut optimized, &options, &entrypoints);
+
+        let options = TargetOptions::default();
+        let (res, rewrites) = run_hvm(session, book, &options)?;
+        let (opt_res, opt_rewrites) = run_hvm(session, optimized, &options)?;
+
+        assert_eq!(res, opt_res, "Testing file '{}'", path.display());
+        assert!(
+            opt_rewrites <= rewrites,
+            "Testing file '{}': {} rewrites after the optimizations and {} before",
+            path.display(),
+            opt_rewrites,
+            rewrites
+        );
+
+        Some(opt_res)
+    })?;
+    Ok(())
+}
+
+/// The applications that get stuck at runtime are reported at the
+/// place of the source that generated them.
+#[test]
+#[timeout(30000)]
+fn test_run_stuck() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/stuck"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let options = TargetOptions::default();
+        let (result, _) = driver::run_book(session, book, &options, Some(1)).ok()?;
+        Some(result)
+    })?;
+    Ok(())
+}
+
+/// The effects are performed on a host with a fixed input, and the
+/// first argument is a file that the programs can write.
+#[test]
+#[timeout(30000)]
+fn test_run_io() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/io"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let options = TargetOptions::default();
+
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let scratch = std::env::temp_dir().join(format!("kind2-test-io-{}-{}.txt", std::process::id(), name));
+        let mut input = "Kind\n".as_bytes();
+        let mut output = Vec::new();
+        let mut host = driver::io::Host {
+            args: vec![scratch.display().to_string()],
+            input: &mut input,
+            output: &mut output,
+        };
+
+        let outcome = driver::run_io_book(session, book, &options, Some(1), &mut host);
+        let _ = fs::remove_file(&scratch);
+        let outcome = outcome.ok()?;
+        let output = String::from_utf8(output).unwrap();
+        Some(format!("{}\n{} {:?}", output, outcome.result, outcome.exit_code))
+    })?;
+    Ok(())
+}
+
+/// The traces print the arguments of the calls, cut at the depth
+/// that is given in the options.
+#[test]
+#[timeout(30000)]
+fn test_hvm_trace() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/trace"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let sender = session.diagnostic_sender.clone();
+        let options = TargetOptions {
+            trace_depth: Some(2),
+            ..Default::default()
+        };
+        let files = target::compile_with_target(&HvmTarget, book, &options, sender).ok()?;
+        Some(files[0].contents.clone())
+    })?;
+    Ok(())
+}
+
+/// Runs the book with the HVM target, returning what the traces
+/// printed followed by the result.
+fn run_traced(session: &Session, book: untyped::Book, options: &TargetOptions) -> Option<String> {
+    let capture = Arc::new(Mutex::new(Vec::new()));
+    let previous = std::io::set_output_capture(Some(capture.clone()));
+    let result = run_hvm(session, book, options);
+    std::io::set_output_capture(previous);
+
+    let log = String::from_utf8(capture.lock().unwrap().clone()).unwrap();
+    Some(format!("{}{}", log, result?.0))
+}
+
+/// The traces of the functions marked with `#trace` and, with a
+/// filter, only the ones of the functions that match it, even if
+/// others are marked.
+#[test]
+#[timeout(30000)]
+fn test_run_trace() -> Result<(), Error> {
+    test_kind2_golden(Path::new("./suite/trace"), "run.golden", |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+
+        let marked = run_traced(session, book.clone(), &TargetOptions::default())?;
+
+        let options = TargetOptions {
+            trace_filter: Some("List.*".to_string()),
+            trace_depth: Some(1),
+            ..Default::default()
+        };
+        let filtered = run_traced(session, book, &options)?;
+
+        assert!(
+            filtered.lines().all(|x| !x.starts_with("(Kind.Trace.Double")),
+            "Testing file '{}'",
+            path.display()
+        );
+
+        Some(format!("{}\n---\n{}", marked, filtered))
+    })?;
+    Ok(())
+}
+
+/// The profiler charges every rewrite of the program, except for the
+/// call of `Main`, to some function.
+#[test]
+#[timeout(30000)]
+fn test_run_profile() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/profile"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let options = TargetOptions::default();
+        let (result, rewrites, profile) = driver::profile_book(session, book, &options).ok()?;
+
+        let charged: u64 = profile.functions.iter().map(|x| x.1.rewrites).sum();
+        assert_eq!(charged + 1, rewrites, "Testing file '{}'", path.display());
+
+        Some(format!("{}\n{}\n{}", result, profile, profile.collapsed()))
+    })?;
+    Ok(())
+}
+
+/// The natural numbers compiled to numbers should give the same
+/// results as the unary ones.
+#[test]
+#[timeout(30000)]
+fn test_run_native_nat() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/nat"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+
+        let native = TargetOptions {
+            native_nat: [("hvm".to_string(), true)].into_iter().collect(),
+            ..Default::default()
+        };
+        let native = run_hvm(session, book.clone(), &native)?;
+        let unary = run_hvm(session, book, &TargetOptions::default())?;
+
+        assert_eq!(native.0, unary.0, "Testing file '{}'", path.display());
+
+        Some(native.0)
+    })?;
+    Ok(())
+}
+
+/// The partial functions over the natural numbers compiled to numbers
+/// get stuck on the numbers that no rule matches, and are reported
+/// at the function that the user wrote.
+#[test]
+#[timeout(30000)]
+fn test_run_native_nat_stuck() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/nat_stuck"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let options = TargetOptions {
+            native_nat: [("hvm".to_string(), true)].into_iter().collect(),
+            ..Default::default()
+        };
+        let (result, _) = driver::run_book(session, book, &options, Some(1)).ok()?;
+        Some(result)
+    })?;
+    test_kind2_golden(Path::new("./suite/nat_stuck"), "kdl.golden", |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        // The KDL compiles the natural numbers to native ones by default.
+        let options = TargetOptions::default();
+        let sender = session.diagnostic_sender.clone();
+        let files = target::compile_with_target(&KdlTarget, book, &options, sender).ok()?;
+        Some(files[0].contents.clone())
     })?;
     Ok(())
 }
@@ -128,12 +419,8 @@ fn test_eval_issues() -> Result<(), Error> {
 fn test_run_issues() -> Result<(), Error> {
     test_kind2(Path::new("./suite/issues/run"), |path, session| {
         let entrypoints = vec!["Main".to_string()];
-        let check = driver::erase_book(session, path, entrypoints)
-            .map(|file| driver::compile_book_to_hvm(file, false))
-            .map(|file| driver::execute_file(&file.to_string(), Some(1)))
-            .flatten();
-
-        check.ok().map(|x| x.0)
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        run_hvm(session, book, &TargetOptions::default()).map(|x| x.0)
     })?;
     Ok(())
 }
@@ -143,12 +430,406 @@ fn test_run_issues() -> Result<(), Error> {
 fn test_kdl() -> Result<(), Error> {
     test_kind2(Path::new("./suite/kdl"), |path, session| {
         let entrypoints = vec!["Main".to_string()];
-        let check = driver::compile_book_to_kdl(path, session, "", entrypoints);
-        check.ok().map(|x| x.to_string())
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let sender = session.diagnostic_sender.clone();
+        let options = TargetOptions::default();
+        let files = target::compile_with_target(&KdlTarget, book, &options, sender).ok()?;
+        Some(files[0].contents.clone())
+    })?;
+    Ok(())
+}
+
+/// The namespace leaves less letters for the names, so the long names
+/// are shortened more and can collide with each other.
+#[test]
+#[timeout(30000)]
+fn test_kdl_namespace() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/kdl_namespace"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let sender = session.diagnostic_sender.clone();
+        let options = TargetOptions {
+            namespace: "Contract".to_string(),
+            ..Default::default()
+        };
+        let files = target::compile_with_target(&KdlTarget, book, &options, sender).ok()?;
+        Some(files[0].contents.clone())
+    })?;
+    Ok(())
+}
+
+/// The manifest has the KDL name of each one of the compiled entries.
+#[test]
+#[timeout(30000)]
+fn test_kdl_manifest() -> Result<(), Error> {
+    test_kind2_golden(Path::new("./suite/kdl"), "manifest.golden", |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let options = TargetOptions {
+            name: "Main".to_string(),
+            ..Default::default()
+        };
+        let files = driver::build_book(session, path, "kdl", &options, entrypoints).ok()?;
+        let manifest = files.into_iter().find(|x| x.path.ends_with("Main.kdl.json"))?;
+        Some(manifest.contents)
+    })?;
+    Ok(())
+}
+
+/// The contracts have the functions after the ones that they call,
+/// the runs after all of the functions and a summary.
+#[test]
+#[timeout(30000)]
+fn test_kdl_contract() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/contract"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let options = TargetOptions::default();
+        let contract = driver::build_contract(session, path, &options, entrypoints).ok()?;
+        Some(format!("{}\n{}", contract.bundle(), contract))
+    })?;
+    Ok(())
+}
+
+#[test]
+#[timeout(30000)]
+fn test_kdl_stats() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/kdl_stats"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let options = TargetOptions::default();
+        let budgets = kind_target_kdl::Budgets {
+            max_size: Some(64),
+            max_total_size: Some(128),
+            max_rules: Some(2),
+            max_dups: Some(0),
+            max_name_len: Some(10),
+            deny: false,
+        };
+        let stats = driver::kdl_stats(session, path, &options, &budgets, entrypoints).ok()?;
+        Some(stats.to_string())
+    })?;
+    Ok(())
+}
+
+/// Prints a KDL term with its binders numbered in the order they're
+/// found and with the variables of the `dup`s replaced by the value that
+/// they duplicate, so linearizing a term doesn't change its form.
+fn kdl_form(term: &Term, env: &mut Vec<(Name, String)>, fresh: &mut usize) -> String {
+    let forms = |args: &[Term], env: &mut Vec<_>, fresh: &mut usize| {
+        args.iter().map(|x| format!(" {}", kdl_form(x, env, fresh))).collect::<String>()
+    };
+    match term {
+        Term::Var { name } => match env.iter().rev().find(|x| x.0 == *name) {
+            Some((_, form)) => form.clone(),
+            None => format!("free {}", name),
+        },
+        Term::Dup { nam0, nam1, expr, body } => {
+            let expr = kdl_form(expr, env, fresh);
+            env.push((*nam0, expr.clone()));
+            env.push((*nam1, expr));
+            let body = kdl_form(body, env, fresh);
+            env.truncate(env.len() - 2);
+            body
+        }
+        Term::Lam { name, body } => {
+            let var = format!("v{}", fresh);
+            *fresh += 1;
+            env.push((*name, var.clone()));
+            let body = kdl_form(body, env, fresh);
+            env.pop();
+            format!("@{} {}", var, body)
+        }
+        Term::App { func, argm } => {
+            format!("(!{} {})", kdl_form(func, env, fresh), kdl_form(argm, env, fresh))
+        }
+        Term::Ctr { name, args } => format!("{{{}{}}}", name, forms(args, env, fresh)),
+        Term::Fun { name, args } => format!("({}{})", name, forms(args, env, fresh)),
+        Term::Num { numb } => format!("#{}", numb),
+        Term::Op2 { oper, val0, val1 } => {
+            format!("({:?} {} {})", oper, kdl_form(val0, env, fresh), kdl_form(val1, env, fresh))
+        }
+    }
+}
+
+fn kdl_statement_form(statement: &Statement) -> String {
+    let mut fresh = 0;
+    match statement {
+        Statement::Fun { func, init, .. } => {
+            let mut forms = Vec::new();
+            for rule in &func.rules {
+                let mut env = Vec::new();
+                let mut bind = |term: &Term, env: &mut Vec<_>| {
+                    if let Term::Var { name } = term {
+                        env.push((*name, format!("v{}", fresh)));
+                        fresh += 1;
+                    }
+                };
+                if let Term::Fun { args, .. } = &rule.lhs {
+                    for arg in args {
+                        match arg {
+                            Term::Ctr { args, .. } => args.iter().for_each(|x| bind(x, &mut env)),
+                            _ => bind(arg, &mut env),
+                        }
+                    }
+                }
+                let lhs = kdl_form(&rule.lhs, &mut env, &mut 0);
+                forms.push(format!("{} = {}", lhs, kdl_form(&rule.rhs, &mut env, &mut fresh)));
+            }
+            if let Some(init) = init {
+                forms.push(kdl_form(init, &mut Vec::new(), &mut fresh));
+            }
+            forms.join("; ")
+        }
+        Statement::Run { expr, .. } => kdl_form(expr, &mut Vec::new(), &mut fresh),
+        _ => String::new(),
+    }
+}
+
+/// Number of terms of a statement without the `dup`s, so linearizing
+/// it only changes it if it adds `let`s.
+fn kdl_statement_terms(statement: &Statement) -> usize {
+    fn go(term: &Term) -> usize {
+        match term {
+            Term::Var { .. } | Term::Num { .. } => 1,
+            Term::Dup { expr, body, .. } => go(expr) + go(body) - 1,
+            Term::Lam { body, .. } => 1 + go(body),
+            Term::App { func, argm } => 1 + go(func) + go(argm),
+            Term::Ctr { args, .. } | Term::Fun { args, .. } => 1 + args.iter().map(go).sum::<usize>(),
+            Term::Op2 { val0, val1, .. } => 1 + go(val0) + go(val1),
+        }
+    }
+    match statement {
+        Statement::Fun { func, init, .. } => {
+            let rules = func.rules.iter().map(|x| go(&x.lhs) + go(&x.rhs));
+            rules.sum::<usize>() + init.as_ref().map(go).unwrap_or(0)
+        }
+        Statement::Run { expr, .. } => go(expr),
+        _ => 0,
+    }
+}
+
+#[test]
+#[timeout(30000)]
+fn test_kdl_linearize() -> Result<(), Error> {
+    test_kind2_golden(Path::new("./suite/kdl"), "linearize.golden", |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let options = TargetOptions::default();
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let book = target::lower_book(&KdlTarget, book, &options);
+        let sender = session.diagnostic_sender.clone();
+        let file = kind_target_kdl::compile_book_unlinearized(book, sender, "").ok()?;
+        let with_lets = kind_target_kdl::linearize_file_with_lets(file.clone());
+        let linear = kind_target_kdl::linearize_file(file.clone());
+
+        let statements = file.funs.values().chain(file.runs.values());
+        let linear_statements = linear.funs.values().chain(linear.runs.values());
+        for (statement, linear) in statements.zip(linear_statements) {
+            assert_eq!(kdl_statement_form(statement), kdl_statement_form(linear));
+            assert_eq!(kdl_statement_terms(statement), kdl_statement_terms(linear));
+        }
+
+        // Substituting the variables used once never makes a statement bigger
+        // than wrapping them in `let`s.
+        let before = kind_target_kdl::Stats::measure(&with_lets).statements;
+        let after = kind_target_kdl::Stats::measure(&linear).statements;
+        let mut res = String::new();
+        for (name, statement) in linear.funs.iter().chain(linear.runs.iter()) {
+            let before = before.iter().find(|x| x.kind_name == *name)?;
+            let after = after.iter().find(|x| x.kind_name == *name)?;
+            assert!(after.size <= before.size, "{} grew from {} to {} bytes", name, before.size, after.size);
+            res.push_str(&format!(
+                "{}: {} terms, {} dups, {} bytes ({} with lets)\n",
+                name,
+                kdl_statement_terms(statement),
+                after.dups,
+                after.size,
+                before.size
+            ));
+        }
+        Some(res)
+    })?;
+    Ok(())
+}
+
+#[test]
+fn test_kdl_linearize_dup_shadowing() {
+    // The `dup` shadows `b` only in its body, so the last `b` is still the
+    // parameter. Restoring it used to look up `a` twice and lose `b`.
+    let code = "fun (Foo b) { (Foo b) = {Pair dup a b = {Zero}; {Pair a b} b} }";
+    let statement = kindelia_lang::parser::parse_code(code).unwrap().remove(0);
+    let mut file = kind_target_kdl::File {
+        ctrs: Default::default(),
+        funs: Default::default(),
+        runs: Default::default(),
+        manifest: Default::default(),
+    };
+    file.funs.insert("Foo".to_string(), statement);
+    let linear = kind_target_kdl::linearize_file(file.clone());
+    assert_eq!(kdl_statement_form(&file.funs["Foo"]), kdl_statement_form(&linear.funs["Foo"]));
+}
+
+#[test]
+#[timeout(30000)]
+fn test_json() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/json"), |path, session| {
+        // Each stage loads the files again, so they use sessions of their own.
+        let new_session = || {
+            let root = PathBuf::from("./suite/lib").canonicalize().unwrap();
+            Session::new(root, std::sync::mpsc::channel().0, false)
+        };
+
+        let mut concrete_session = new_session();
+        let concrete = driver::to_book(&mut concrete_session, path).ok()?;
+        let mut desugared_session = new_session();
+        let desugared = driver::desugar_book(&mut desugared_session, path).ok()?;
+
+        let books = [
+            driver::json::book_to_json(&concrete_session, "concrete", &concrete).ok()?,
+            driver::json::book_to_json(&desugared_session, "desugared", &desugared).ok()?,
+        ];
+        for (json, stage) in books.iter().zip(["concrete", "desugared"]) {
+            let json: serde_json::Value = serde_json::from_str(json).unwrap();
+            assert_eq!(json["stage"], stage);
+            assert_eq!(json["version"], driver::json::SCHEMA_VERSION);
+        }
+
+        // Ranges without a place in the sources are not sent to the locator.
+        let ghost = kind_span::with_locator(Box::new(|_| unreachable!()), || {
+            serde_json::to_value(Range::ghost_range()).unwrap()
+        });
+        assert_eq!(ghost, serde_json::Value::Null);
+
+        let untyped = driver::erase_book(session, path, vec!["Main".to_string()]).ok()?;
+        driver::json::book_to_json(session, "untyped", &untyped).ok()
+    })?;
+    Ok(())
+}
+
+#[test]
+#[timeout(30000)]
+fn test_graph() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/graph"), |path, session| {
+        let graph = driver::dependency_graph(session, path).ok()?;
+        let double = driver::find_definition(session, &graph, "Double").ok()?;
+        let bool = driver::find_definition(session, &graph, "Bool").ok()?;
+        let in_cycles = graph.cycles().into_iter().flatten().collect();
+
+        let all = (0..graph.names.len()).collect();
+        let mut res = graph.to_dot(&all);
+        res.push_str(&graph.to_dot(&graph.reachable(double, false)));
+        res.push_str(&graph.to_dot(&graph.reachable(bool, true)));
+        res.push_str(&graph.to_json(session, &in_cycles).ok()?);
+        Some(res)
+    })?;
+    Ok(())
+}
+
+#[test]
+#[timeout(30000)]
+fn test_dead_code() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/dead_code"), |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let report = driver::dead_code(session, path, entrypoints);
+        report.map(|report| report.to_string()).ok()
     })?;
     Ok(())
 }
 
+#[test]
+#[timeout(30000)]
+fn test_why_relevant() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/why_relevant"), |path, session| {
+        let book = driver::desugar_book(session, path).ok()?;
+        let entrypoints = vec!["Main".to_string()];
+
+        // Each chain is sent as a diagnostic, so they make the golden file.
+        for name in ["Main", "Color.blue", "Data.Maybe.some", "Color"] {
+            erasure::relevance_chain(&book, session.diagnostic_sender.clone(), entrypoints.clone(), name);
+        }
+        Some("Ok!".to_string())
+    })?;
+    Ok(())
+}
+
+/// The programs are compiled to JavaScript and run in node, and they
+/// should give the same results as in the HVM. The modules are kept
+/// as goldens too.
+#[test]
+#[timeout(30000)]
+fn test_js() -> Result<(), Error> {
+    test_kind2_golden(Path::new("./suite/eval"), "js.golden", |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        let book = driver::erase_book(session, path, entrypoints).ok()?;
+        let options = TargetOptions::default();
+        let sender = session.diagnostic_sender.clone();
+        let files = target::compile_with_target(&JsTarget, book.clone(), &options, sender).ok()?;
+        let code = files[0].contents.clone();
+
+        if let Some(result) = run_js(path, &code) {
+            let (expected, _) = run_hvm(session, book, &options)?;
+            assert_eq!(expected, result, "Testing file '{}'", path.display());
+        }
+
+        Some(code)
+    })?;
+    Ok(())
+}
+
+/// Prints the result of a module like the HVM does, or the term that
+/// got stuck if no rule matched it.
+const JS_RUNNER: &str = r#"
+import { "Main" as main } from "./main.mjs";
+
+const show = (value) => {
+  switch (typeof value) {
+    case "bigint":
+      return value.toString();
+    case "string":
+      return `"${value}"`;
+  }
+  const fields = Object.keys(value).filter((key) => key !== "$").map((key) => value[key]);
+  if (value.$ === "Data.List.nil" || value.$ === "Data.List.cons") {
+    const items = [];
+    while (value.$ === "Data.List.cons") {
+      const [head, tail] = Object.keys(value).filter((key) => key !== "$");
+      items.push(show(value[head]));
+      value = value[tail];
+    }
+    return `[${items.join(", ")}]`;
+  }
+  return `(${[value.$, ...fields.map(show)].join(" ")})`;
+};
+
+try {
+  process.stdout.write(show(main()));
+} catch (error) {
+  if (!error.term) {
+    throw error;
+  }
+  process.stdout.write(show(error.term));
+}
+"#;
+
+/// Runs the `Main` of a compiled module in node. Nothing is run if
+/// node is not installed.
+fn run_js(path: &Path, code: &str) -> Option<String> {
+    let name = path.file_stem().unwrap().to_string_lossy();
+    let dir = std::env::temp_dir().join(format!("kind2-test-js-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.mjs"), code).unwrap();
+    fs::write(dir.join("run.mjs"), JS_RUNNER).unwrap();
+
+    let output = std::process::Command::new("node").arg(dir.join("run.mjs")).output();
+    let _ = fs::remove_dir_all(&dir);
+
+    let output = output.ok()?;
+    assert!(
+        output.status.success(),
+        "Running file '{}': {}",
+        path.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
 #[test]
 #[timeout(30000)]
 fn test_erasure() -> Result<(), Error> {
@@ -160,6 +841,21 @@ fn test_erasure() -> Result<(), Error> {
     Ok(())
 }
 
+/// With a threshold, `Pred` is inlined and `Dec`, that has the same
+/// size, is kept as a call because of its `#noinline`.
+#[test]
+#[timeout(30000)]
+fn test_erasure_inline_threshold() -> Result<(), Error> {
+    let file = Path::new("./suite/erasure/Inline.kind2");
+    test_kind2_golden(file, "threshold.golden", |path, session| {
+        let entrypoints = vec!["Main".to_string()];
+        session.optimizations.inline_threshold = Some(16);
+        let check = driver::erase_book(session, path, entrypoints).map(|file| file.to_string());
+        check.ok()
+    })?;
+    Ok(())
+}
+
 #[test]
 #[timeout(30000)]
 fn test_coverage() -> Result<(), Error> {
@@ -170,3 +866,76 @@ fn test_coverage() -> Result<(), Error> {
     })?;
     Ok(())
 }
+
+/// Derives `T.name`, a string with the name of the type.
+struct NameDerive;
+
+fn derive_name(range: Range, name: QualifiedIdent, type_name: &QualifiedIdent) -> Entry {
+    let string = |lit| {
+        Box::new(Expr {
+            data: ExprKind::Lit { lit },
+            range,
+        })
+    };
+
+    Entry {
+        name: name.clone(),
+        docs: vec![],
+        args: Default::default(),
+        typ: Expr::cons(QualifiedIdent::new_static("Data.String", None, range), vec![], range),
+        rules: vec![Box::new(Rule {
+            name,
+            pats: vec![],
+            body: string(Literal::String(type_name.to_string())),
+            range,
+        })],
+        range,
+        attrs: vec![],
+        generated_by: None,
+    }
+}
+
+impl CustomDerive for NameDerive {
+    fn derive_sum_type(&self, range: Range, sum: &SumTypeDecl) -> Option<DeriveResult> {
+        Some((vec![derive_name(range, sum.name.add_segment("name"), &sum.name)], vec![]))
+    }
+
+    fn derive_record_type(&self, range: Range, rec: &RecordDecl) -> Option<DeriveResult> {
+        Some((vec![derive_name(range, rec.name.add_segment("name"), &rec.name)], vec![]))
+    }
+}
+
+/// Derives a definition outside of the namespace of the type.
+struct OutsideDerive;
+
+impl CustomDerive for OutsideDerive {
+    fn derive_sum_type(&self, range: Range, sum: &SumTypeDecl) -> Option<DeriveResult> {
+        let outside = QualifiedIdent::new_static("Outside.name", None, range);
+        Some((vec![derive_name(range, outside, &sum.name)], vec![]))
+    }
+}
+
+/// Derives a definition that uses an unbound name.
+struct UnboundDerive;
+
+impl CustomDerive for UnboundDerive {
+    fn derive_sum_type(&self, range: Range, sum: &SumTypeDecl) -> Option<DeriveResult> {
+        let mut entry = derive_name(range, sum.name.add_segment("unbound"), &sum.name);
+        entry.typ = Expr::cons(QualifiedIdent::new_static("Unbound", None, range), vec![], range);
+        Some((vec![entry], vec![]))
+    }
+}
+
+#[test]
+#[timeout(30000)]
+fn test_custom_derive() -> Result<(), Error> {
+    test_kind2(Path::new("./suite/derive"), |path, session| {
+        session.derives.register("name", NameDerive);
+        session.derives.register("outside", OutsideDerive);
+        session.derives.register("unbound", UnboundDerive);
+        let entrypoints = vec!["Main".to_string()];
+        let check = driver::type_check_book(session, path, entrypoints, Some(1), true);
+        check.map(|_| "Ok!".to_string()).ok()
+    })?;
+    Ok(())
+}
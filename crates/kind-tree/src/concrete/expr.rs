@@ -8,11 +8,12 @@ use crate::symbol::{Ident, QualifiedIdent};
 use crate::Operator;
 
 use kind_span::{Locatable, Range};
+use serde::Serialize;
 use std::fmt::{Display, Error, Formatter};
 
 /// A binding express the positional or named argument of
 /// a constructor or function.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum Binding {
     Positional(Box<Expr>),
     Named(Range, Ident, Box<Expr>),
@@ -22,7 +23,7 @@ pub enum Binding {
 pub type Spine = Vec<Binding>;
 
 /// A binding that is used inside applications.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct AppBinding {
     pub data: Box<Expr>,
     pub erased: bool,
@@ -46,7 +47,7 @@ impl AppBinding {
 
 /// A case binding is a field or a rename of some field
 /// inside a match expression.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum CaseBinding {
     Field(Ident),
     Renamed(Ident, Ident),
@@ -56,7 +57,7 @@ pub enum CaseBinding {
 /// strutinizer, bindings to the names of each arguments and
 /// a right-hand side value. The ignore_rest flag useful to just
 /// fill all of the case bindings that are not used with a default name.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Case {
     pub constructor: Ident,
     pub bindings: Vec<CaseBinding>,
@@ -66,7 +67,7 @@ pub struct Case {
 
 /// A match block that will be desugared
 /// into an eliminator of a datatype.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Match {
     pub typ: QualifiedIdent,
     pub scrutinee: Ident,
@@ -77,7 +78,7 @@ pub struct Match {
 }
 
 /// Substitution
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Substitution {
     pub name: Ident,
     pub redx: usize,
@@ -85,7 +86,7 @@ pub struct Substitution {
     pub expr: Box<Expr>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum Literal {
     /// The universe of types (e.g. Type)
     Type,
@@ -111,13 +112,13 @@ pub enum Literal {
 
 /// A destruct of a single constructor. It's a flat destruct
 /// and just translates into a eliminator for records.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum Destruct {
     Destruct(Range, QualifiedIdent, Vec<CaseBinding>, Option<Range>),
     Ident(Ident),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum SttmKind {
     Expr(Box<Expr>, Box<Sttm>),
     Ask(Destruct, Box<Expr>, Box<Sttm>),
@@ -130,20 +131,20 @@ pub enum SttmKind {
 /// describes the idea of `sequence` inside a monad
 /// each monadic action contains a `next` element that is
 /// desugared into a 'monadic bind'.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Sttm {
     pub data: SttmKind,
     pub range: Range,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum SeqOperation {
     Set(Box<Expr>),
     Mut(Box<Expr>),
     Get,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct SeqRecord {
     pub typ: Box<Expr>,
     pub expr: Box<Expr>,
@@ -151,7 +152,7 @@ pub struct SeqRecord {
     pub operation: SeqOperation,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum ExprKind {
     /// Name of a variable
     Var { name: Ident },
@@ -232,7 +233,7 @@ pub enum ExprKind {
 }
 
 /// Describes a single expression inside Kind2.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Expr {
     pub data: ExprKind,
     pub range: Range,
@@ -12,6 +12,7 @@ use expr::Expr;
 use fxhash::FxHashMap;
 use kind_span::{Locatable, Range};
 use linked_hash_map::LinkedHashMap;
+use serde::Serialize;
 
 use self::pat::Pat;
 
@@ -22,7 +23,7 @@ pub mod visitor;
 pub use expr::*;
 
 /// A value of a attribute
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum AttributeStyle {
     Ident(Range, Ident),
     String(Range, String),
@@ -34,7 +35,7 @@ pub enum AttributeStyle {
 /// that usually is on the top of a declaration
 /// and can be attached to a function declaration
 /// it express some compiler properties
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Attribute {
     pub name: Ident,
     pub args: Vec<AttributeStyle>,
@@ -48,10 +49,13 @@ pub struct Attribute {
 /// compiled.
 /// hide: that express a implicit argument (that will
 /// be discovered through unification).
-#[derive(Clone, Debug)]
+/// instance: that express a hidden argument that is
+/// filled with an instance of a class (e.g. `[m: Monoid t]`).
+#[derive(Clone, Debug, Serialize)]
 pub struct Argument {
     pub hidden: bool,
     pub erased: bool,
+    pub instance: bool,
     pub name: Ident,
     pub typ: Option<Box<Expr>>,
     pub range: Range,
@@ -60,7 +64,7 @@ pub struct Argument {
 /// A rule is a equation that in the left-hand-side
 /// contains a list of patterns @pats@ and on the
 /// right hand side a value.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Rule {
     pub name: QualifiedIdent,
     pub pats: Vec<Box<Pat>>,
@@ -72,7 +76,7 @@ pub struct Rule {
 /// and has rules. The type of the function
 /// consists of the arguments @args@ and the
 /// return type @typ@.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Entry {
     pub name: QualifiedIdent,
     pub docs: Vec<String>,
@@ -86,7 +90,7 @@ pub struct Entry {
 
 /// A single cosntructor inside the algebraic data
 /// type definition.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Constructor {
     pub name: Ident,
     pub docs: Vec<String>,
@@ -97,7 +101,7 @@ pub struct Constructor {
 
 /// An algebraic data type definition that supports
 /// parametric and indexed data type definitions.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct SumTypeDecl {
     pub name: QualifiedIdent,
     pub docs: Vec<String>,
@@ -108,7 +112,7 @@ pub struct SumTypeDecl {
 }
 
 /// A single constructor data type.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct RecordDecl {
     pub name: QualifiedIdent,
     pub docs: Vec<String>,
@@ -132,7 +136,7 @@ impl RecordDecl {
 }
 
 /// All of the structures
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum TopLevel {
     SumType(SumTypeDecl),
     RecordType(RecordDecl),
@@ -169,20 +173,34 @@ impl TopLevel {
     }
 }
 
+/// A syntax macro that is declared at top level. Each call of
+/// it inside the module is replaced by the body with the
+/// parameters substituted by the arguments.
+#[derive(Clone, Debug, Serialize)]
+pub struct MacroDecl {
+    pub name: QualifiedIdent,
+    pub docs: Vec<String>,
+    pub params: Vec<Ident>,
+    pub body: Box<Expr>,
+    pub range: Range,
+}
+
 /// A module is a collection of top level entries
 /// that contains syntatic sugars. In the future
 /// it will contain a HashMap to local renames.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Module {
     pub entries: Vec<TopLevel>,
+    #[serde(serialize_with = "crate::serialize_sorted")]
     pub uses: FxHashMap<String, String>,
+    pub macros: Vec<MacroDecl>,
 }
 
 /// Metadata about entries, it's really useful when we
 /// are trying to desugar something that does not contains
 /// a lot of information like a record definition or a sum
 /// type definition.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct EntryMeta {
     pub hiddens: usize,
     pub erased: usize,
@@ -194,15 +212,17 @@ pub struct EntryMeta {
 
 /// A book stores definitions by name. It's generated
 /// by joining a bunch of books that are already resolved.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct Book {
     // Ordered hashset
     pub names: LinkedHashMap<String, QualifiedIdent>,
 
     // Probably deterministic order everytime
+    #[serde(serialize_with = "crate::serialize_sorted")]
     pub entries: FxHashMap<String, TopLevel>,
 
     // Stores some important information in order to desugarize
+    #[serde(serialize_with = "crate::serialize_sorted")]
     pub meta: FxHashMap<String, EntryMeta>,
 }
 
@@ -290,8 +310,27 @@ impl Display for TopLevel {
     }
 }
 
+impl Display for MacroDecl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        for doc in &self.docs {
+            writeln!(f, "/// {}", doc)?;
+        }
+        write!(f, "macro {}", self.name)?;
+        for param in &self.params {
+            write!(f, " {}", param)?;
+        }
+        writeln!(f, " {{
+  {}
+}}
+", self.body)
+    }
+}
+
 impl Display for Module {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        for mac in &self.macros {
+            write!(f, "{}", mac)?;
+        }
         for entr in &self.entries {
             write!(f, "{}", entr)?;
         }
@@ -311,6 +350,7 @@ impl Display for Book {
 impl Display for Argument {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         let (open, close) = match (self.erased, self.hidden) {
+            _ if self.instance => ("[", "]"),
             (false, false) => ("(", ")"),
             (false, true) => ("+<", ">"),
             (true, false) => ("-(", ")"),
@@ -587,6 +627,7 @@ impl Argument {
         Argument {
             hidden: false,
             erased: false,
+            instance: false,
             name,
             typ: Some(typ),
             range,
@@ -597,6 +638,7 @@ impl Argument {
         Argument {
             hidden: true,
             erased: true,
+            instance: false,
             name: self.name.clone(),
             typ: self.typ.clone(),
             range: self.range,
@@ -6,14 +6,15 @@
 use std::fmt::{Display, Error, Formatter};
 
 use kind_span::Range;
+use serde::Serialize;
 
 use crate::symbol::{Ident, QualifiedIdent};
 
 // Really useful thin layer on ident.
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub struct PatIdent(pub Ident);
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub enum PatKind {
     /// Name of a variable
     Var(PatIdent),
@@ -38,7 +39,7 @@ pub enum PatKind {
 }
 
 /// Describes a single `pattern`
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub struct Pat {
     pub data: PatKind,
     pub range: Range,
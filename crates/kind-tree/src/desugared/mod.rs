@@ -6,6 +6,7 @@ use std::fmt::{Display, Error, Formatter};
 use fxhash::FxHashMap;
 use kind_span::Range;
 use linked_hash_map::LinkedHashMap;
+use serde::Serialize;
 
 pub use crate::Operator;
 
@@ -20,13 +21,13 @@ use crate::{
 /// as ((((a b) c) d) e) that looks like a spine.
 pub type Spine = Vec<Box<Expr>>;
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub struct AppBinding {
     pub data: Box<Expr>,
     pub erased: bool,
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub enum ExprKind {
     /// Name of a variable
     Var { name: Ident },
@@ -95,7 +96,7 @@ pub enum ExprKind {
     Err,
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub struct Expr {
     pub data: ExprKind,
     pub range: Range,
@@ -310,7 +311,7 @@ impl Expr {
 /// compiled.
 /// hide: that express a implicit argument (that will
 /// be discovered through unification).
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Argument {
     pub hidden: bool,
     pub erased: bool,
@@ -322,7 +323,7 @@ pub struct Argument {
 /// A rule is a equation that in the left-hand-side
 /// contains a list of patterns @pats@ and on the
 /// right hand side a value.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Rule {
     pub name: QualifiedIdent,
     pub pats: Vec<Box<Expr>>,
@@ -334,7 +335,7 @@ pub struct Rule {
 /// and has rules. The type of the function
 /// consists of the arguments @args@ and the
 /// return type @typ@.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Entry {
     pub name: QualifiedIdent,
     pub args: Vec<Argument>,
@@ -345,7 +346,7 @@ pub struct Entry {
 }
 
 /// Type family information
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Family {
     pub name: QualifiedIdent,
     pub parameters: Telescope<Argument>,
@@ -353,10 +354,12 @@ pub struct Family {
 }
 
 /// A book is a collection of desugared entries.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct Book {
     pub entrs: LinkedHashMap<String, Box<Entry>>,
+    #[serde(serialize_with = "crate::serialize_sorted")]
     pub names: FxHashMap<String, usize>,
+    #[serde(serialize_with = "crate::serialize_sorted")]
     pub families: FxHashMap<String, Family>,
     pub holes: u64,
 }
@@ -24,13 +24,28 @@ pub mod symbol;
 
 use std::fmt::{Formatter, Display, Error};
 
+use fxhash::FxHashMap;
+use serde::{Serialize, Serializer};
+
 use symbol::Ident;
 
+/// Serializes a hash map sorted by its keys, so the serialization of
+/// a book doesn't depend on the order of the hashes.
+pub(crate) fn serialize_sorted<V: Serialize, S: Serializer>(
+    map: &FxHashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    serializer.collect_map(entries)
+}
+
 /// Attributes describes some compiler specific aspects
 /// like inlining and derivations.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct Attributes {
     pub inlined: bool,
+    pub noinline: bool,
     pub kdl_run: bool,
     pub kdl_erase: bool,
     pub kdl_name: Option<Ident>,
@@ -39,10 +54,14 @@ pub struct Attributes {
     pub keep: bool,
     pub partial: bool,
     pub axiom: bool,
+    pub enum_value: Option<u64>, // Number of a constructor of a type that derives `enum`
+    pub single_constructor: bool, // The only constructor of a type that can be unboxed
+    pub class: bool,
+    pub instance: bool,
 }
 
 /// Enum of binary operators.
-#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub enum Operator {
     Add,
     Sub,
@@ -1,6 +1,7 @@
 //! Describes identifiers and symbols inside the language.
 
 use kind_span::{Range, SyntaxCtxIndex};
+use serde::{Serialize, Serializer};
 use std::fmt::Display;
 use std::hash::Hash;
 
@@ -41,8 +42,14 @@ impl Hash for Symbol {
 
 impl Eq for Symbol {}
 
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.data)
+    }
+}
+
 /// Identifier inside a syntax context.
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub struct Ident {
     pub data: Symbol,
     pub range: Range,
@@ -51,7 +58,7 @@ pub struct Ident {
 
 /// Qualified Identifiers always refer to top level
 /// constructions.
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub struct QualifiedIdent {
     root: Symbol,
     aux: Option<Symbol>,
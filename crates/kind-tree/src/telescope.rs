@@ -1,6 +1,8 @@
+use serde::Serialize;
+
 /// A sequence of arguments that depends on the previous sequence
 /// it's similar to a iterated sigma type.
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize)]
 pub struct Telescope<T>(Vec<T>);
 
 impl<T> Default for Telescope<T> {
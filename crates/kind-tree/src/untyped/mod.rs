@@ -6,6 +6,7 @@ use std::fmt::{Display, Error, Formatter};
 use fxhash::FxHashMap;
 use kind_span::Range;
 use linked_hash_map::LinkedHashMap;
+use serde::Serialize;
 
 pub use crate::Operator;
 use crate::{
@@ -18,7 +19,7 @@ use crate::{
 /// as ((((a b) c) d) e) that looks like a spine.
 pub type Spine = Vec<Box<Expr>>;
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub enum ExprKind {
     /// Name of a variable
     Var {
@@ -73,7 +74,7 @@ pub enum ExprKind {
     Err,
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub struct Expr {
     pub data: ExprKind,
     pub range: Range,
@@ -168,7 +169,7 @@ impl Expr {
 /// compiled.
 /// hide: that express a implicit argument (that will
 /// be discovered through unification).
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Argument {
     pub hidden: bool,
     pub erased: bool,
@@ -180,7 +181,7 @@ pub struct Argument {
 /// A rule is a equation that in the left-hand-side
 /// contains a list of patterns @pats@ and on the
 /// right hand side a value.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Rule {
     pub name: QualifiedIdent,
     pub pats: Vec<Box<Expr>>,
@@ -192,7 +193,7 @@ pub struct Rule {
 /// and has rules. The type of the function
 /// consists of the arguments @args@ and the
 /// return type @typ@.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Entry {
     pub name: QualifiedIdent,
     pub args: Vec<(String, Range, bool)>,
@@ -202,9 +203,10 @@ pub struct Entry {
 }
 
 /// A book is a collection of desugared entries.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct Book {
     pub entrs: LinkedHashMap<String, Box<Entry>>,
+    #[serde(serialize_with = "crate::serialize_sorted")]
     pub names: FxHashMap<String, usize>,
 }
 